@@ -2,12 +2,12 @@ use color_eyre::{eyre::Report, Result};
 use std::path::Path;
 use svggloo::{
     setup,
-    template::{render, Exporter},
+    template::{render, Exporter, RenderOptions},
 };
 
 // The paths must be relative to the Cargo.toml file.
-const SVG_TEMPLATE_FILENAME: &'static str = "examples/brochure/brochure.svg";
-const OUTPUT_DIR: &'static str = "examples/brochure/output";
+const SVG_TEMPLATE_FILENAME: &str = "examples/brochure/brochure.svg";
+const OUTPUT_DIR: &str = "examples/brochure/output";
 
 fn main() -> Result<(), Report> {
     // Setup the application.
@@ -21,13 +21,12 @@ fn main() -> Result<(), Report> {
 
     // Render the template.
     let fields = vec![String::from("co"), String::from("st"), String::from("ci")];
-    let _ = render(
-        &svg_template,
-        output_dir,
-        Some(Exporter::Inkscape),
-        Some(fields),
-        None,
-    )?;
+    let options = RenderOptions {
+        exporter: Some(Exporter::Inkscape),
+        field_based_name: Some(fields),
+        ..Default::default()
+    };
+    render(svg_template, output_dir, &options)?;
 
     Ok(())
 }
@@ -2,12 +2,12 @@ use color_eyre::{eyre::Report, Result};
 use std::path::Path;
 use svggloo::{
     setup,
-    template::{render, Exporter},
+    template::{render, Exporter, RenderOptions},
 };
 
 // The paths must be relative to the Cargo.toml file.
-const SVG_TEMPLATE_FILENAME: &'static str = "examples/quantifier/bike_lane_categories.svg";
-const OUTPUT_DIR: &'static str = "examples/quantifier/output";
+const SVG_TEMPLATE_FILENAME: &str = "examples/quantifier/bike_lane_categories.svg";
+const OUTPUT_DIR: &str = "examples/quantifier/output";
 
 fn main() -> Result<(), Report> {
     // Setup the application.
@@ -25,13 +25,12 @@ fn main() -> Result<(), Report> {
         String::from("state"),
         String::from("city"),
     ];
-    let _ = render(
-        &svg_template,
-        output_dir,
-        Some(Exporter::Inkscape),
-        Some(fields),
-        None,
-    )?;
+    let options = RenderOptions {
+        exporter: Some(Exporter::Inkscape),
+        field_based_name: Some(fields),
+        ..Default::default()
+    };
+    render(svg_template, output_dir, &options)?;
 
     Ok(())
 }
@@ -0,0 +1,117 @@
+use color_eyre::{eyre::Report, Result};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Marker of the temporary files, only left behind by an interrupted run.
+const MARKER: &str = ".svggloo-tmp";
+
+/// Temporary file next to a file, hidden and keeping its extension, e.g.
+/// `.austin.svggloo-tmp.svg` for `austin.svg`.
+///
+/// The temporary files of an SVG file and of its PDF export differ only by
+/// their extensions, like the files themselves.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!(".{}{}.{}", stem, MARKER, extension.to_string_lossy()),
+        None => format!(".{}{}", stem, MARKER),
+    };
+    path.with_file_name(name)
+}
+
+/// Write a file atomically, i.e. into a temporary file of the same directory
+/// renamed once complete, so that the file is either complete or absent, even
+/// when the process is killed.
+pub fn write_atomic<F>(path: &Path, write: F) -> Result<(), Report>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), Report>,
+{
+    write_atomic_checked(path, write, |_| Ok(()))
+}
+
+/// Write a file atomically, like [`write_atomic`], once the temporary file
+/// passes a check, e.g. that the written document is well-formed.
+pub fn write_atomic_checked<F, C>(path: &Path, write: F, check: C) -> Result<(), Report>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), Report>,
+    C: FnOnce(&Path) -> Result<(), Report>,
+{
+    let temp = temp_path(path);
+    let written = File::create(&temp)
+        .map_err(Report::from)
+        .and_then(|file| {
+            let mut file = BufWriter::new(file);
+            write(&mut file)?;
+            Ok(file.flush()?)
+        })
+        .and_then(|_| check(&temp))
+        .and_then(|_| Ok(fs::rename(&temp, path)?));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    written
+}
+
+/// Copy a file atomically.
+pub fn copy_atomic(src: &Path, dst: &Path) -> Result<(), Report> {
+    write_atomic(dst, |out| {
+        io::copy(&mut File::open(src)?, out)?;
+        Ok(())
+    })
+}
+
+/// Remove the temporary files left in a directory by an interrupted run, and
+/// return their number.
+pub fn clean(dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_temp(path) && path.is_file())
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count()
+}
+
+/// Check whether a file is a temporary file.
+fn is_temp(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| name.starts_with('.') && name.contains(MARKER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = std::env::temp_dir().join("svggloo-test-atomic");
+        fs::create_dir_all(&dir).unwrap();
+        let svg = dir.join("austin.svg");
+        assert_eq!(temp_path(&svg), dir.join(".austin.svggloo-tmp.svg"));
+        assert_eq!(
+            temp_path(&svg).with_extension("pdf"),
+            temp_path(&svg.with_extension("pdf"))
+        );
+
+        write_atomic(&svg, |out| Ok(out.write_all(b"<svg/>")?)).unwrap();
+        assert_eq!(fs::read_to_string(&svg).unwrap(), "<svg/>");
+        let failed = write_atomic(&svg, |out| {
+            out.write_all(b"<sv")?;
+            Err(Report::msg("interrupted"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(&svg).unwrap(), "<svg/>");
+        assert!(!temp_path(&svg).exists());
+
+        fs::write(temp_path(&svg), "<sv").unwrap();
+        assert_eq!(clean(&dir), 1);
+        assert!(svg.is_file());
+    }
+}
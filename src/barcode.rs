@@ -0,0 +1,241 @@
+use std::{fmt, str::FromStr};
+
+/// Widths of the bars and spaces of the Code 128 symbols, by value, starting
+/// with a bar. The last symbol is the stop symbol.
+const CODE128: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+
+/// Value of the start symbol of the Code 128 code set B, encoding ASCII texts.
+const START_B: usize = 104;
+/// Value of the start symbol of the Code 128 code set C, encoding pairs of
+/// digits.
+const START_C: usize = 105;
+/// Value of the Code 128 stop symbol.
+const STOP: usize = 106;
+
+/// Modules of the EAN-13 digits of the left-hand odd parity (L) code, the
+/// even parity (G) and right-hand (R) codes being derived from them.
+const EAN_DIGITS: [&str; 10] = [
+    "0001101", "0011001", "0010011", "0111101", "0100011", "0110001", "0101111", "0111011",
+    "0110111", "0001011",
+];
+
+/// Parities of the digits of the left half of an EAN-13 code, encoding its
+/// first digit.
+const EAN_PARITIES: [&str; 10] = [
+    "LLLLLL", "LLGLGG", "LLGGLG", "LLGGGL", "LGLLGG", "LGGLLG", "LGGGLL", "LGLGLG", "LGLGGL",
+    "LGGLGL",
+];
+
+/// Barcode symbology.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Symbology {
+    /// Code 128, encoding ASCII texts such as record identifiers.
+    #[default]
+    Code128,
+    /// EAN-13, encoding 12 digits and a check digit.
+    Ean13,
+}
+
+impl FromStr for Symbology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "code128" => Ok(Self::Code128),
+            "ean" | "ean13" => Ok(Self::Ean13),
+            _ => Err(format!(
+                "unknown barcode type {:?}, expected code128 or ean13",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Symbology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Code128 => "code128",
+            Self::Ean13 => "ean13",
+        })
+    }
+}
+
+/// Encode a value as the modules of a barcode, `true` for the dark ones.
+///
+/// The quiet zones around the barcode, of at least 10 modules, are not
+/// included.
+pub fn encode(value: &str, symbology: Symbology) -> Result<Vec<bool>, String> {
+    match symbology {
+        Symbology::Code128 => code128(value),
+        Symbology::Ean13 => ean13(value),
+    }
+}
+
+/// Encode a value as a Code 128 barcode.
+///
+/// Even numbers of digits are encoded by pairs with the code set C, the other
+/// values with the code set B.
+fn code128(value: &str) -> Result<Vec<bool>, String> {
+    if value.is_empty() {
+        return Err(String::from("cannot encode an empty value as a barcode"));
+    }
+    let pairs = value.as_bytes().chunks_exact(2);
+    let symbols: Vec<usize> =
+        if pairs.remainder().is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+            std::iter::once(START_C)
+                .chain(pairs.map(|pair| ((pair[0] - b'0') * 10 + pair[1] - b'0') as usize))
+                .collect()
+        } else {
+            let values = value
+                .chars()
+                .map(|c| match c {
+                    ' '..='~' => Ok(c as usize - ' ' as usize),
+                    _ => Err(format!("cannot encode {:?} as a Code 128 barcode", c)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            std::iter::once(START_B).chain(values).collect()
+        };
+    let checksum = symbols
+        .iter()
+        .enumerate()
+        .map(|(i, symbol)| i.max(1) * symbol)
+        .sum::<usize>()
+        % 103;
+
+    let mut modules = Vec::new();
+    for symbol in symbols.into_iter().chain([checksum, STOP]) {
+        for (i, width) in CODE128[symbol].bytes().enumerate() {
+            let dark = i % 2 == 0;
+            for _ in 0..width - b'0' {
+                modules.push(dark);
+            }
+        }
+    }
+    Ok(modules)
+}
+
+/// Check digit of the first 12 digits of an EAN-13 code.
+fn ean_check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, digit)| *digit as u32 * if i % 2 == 0 { 1 } else { 3 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// Swap the dark and light modules of an EAN-13 pattern.
+fn complement(pattern: &str) -> String {
+    pattern
+        .chars()
+        .map(|c| if c == '1' { '0' } else { '1' })
+        .collect()
+}
+
+/// Encode 12 digits, or 13 digits with a valid check digit, as an EAN-13
+/// barcode.
+fn ean13(value: &str) -> Result<Vec<bool>, String> {
+    let mut digits: Vec<u8> = value
+        .trim()
+        .bytes()
+        .map(|b| match b.is_ascii_digit() {
+            true => Ok(b - b'0'),
+            false => Err(format!("{:?} is not a valid EAN-13 code", value)),
+        })
+        .collect::<Result<_, _>>()?;
+    let check = match digits.len() {
+        12 | 13 => ean_check_digit(&digits[..12]),
+        _ => return Err(format!("{:?} is not a valid EAN-13 code", value)),
+    };
+    match digits.get(12) {
+        Some(digit) if *digit != check => {
+            return Err(format!(
+                "{:?} has an invalid check digit, expected {}",
+                value, check
+            ))
+        }
+        Some(_) => (),
+        None => digits.push(check),
+    }
+
+    let mut modules = Vec::new();
+    let mut push = |pattern: &str| modules.extend(pattern.bytes().map(|b| b == b'1'));
+    push("101");
+    for (digit, parity) in digits[1..7]
+        .iter()
+        .zip(EAN_PARITIES[digits[0] as usize].chars())
+    {
+        let left = EAN_DIGITS[*digit as usize];
+        match parity {
+            // The G code is the reversed R code, i.e. the reversed complement.
+            'G' => push(&complement(left).chars().rev().collect::<String>()),
+            _ => push(left),
+        }
+    }
+    push("01010");
+    for digit in &digits[7..] {
+        push(&complement(EAN_DIGITS[*digit as usize]));
+    }
+    push("101");
+    Ok(modules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write modules as `1` and `0`.
+    fn bits(modules: &[bool]) -> String {
+        modules.iter().map(|m| if *m { '1' } else { '0' }).collect()
+    }
+
+    #[test]
+    fn test_code128() {
+        assert!(CODE128[..STOP].iter().all(|s| s
+            .bytes()
+            .map(|b| (b - b'0') as usize)
+            .sum::<usize>()
+            == 11));
+        // Start B, 7 characters, the checksum and the stop symbol.
+        let modules = encode("PJJ123C", Symbology::Code128).unwrap();
+        assert_eq!(modules.len(), 9 * 11 + 13);
+        assert!(bits(&modules).starts_with("11010010000"));
+        // 104 + 48 + 2 * 42 + 3 * 42 + 4 * 17 + 5 * 18 + 6 * 19 + 7 * 35 = 55 mod 103
+        assert_eq!(&bits(&modules)[88..99], "11101000110");
+        assert!(bits(&modules).ends_with("1100011101011"));
+        // Start C, 3 pairs of digits, the checksum and the stop symbol.
+        assert_eq!(
+            encode("123456", Symbology::Code128).unwrap().len(),
+            5 * 11 + 13
+        );
+        assert!(encode("", Symbology::Code128).is_err());
+        assert!(encode("café", Symbology::Code128).is_err());
+    }
+
+    #[test]
+    fn test_ean13() {
+        let modules = encode("400638133393", Symbology::Ean13).unwrap();
+        assert_eq!(modules, encode("4006381333931", Symbology::Ean13).unwrap());
+        assert_eq!(
+            bits(&modules),
+            "10100011010100111010111101111010001001011001101010\
+             100001010000101000010111010010000101100110101"
+        );
+        assert!(encode("4006381333932", Symbology::Ean13).is_err());
+        assert!(encode("40063813", Symbology::Ean13).is_err());
+        assert_eq!("EAN-13".parse(), Ok(Symbology::Ean13));
+    }
+}
@@ -0,0 +1,138 @@
+use color_eyre::{eyre::Report, Result};
+use regex::bytes::Regex;
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    ops::Range,
+    str,
+};
+
+/// Minimum size of the data URIs kept out of a large template.
+pub const MIN_BLOB_SIZE: usize = 16 * 1024;
+
+/// Start of the placeholders of the blobs.
+///
+/// The placeholders are data URIs themselves, so that the SVG processing, e.g.
+/// the embedding of the linked images, leaves them alone.
+const PREFIX: &str = "data:application/x-svggloo-blob,";
+
+/// Length of the hash ending the placeholders.
+const HASH_LEN: usize = 16;
+
+/// Large static blocks of a template, such as the embedded images, kept out of
+/// its source and spliced back in the rendered files as they are written.
+///
+/// The blocks are held once, as ranges of the original template, instead of
+/// being copied in the compiled template and in every rendered file.
+#[derive(Debug, Default)]
+pub struct Blobs {
+    data: Vec<u8>,
+    ranges: HashMap<String, Range<usize>>,
+}
+
+impl Blobs {
+    /// Replace the data URIs of at least [`MIN_BLOB_SIZE`] bytes of a template
+    /// with placeholders, and return the source left along with the blobs.
+    ///
+    /// The placeholders are named after the content of the blobs, so that the
+    /// source changes when a blob does.
+    pub fn extract(template: Vec<u8>) -> Result<(String, Self), Report> {
+        let data_uri = Regex::new(r"data:[\w.+-]+/[\w.+-]+;base64,[A-Za-z0-9+/=\s]+").unwrap();
+        let mut source = String::new();
+        let mut ranges = HashMap::new();
+        let mut last = 0;
+        for m in data_uri.find_iter(&template) {
+            if m.len() < MIN_BLOB_SIZE {
+                continue;
+            }
+            source.push_str(str::from_utf8(&template[last..m.start()])?);
+            let mut hasher = DefaultHasher::new();
+            m.as_bytes().hash(&mut hasher);
+            let placeholder = format!("{}{:0width$x}", PREFIX, hasher.finish(), width = HASH_LEN);
+            source.push_str(&placeholder);
+            ranges.insert(placeholder, m.range());
+            last = m.end();
+        }
+        source.push_str(str::from_utf8(&template[last..])?);
+
+        Ok((
+            source,
+            Self {
+                data: template,
+                ranges,
+            },
+        ))
+    }
+
+    /// Write a rendered file, splicing the blobs back in.
+    pub fn write(&self, svg: &str, out: &mut impl Write) -> io::Result<()> {
+        let mut rest = svg;
+        while let Some(start) = rest.find(PREFIX) {
+            let end = (start + PREFIX.len() + HASH_LEN).min(rest.len());
+            match rest.get(start..end).and_then(|p| self.ranges.get(p)) {
+                Some(range) => {
+                    out.write_all(&rest.as_bytes()[..start])?;
+                    out.write_all(&self.data[range.clone()])?;
+                    rest = &rest[end..];
+                }
+                None => {
+                    let end = start + PREFIX.len();
+                    out.write_all(&rest.as_bytes()[..end])?;
+                    rest = &rest[end..];
+                }
+            }
+        }
+        out.write_all(rest.as_bytes())
+    }
+
+    /// Splice the blobs back in a rendered file, in memory.
+    pub fn splice<'a>(&self, svg: &'a str) -> Cow<'a, str> {
+        if self.ranges.is_empty() || !svg.contains(PREFIX) {
+            return Cow::Borrowed(svg);
+        }
+        let mut spliced = Vec::new();
+        self.write(svg, &mut spliced)
+            .expect("writing to memory cannot fail");
+        // The blobs are ASCII, cut at ASCII boundaries.
+        Cow::Owned(String::from_utf8(spliced).expect("valid UTF-8"))
+    }
+
+    /// Number of blobs.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Whether no blob was extracted.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blobs() {
+        let image = format!("data:image/png;base64,{}", "iVBORw0K".repeat(4096));
+        let template = format!(
+            r#"<svg><image href="{}"/><image href="data:image/png;base64,AAAA"/><text>{{{{ city }}}}</text><image href="{}"/></svg>"#,
+            image, image
+        );
+        let (source, blobs) = Blobs::extract(template.clone().into_bytes()).unwrap();
+        assert_eq!(blobs.len(), 1);
+        assert!(source.len() < 300);
+        assert!(source.contains("base64,AAAA"));
+        assert_eq!(source.matches(PREFIX).count(), 2);
+
+        let rendered = source.replace("{{ city }}", "Austin");
+        assert_eq!(
+            blobs.splice(&rendered),
+            template.replace("{{ city }}", "Austin")
+        );
+        let unknown = format!("<svg>{}0123</svg>", PREFIX);
+        assert_eq!(blobs.splice(&unknown), unknown);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::atomic::copy_atomic;
+use color_eyre::{eyre::Report, Result};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Content-addressed cache of the generated files, shared across runs.
+///
+/// Each entry is named after a key identifying everything the output depends
+/// on, and holds the rendered SVG along with the exported PDF, if any.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Use a cache directory, created on the first store.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// Path of the cached file of an entry, with an extension.
+    fn path(&self, key: &str, extension: &str) -> PathBuf {
+        self.dir.join(key).with_extension(extension)
+    }
+
+    /// Return the rendered SVG of an entry, if the entry is complete.
+    ///
+    /// An entry is complete when it has an exported PDF, or when no export is
+    /// expected.
+    pub fn get(&self, key: &str, exported: bool) -> Option<String> {
+        if exported && !self.path(key, "pdf").is_file() {
+            return None;
+        }
+        fs::read_to_string(self.path(key, "svg")).ok()
+    }
+
+    /// Store a rendered SVG file, and its exported PDF if expected.
+    ///
+    /// The files are copied, so that the entry is not altered when the output
+    /// files are overwritten in place.
+    pub fn store(&self, key: &str, svg: &Path, exported: bool) -> Result<(), Report> {
+        fs::create_dir_all(&self.dir)?;
+        if exported {
+            copy_atomic(&svg.with_extension("pdf"), &self.path(key, "pdf"))?;
+        }
+        copy_atomic(svg, &self.path(key, "svg"))?;
+
+        Ok(())
+    }
+
+    /// Restore the exported PDF of an entry.
+    ///
+    /// The PDF is hard-linked when possible and `link` is set. It must then be
+    /// removed rather than overwritten in place, see [`discard`].
+    pub fn restore(&self, key: &str, pdf: &Path, link: bool) -> Result<(), Report> {
+        match link {
+            true => link_or_copy(&self.path(key, "pdf"), pdf)?,
+            false => {
+                discard(pdf)?;
+                copy_atomic(&self.path(key, "pdf"), pdf)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Remove a file, if it exists.
+///
+/// An output file restored from the cache must be discarded before being
+/// generated again, since it may share its content with the cache entry.
+pub fn discard(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Hard-link a file, or copy it when it cannot be linked, e.g. across file
+/// systems.
+fn link_or_copy(src: &Path, dst: &Path) -> Result<(), Report> {
+    discard(dst)?;
+    match fs::hard_link(src, dst) {
+        Ok(()) => Ok(()),
+        Err(_) => copy_atomic(src, dst),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join("svggloo-test-cache");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let svg = dir.join("austin.svg");
+        fs::write(&svg, "<svg/>").unwrap();
+        fs::write(svg.with_extension("pdf"), "%PDF").unwrap();
+
+        let cache = Cache::new(&dir.join("cache"));
+        assert_eq!(cache.get("0123", false), None);
+        cache.store("0123", &svg, true).unwrap();
+        assert_eq!(cache.get("0123", true).as_deref(), Some("<svg/>"));
+        assert_eq!(cache.get("4567", true), None);
+
+        let pdf = dir.join("dallas.pdf");
+        cache.restore("0123", &pdf, true).unwrap();
+        assert_eq!(fs::read_to_string(pdf).unwrap(), "%PDF");
+    }
+}
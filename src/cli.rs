@@ -1,32 +1,766 @@
-use crate::template::Exporter;
-use clap::{crate_name, Parser, ValueHint};
+use crate::{
+    data::{Keep, Running, SortKey},
+    export::{ExportArea, Exporter},
+    hook::PostCommand,
+    map::DEFAULT_MAP_PROVIDER,
+    pdf::{Optimization, PdfStandard},
+    print::Duplex,
+    reader::InputEncoding,
+    schema::OnInvalid,
+    svg::{PageFit, PageSize},
+    template::{OnEmptyName, DEFAULT_MAX_OUTPUTS},
+    upload::Destination,
+};
+use clap::{crate_name, ArgAction, Args, Parser, Subcommand, ValueHint};
 use std::path::PathBuf;
 
 // Main options.
 #[derive(Parser, Debug)]
-#[clap(name = crate_name!(), author, about, version)]
+#[command(
+    name = crate_name!(),
+    author,
+    about,
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub struct Opts {
     /// Sets the verbosity level
-    #[clap(short, long, parse(from_occurrences))]
+    #[arg(short, long, action = ArgAction::Count, global = true)]
     pub verbose: u8,
-    /// Specify the data fields to use to generate the rendered template name
-    // Due to a bug in clap parser, we cannot use a `Option<Vec<String>>` with
-    // multiple values. Therefore we are allowing multiple occurences with one
-    // single value.
-    // Ref: https://github.com/clap-rs/clap/issues/1772
-    // Ref: https://github.com/clap-rs/clap/issues/3066
-    #[clap(long, multiple_occurrences(true), number_of_values = 1)]
+    /// Suppress all output but the errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Disable the colors of the error reports, as does the NO_COLOR
+    /// environment variable
+    #[arg(long, global = true)]
+    pub no_color: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    // Rendering is the default command.
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+// Subcommands.
+// The enum is parsed only once, the size of the rendering options is fine.
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Render a template (default command)
+    Render(RenderArgs),
+    /// Create a new project with a starter template and data
+    Init(InitArgs),
+    /// Generate sample data for a template
+    Fake(FakeArgs),
+    /// Compare the rendered files with golden files
+    Snapshot(SnapshotArgs),
+    /// Compare the rendered files of two output directories
+    Diff(DiffArgs),
+    /// Check the files of an output directory against the checksums of its
+    /// manifest
+    Verify(VerifyArgs),
+    /// Report the common problems of a template
+    Lint(LintArgs),
+    /// Review the records in a terminal interface and render the selected ones
+    Tui(TuiArgs),
+    /// Serve the rendering of a template over HTTP
+    ServeApi(ServeApiArgs),
+    /// Process the job files of a spool directory
+    Queue(QueueArgs),
+}
+
+// Rendering options.
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// Specify the data fields to use to generate the rendered template name,
+    /// as a comma-separated list, space-separated values or repeated; end the
+    /// space-separated values with -- when the template follows them
+    #[arg(long, num_args = 1.., value_delimiter = ',', action = ArgAction::Append)]
     pub field: Option<Vec<String>>,
-    /// Specify the template
-    #[clap(parse(from_os_str), value_hint = ValueHint::FilePath)]
-    pub template: PathBuf,
+    /// Specify the template, or - to read it from the standard input
+    // Optional only to let the subcommands take precedence over the default
+    // command.
+    #[arg(
+        value_hint = ValueHint::FilePath,
+        required_unless_present = "list_exporters"
+    )]
+    pub template: Option<PathBuf>,
     /// Specify the output directory
-    #[clap(parse(from_os_str), value_hint = ValueHint::DirPath, default_value = "output")]
+    #[arg(value_hint = ValueHint::DirPath, default_value = "output")]
     pub output_dir: PathBuf,
+    /// Specify the separator [default: -]
+    #[arg(short, long)]
+    pub separator: Option<String>,
+    /// Transliterate the names of the rendered files to ASCII, e.g. São Paulo
+    /// to sao_paulo
+    #[arg(long)]
+    pub ascii_names: bool,
+    /// Suffix the names shared by several records, including the ones differing
+    /// only by case, with a number instead of failing
+    #[arg(long)]
+    pub disambiguate_names: bool,
+    /// Specify what to do with the records whose naming fields are empty
+    #[arg(long, value_enum, default_value = "fail")]
+    pub on_empty_name: OnEmptyName,
+    /// Specify the placeholder of the empty naming fields, with
+    /// --on-empty-name placeholder
+    #[arg(long, default_value = "unnamed", value_name = "TEXT")]
+    pub name_placeholder: String,
+    /// Export the rendered template as PDF
+    #[arg(short, long, value_enum)]
+    pub exporter: Option<Exporter>,
+    /// Specify the data field naming the exporter of each record, overriding
+    /// the default exporter
+    #[arg(long, value_name = "COLUMN")]
+    pub exporter_column: Option<String>,
+    /// List the exporters with their path and version, and the one the current
+    /// configuration selects
+    #[arg(long)]
+    pub list_exporters: bool,
+    /// Optimize the exported files with Ghostscript, for the screen, ebooks or
+    /// prepress
+    #[arg(long, value_enum, value_name = "PRESET")]
+    pub optimize_pdf: Option<Optimization>,
+    /// Convert the exported files to a PDF standard with Ghostscript
+    #[arg(long, value_enum, value_name = "STANDARD")]
+    pub pdf_standard: Option<PdfStandard>,
+    /// Specify the ICC profile of the output intent, required by the PDF
+    /// standards, e.g. an sRGB profile for PDF/A-2b
+    #[arg(long, value_hint = ValueHint::FilePath, requires = "pdf_standard")]
+    pub icc_profile: Option<PathBuf>,
+    /// Encrypt the exported files with this password
+    #[arg(long, env = "SVGGLOO_PDF_PASSWORD", hide_env_values = true)]
+    pub pdf_password: Option<String>,
+    /// Specify the data field holding the password of each exported file
+    #[arg(long, value_name = "COLUMN")]
+    pub pdf_password_column: Option<String>,
+    /// Sign the exported files with the certificate of this PKCS#12 file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub sign_cert: Option<PathBuf>,
+    /// Specify the passphrase of the PKCS#12 file
+    #[arg(
+        long,
+        env = "SVGGLOO_SIGN_PASS",
+        hide_env_values = true,
+        requires = "sign_cert"
+    )]
+    pub sign_pass: Option<String>,
+    /// Specify the static map provider URL template used by `map_image`
+    #[arg(long, default_value = DEFAULT_MAP_PROVIDER)]
+    pub map_provider: String,
+    /// Specify the API key of the static map provider
+    #[arg(long, env = "SVGGLOO_MAP_API_KEY", hide_env_values = true)]
+    pub map_api_key: Option<String>,
+    /// Specify the directory where the map snapshots are cached
+    #[arg(long, value_hint = ValueHint::DirPath, default_value = ".svggloo-cache/maps")]
+    pub map_cache_dir: PathBuf,
+    /// Specify the directory containing the icons used by `icon`
+    #[arg(long, value_hint = ValueHint::DirPath, default_value = "icons")]
+    pub icon_dir: PathBuf,
+    /// Specify the theme files to render each record with (glob patterns are
+    /// accepted)
+    #[arg(long)]
+    pub theme: Vec<String>,
+    /// Specify the locales to render each record in, as a comma-separated list
+    #[arg(long, value_delimiter = ',')]
+    pub locales: Vec<String>,
+    /// Specify the data field listing the locales of each record
+    #[arg(long)]
+    pub locale_column: Option<String>,
+    /// Show or hide the layers matching boolean data fields
+    #[arg(long)]
+    pub toggle_layers: bool,
+    /// Strip the Inkscape/Sodipodi editor metadata from the rendered files
+    #[arg(long)]
+    pub strip_metadata: bool,
+    /// Interpolate the data values verbatim instead of escaping them for XML
+    #[arg(long)]
+    pub no_autoescape: bool,
+    /// Keep the newline following the block tags, such as {% if %}
+    #[arg(long)]
+    pub no_trim_blocks: bool,
+    /// Keep the spaces preceding the block tags on their line
+    #[arg(long)]
+    pub no_lstrip_blocks: bool,
+    /// Skip the well-formedness check of the rendered files
+    #[arg(long)]
+    pub no_validate: bool,
+    /// Reshape long data into one record per key, with a column for each name
+    /// of the NAMES column holding the value of the VALUES column
+    #[arg(long, value_name = "NAMES:VALUES", value_parser = parse_pivot, requires = "pivot_key")]
+    pub pivot: Option<(String, String)>,
+    /// Specify the columns identifying the records of the reshaped data, as a
+    /// comma-separated list
+    #[arg(long, value_name = "COLUMN", value_delimiter = ',', requires = "pivot")]
+    pub pivot_key: Vec<String>,
+    /// Render every combination of the records with the records of this data
+    /// file, such as logo variants
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub cross: Option<PathBuf>,
+    /// Compute a column from an expression on the other columns, e.g.
+    /// 'density = population / area'
+    #[arg(long, value_name = "NAME=EXPRESSION", value_parser = parse_key_value)]
+    pub compute: Vec<(String, String)>,
+    /// Parse the JSON arrays and objects of these columns, as a
+    /// comma-separated list
+    #[arg(long, value_name = "COLUMN", value_delimiter = ',')]
+    pub parse_json_columns: Vec<String>,
+    /// Nest the values of the dotted columns into objects, e.g. scores.network
+    /// into scores
+    #[arg(long)]
+    pub nest_columns: bool,
+    /// Expose all the records to the templates as _all
+    #[arg(long)]
+    pub expose_all: bool,
+    /// Expose the other records sharing the value of this column to the
+    /// templates as _siblings
+    #[arg(long, value_name = "COLUMN")]
+    pub siblings_by: Option<String>,
+    /// Expose the previous and the next records of the data file to the
+    /// templates as _prev and _next
+    #[arg(long)]
+    pub expose_neighbors: bool,
+    /// Inject a CSS stylesheet into every rendered file
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub inject_css: Option<PathBuf>,
+    /// Embed the linked images into the rendered files
+    #[arg(long)]
+    pub embed_images: bool,
+    /// Resize the rendered files to a page size (letter, legal, tabloid, a3,
+    /// a4, a5, or WIDTHxHEIGHT with a unit, e.g. 8.5x11in)
+    #[arg(long)]
+    pub page_size: Option<PageSize>,
+    /// Specify how the content is fitted into the page size
+    #[arg(long, value_enum, default_value = "scale")]
+    pub page_fit: PageFit,
+    /// Scale the canvas of the rendered files
+    #[arg(long)]
+    pub scale: Option<f64>,
+    /// Specify the data field holding the title of each document
+    #[arg(long, value_name = "COLUMN")]
+    pub title_column: Option<String>,
+    /// Specify the language of the documents, e.g. en-US
+    #[arg(long)]
+    pub lang: Option<String>,
+    /// Specify the data field holding the language of each document
+    #[arg(long, value_name = "COLUMN")]
+    pub lang_column: Option<String>,
+    /// Specify the data field holding the alternative text of the image with
+    /// this id
+    #[arg(long, value_name = "ID=COLUMN", value_parser = parse_key_value)]
+    pub image_alt: Vec<(String, String)>,
+    /// Stamp a watermark text diagonally across the rendered files
+    #[arg(long, value_name = "TEXT")]
+    pub watermark_text: Option<String>,
+    /// Composite an SVG overlay over the rendered files
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub overlay: Option<PathBuf>,
+    /// Specify the area to export: drawing (Inkscape only), page, or a custom
+    /// x:y:width:height area in user units
+    #[arg(long)]
+    pub export_area: Option<ExportArea>,
+    /// Specify the data file, either a path or an http(s) URL, required when
+    /// the template is read from the standard input [default: the CSV file
+    /// next to the template]
+    #[arg(long, value_name = "PATH|URL", conflicts_with = "set")]
+    pub data: Option<String>,
+    /// Read the records from a Google Sheets spreadsheet, by URL or ID,
+    /// instead of the data file (requires the sheets feature)
+    #[arg(long, value_name = "URL|ID", conflicts_with_all = ["data", "set"])]
+    pub sheet: Option<String>,
+    /// Specify the range of the spreadsheet to read, e.g. 'Cities!A1:F'
+    /// [default: the first sheet]
+    #[arg(long, requires = "sheet")]
+    pub sheet_range: Option<String>,
+    /// Specify the API key reading the publicly shared spreadsheets
+    #[arg(long, env = "SVGGLOO_SHEETS_API_KEY", hide_env_values = true)]
+    pub sheets_api_key: Option<String>,
+    /// Specify the JSON key file of the service account reading the
+    /// spreadsheets shared with it
+    #[arg(long, env = "GOOGLE_APPLICATION_CREDENTIALS", value_hint = ValueHint::FilePath)]
+    pub sheets_credentials: Option<PathBuf>,
+    /// Specify the directory where the remote data files are cached
+    #[arg(long, value_hint = ValueHint::DirPath, default_value = ".svggloo-cache/data")]
+    pub data_cache_dir: PathBuf,
+    /// Specify the field delimiter of the data file, \t for tabs [default:
+    /// detected]
+    #[arg(long, value_name = "CHAR", value_parser = parse_delimiter)]
+    pub delimiter: Option<u8>,
+    /// Specify the character encoding of the data file, such as windows-1252,
+    /// or auto to detect it [default: utf-8]
+    #[arg(long, value_name = "LABEL")]
+    pub encoding: Option<InputEncoding>,
+    /// Lowercase the headers of the data file, and replace their spaces with
+    /// underscores
+    #[arg(long)]
+    pub normalize_headers: bool,
+    /// Rename the duplicate headers of the data file (name, name_2) and the
+    /// empty ones (column_4), instead of failing
+    #[arg(long)]
+    pub rename_headers: bool,
+    /// Render a single record from KEY=VALUE pairs instead of the data file
+    #[arg(long, value_parser = parse_key_value)]
+    pub set: Vec<(String, String)>,
+    /// Write the single rendered file to the standard output, the selection
+    /// having to produce a single output
+    #[arg(long, conflicts_with_all = ["output_file", "exporter", "exporter_column"])]
+    pub stdout: bool,
+    /// Write the single rendered file to this path
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub output_file: Option<PathBuf>,
+    /// Specify the schema file the data are validated against
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub schema: Option<PathBuf>,
+    /// Specify what to do with the records violating the schema
+    #[arg(long, value_enum, default_value = "fail")]
+    pub on_invalid: OnInvalid,
+    /// Only render the records matching COLUMN=VALUE
+    #[arg(long, value_parser = parse_key_value)]
+    pub only: Vec<(String, String)>,
+    /// Render only once the records sharing the same values in these columns,
+    /// as a comma-separated list
+    #[arg(long, value_delimiter = ',')]
+    pub dedupe_by: Vec<String>,
+    /// Specify which occurrence of the duplicated records is kept
+    #[arg(long, value_enum, default_value = "first")]
+    pub dedupe_keep: Keep,
+    /// Sort the records by these columns, as a comma-separated list of COLUMN
+    /// or COLUMN:desc
+    #[arg(long, value_delimiter = ',')]
+    pub sort_by: Vec<SortKey>,
+    /// Expose the position of the records in the selection to the templates
+    /// as _rank
+    #[arg(long)]
+    pub rank: bool,
+    /// Expose running aggregates of these columns over the selection to the
+    /// templates as _running, as a comma-separated list of COLUMN or
+    /// COLUMN:sum|mean|min|max|count
+    #[arg(long, value_delimiter = ',', value_name = "COLUMN[:AGGREGATE]")]
+    pub running: Vec<Running>,
+    /// Skip the first N records
+    #[arg(long, default_value = "0")]
+    pub offset: usize,
+    /// Render at most N records
+    #[arg(long)]
+    pub limit: Option<usize>,
+    /// Render a random sample of N records
+    #[arg(long)]
+    pub sample: Option<usize>,
+    /// Specify the seed of the random sample
+    #[arg(long, requires = "sample")]
+    pub seed: Option<u64>,
+    /// Generate an HTML gallery of the rendered files in the output directory
+    #[arg(long)]
+    pub gallery: bool,
+    /// Generate PNG thumbnails of this width in the thumbs/ subdirectory
+    #[arg(long, value_name = "WIDTH")]
+    pub thumbnails: Option<u32>,
+    /// Upload the generated files to this destination: s3://bucket/prefix/
+    /// (requires the s3 feature), sftp://user@host/path or
+    /// ftp://user@host/path
+    #[arg(long)]
+    pub upload: Option<Destination>,
+    /// Specify the content type of the uploaded files with this EXTENSION=TYPE
+    #[arg(long, value_parser = parse_key_value, requires = "upload")]
+    pub content_type: Vec<(String, String)>,
+    /// Specify the number of files uploaded simultaneously
+    #[arg(long, default_value = "4")]
+    pub upload_concurrency: usize,
+    /// POST a JSON summary of the run to this URL when it finishes
+    #[arg(long, value_hint = ValueHint::Url)]
+    pub notify_url: Option<String>,
+    /// Email the generated file of each record to the address of this column
+    #[arg(long, value_name = "COLUMN", requires_all = ["smtp", "email_from"])]
+    pub email_column: Option<String>,
+    /// Specify the SMTP server: smtp://host[:port] or smtps://host[:port]
+    #[arg(long, value_hint = ValueHint::Url, requires = "email_column")]
+    pub smtp: Option<String>,
+    /// Specify the sender of the emails
+    #[arg(long, value_name = "ADDRESS", requires = "email_column")]
+    pub email_from: Option<String>,
+    /// Specify the template of the email subject
+    #[arg(long, default_value = "Your document")]
+    pub email_subject: String,
+    /// Specify the template of the email body
+    #[arg(long, default_value = "Please find your document attached.")]
+    pub email_body: String,
+    /// Send at most N emails per minute
+    #[arg(long, value_name = "N", requires = "email_column")]
+    pub email_rate: Option<u32>,
+    /// Report the emails instead of sending them
+    #[arg(long, requires = "email_column")]
+    pub email_dry_run: bool,
+    /// Reuse the files generated by previous runs from this cache directory
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    pub cache_dir: Option<PathBuf>,
+    /// Skip the files already produced by an interrupted run
+    #[arg(long, conflicts_with_all = ["stdout", "output_file"])]
+    pub resume: bool,
+    /// Warn instead of failing when the data has no records
+    #[arg(long)]
+    pub allow_empty: bool,
+    /// Specify a command run for each produced file, with the {input},
+    /// {output} and {column} placeholders
+    #[arg(long, value_name = "COMMAND")]
+    pub post_cmd: Option<PostCommand>,
+    /// Print the exported files with lp
+    #[arg(long)]
+    pub print: bool,
+    /// Specify the printer queue, instead of the default one
+    #[arg(long, value_name = "NAME", requires = "print")]
+    pub printer: Option<String>,
+    /// Specify the number of copies of each printed file
+    #[arg(long, default_value = "1")]
+    pub copies: u32,
+    /// Specify how the pages are printed on the sheets
+    #[arg(long, value_enum, requires = "print")]
+    pub duplex: Option<Duplex>,
+    /// Print the time spent reading the data, rendering, writing and
+    /// exporting, per stage and per file
+    #[arg(long)]
+    pub profile: bool,
+    /// Write the timings in the Chrome tracing format to this file, implies
+    /// --profile
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "FILE")]
+    pub profile_trace: Option<PathBuf>,
+    /// Keep the large embedded images of the template out of memory until the
+    /// rendered files are written, to bound the memory used by the templates
+    /// of tens of megabytes
+    #[arg(long)]
+    pub large_template: bool,
+    /// Specify the number of records rendered concurrently
+    #[arg(long, default_value = "1", value_name = "N")]
+    pub render_jobs: usize,
+    /// Specify the maximum number of outputs, the run being aborted before
+    /// writing anything when it would produce more
+    #[arg(long, default_value_t = DEFAULT_MAX_OUTPUTS, value_name = "N")]
+    pub max_outputs: usize,
+    /// Specify the number of exporter processes run concurrently, while the
+    /// records are still being rendered
+    #[arg(long, default_value = "1", value_name = "N")]
+    pub export_jobs: usize,
+    /// Specify the niceness of the exporter processes, from 0 to 19, to lower
+    /// their priority
+    #[arg(long, value_name = "LEVEL", value_parser = parse_nice)]
+    pub export_nice: Option<u8>,
+    /// Run the exporter processes in the idle I/O scheduling class (Linux)
+    #[arg(long)]
+    pub export_idle_io: bool,
+    /// Specify the CPUs the exporter processes are restricted to, e.g. 0-3,6
+    /// (Linux)
+    #[arg(long, value_name = "LIST", value_parser = parse_cpus)]
+    pub export_cpus: Option<String>,
+    /// Do not check that the output volume has enough space for the run
+    #[arg(long)]
+    pub no_space_check: bool,
+    /// Record the SHA-256 checksums of the generated files in the manifest
+    #[arg(long)]
+    pub checksums: bool,
+    /// Append the pages of the exported files to this PDF report, skipping
+    /// the records appended by the previous runs
+    #[arg(long, value_hint = ValueHint::FilePath, value_name = "FILE")]
+    pub append_to: Option<PathBuf>,
+    /// Stamp "Page X of Y" on the pages of the report appended to
+    #[arg(long, requires = "append_to")]
+    pub page_numbers: bool,
+    /// Add a bookmark per record to the report appended to, titled after its
+    /// naming fields
+    #[arg(long, requires = "append_to")]
+    pub outline: bool,
+    /// Impose the report appended to into a booklet for saddle-stitch
+    /// printing, with its pages in the order of the printer spreads
+    #[arg(long, requires = "append_to")]
+    pub booklet: bool,
+    /// Specify the size of the sheets of the booklet, placing the two pages of
+    /// each spread side by side, e.g. tabloid or 11x17in
+    #[arg(long, value_name = "SIZE", requires = "booklet")]
+    pub booklet_sheet: Option<PageSize>,
+}
+
+// Sample data generation options.
+#[derive(Args, Debug)]
+pub struct FakeArgs {
+    /// Specify the template
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub template: PathBuf,
+    /// Specify the number of records to generate
+    #[arg(long, default_value = "10")]
+    pub rows: usize,
+    /// Specify the seed of the generator
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Write the data to this file instead of the standard output
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+// Snapshot testing options.
+#[derive(Args, Debug)]
+pub struct SnapshotArgs {
+    /// Specify the template
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub template: PathBuf,
+    /// Specify the data file [default: the CSV file next to the template]
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub data: Option<PathBuf>,
+    /// Specify the field delimiter of the data file, \t for tabs [default:
+    /// detected]
+    #[arg(long, value_name = "CHAR", value_parser = parse_delimiter)]
+    pub delimiter: Option<u8>,
+    /// Specify the character encoding of the data file, such as windows-1252,
+    /// or auto to detect it [default: utf-8]
+    #[arg(long, value_name = "LABEL")]
+    pub encoding: Option<InputEncoding>,
+    /// Lowercase the headers of the data file, and replace their spaces with
+    /// underscores
+    #[arg(long)]
+    pub normalize_headers: bool,
+    /// Rename the duplicate headers of the data file (name, name_2) and the
+    /// empty ones (column_4), instead of failing
+    #[arg(long)]
+    pub rename_headers: bool,
+    /// Specify the directory containing the golden files
+    #[arg(long, value_hint = ValueHint::DirPath, default_value = "snapshots")]
+    pub golden_dir: PathBuf,
+    /// Replace the golden files with the rendered files
+    #[arg(long)]
+    pub update: bool,
+    /// Specify the data fields to use to generate the rendered template name,
+    /// as a comma-separated list, space-separated values or repeated; end the
+    /// space-separated values with -- when the template follows them
+    #[arg(long, num_args = 1.., value_delimiter = ',', action = ArgAction::Append)]
+    pub field: Option<Vec<String>>,
     /// Specify the separator
-    #[clap(short, long, default_value = "-")]
+    #[arg(short, long, default_value = "-")]
     pub separator: String,
+}
+
+// Output comparison options.
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Specify the old output directory
+    #[arg(value_hint = ValueHint::DirPath)]
+    pub old_dir: PathBuf,
+    /// Specify the new output directory
+    #[arg(value_hint = ValueHint::DirPath)]
+    pub new_dir: PathBuf,
+    /// Specify the path of the HTML report
+    #[arg(long, value_hint = ValueHint::FilePath, default_value = "diff.html")]
+    pub report: PathBuf,
+    /// Rasterize the modified files to compare their pixels
+    #[arg(long)]
+    pub rasterize: bool,
+    /// Specify the fraction of pixels allowed to differ
+    #[arg(long, requires = "rasterize", default_value = "0")]
+    pub tolerance: f64,
+}
+
+// Verification options.
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Specify the output directory
+    #[arg(value_hint = ValueHint::DirPath, default_value = "output")]
+    pub output_dir: PathBuf,
+}
+
+// Template linting options.
+#[derive(Args, Debug)]
+pub struct LintArgs {
+    /// Specify the template
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub template: PathBuf,
+    /// Specify the data file [default: the CSV file next to the template]
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub data: Option<PathBuf>,
+}
+
+// Terminal interface options.
+#[derive(Args, Debug)]
+pub struct TuiArgs {
+    /// Specify the template
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub template: PathBuf,
+    /// Specify the output directory
+    #[arg(value_hint = ValueHint::DirPath, default_value = "output")]
+    pub output_dir: PathBuf,
+    /// Specify the data fields to use to generate the rendered template name,
+    /// as a comma-separated list, space-separated values or repeated; end the
+    /// space-separated values with -- when the template follows them
+    #[arg(long, num_args = 1.., value_delimiter = ',', action = ArgAction::Append)]
+    pub field: Option<Vec<String>>,
+    /// Specify the separator [default: -]
+    #[arg(short, long)]
+    pub separator: Option<String>,
     /// Export the rendered template as PDF
-    #[clap(short, long, arg_enum)]
+    #[arg(short, long, value_enum)]
     pub exporter: Option<Exporter>,
 }
+
+// Project creation options.
+#[derive(Args, Debug)]
+pub struct InitArgs {
+    /// Specify the directory of the project
+    #[arg(value_hint = ValueHint::DirPath)]
+    pub directory: PathBuf,
+}
+
+// HTTP API options.
+#[derive(Args, Debug)]
+pub struct ServeApiArgs {
+    /// Specify the template
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub template: PathBuf,
+    /// Specify the address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub bind: String,
+    /// Specify the exporter used to render PDF files
+    #[arg(short, long, value_enum)]
+    pub exporter: Option<Exporter>,
+    /// Specify the data fields to use to generate the rendered template name,
+    /// as a comma-separated list, space-separated values or repeated; end the
+    /// space-separated values with -- when the template follows them
+    #[arg(long, num_args = 1.., value_delimiter = ',', action = ArgAction::Append)]
+    pub field: Option<Vec<String>>,
+}
+
+// Job queue options.
+#[derive(Args, Debug)]
+pub struct QueueArgs {
+    /// Specify the spool directory
+    #[arg(value_hint = ValueHint::DirPath)]
+    pub spool: PathBuf,
+    /// Specify the number of seconds between two scans of the pending jobs
+    #[arg(long, default_value = "5")]
+    pub interval: u64,
+    /// Stop once there are no more pending jobs
+    #[arg(long)]
+    pub once: bool,
+    /// Specify the field delimiter of the data file, \t for tabs [default:
+    /// detected]
+    #[arg(long, value_name = "CHAR", value_parser = parse_delimiter)]
+    pub delimiter: Option<u8>,
+    /// Specify the character encoding of the data file, such as windows-1252,
+    /// or auto to detect it [default: utf-8]
+    #[arg(long, value_name = "LABEL")]
+    pub encoding: Option<InputEncoding>,
+    /// Lowercase the headers of the data file, and replace their spaces with
+    /// underscores
+    #[arg(long)]
+    pub normalize_headers: bool,
+    /// Rename the duplicate headers of the data file (name, name_2) and the
+    /// empty ones (column_4), instead of failing
+    #[arg(long)]
+    pub rename_headers: bool,
+    /// Reuse the files generated by previous jobs from this cache directory
+    #[arg(long, value_hint = ValueHint::DirPath)]
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// Parse a field delimiter: a single ASCII character, or `\t` or `tab` for a
+/// tabulation.
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" | "tab" | "\t" => Ok(b'\t'),
+        s if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        s => Err(format!("invalid delimiter {:?}", s)),
+    }
+}
+
+/// Parse a `KEY=VALUE` pair.
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.trim().to_owned(), value.to_owned()))
+        .filter(|(key, _)| !key.is_empty())
+        .ok_or_else(|| format!("invalid KEY=VALUE pair: {:?}", s))
+}
+
+/// Parse a `NAMES:VALUES` pair of columns.
+fn parse_pivot(s: &str) -> Result<(String, String), String> {
+    s.split_once(':')
+        .map(|(names, values)| (names.trim().to_owned(), values.trim().to_owned()))
+        .filter(|(names, values)| !names.is_empty() && !values.is_empty())
+        .ok_or_else(|| format!("invalid NAMES:VALUES pair: {:?}", s))
+}
+
+/// Parse a niceness, from 0 to 19.
+fn parse_nice(s: &str) -> Result<u8, String> {
+    s.parse::<u8>()
+        .ok()
+        .filter(|nice| *nice <= 19)
+        .ok_or_else(|| format!("invalid niceness {:?}, expected 0 to 19", s))
+}
+
+/// Parse a list of CPUs, like `0-3,6`.
+fn parse_cpus(s: &str) -> Result<String, String> {
+    let valid = s.split(',').all(|range| {
+        let mut bounds = range.splitn(2, '-');
+        bounds.all(|bound| !bound.is_empty() && bound.bytes().all(|b| b.is_ascii_digit()))
+    });
+    match valid {
+        true => Ok(s.to_owned()),
+        false => Err(format!("invalid CPU list {:?}", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_opts() {
+        Opts::command().debug_assert();
+    }
+
+    #[test]
+    fn test_field_values() {
+        let opts = Opts::parse_from([
+            "svggloo",
+            "template.svg",
+            "--field",
+            "country,state",
+            "--field",
+            "city",
+        ]);
+        assert_eq!(opts.render.field.unwrap(), ["country", "state", "city"]);
+
+        let opts = Opts::parse_from([
+            "svggloo",
+            "template.svg",
+            "--field",
+            "country",
+            "state",
+            "city",
+        ]);
+        assert_eq!(opts.render.field.unwrap(), ["country", "state", "city"]);
+
+        // The space-separated values are ended with `--` when the template and
+        // the output directory follow them.
+        let opts = Opts::parse_from([
+            "svggloo",
+            "--field",
+            "country",
+            "--field",
+            "state",
+            "city",
+            "--",
+            "quantifier/bike_lane_categories.svg",
+            "out",
+        ]);
+        assert_eq!(opts.render.field.unwrap(), ["country", "state", "city"]);
+        assert_eq!(
+            opts.render.template.unwrap(),
+            PathBuf::from("quantifier/bike_lane_categories.svg")
+        );
+        assert_eq!(opts.render.output_dir, PathBuf::from("out"));
+    }
+
+    #[test]
+    fn test_parse_cpus() {
+        assert_eq!(parse_cpus("0-3,6").unwrap(), "0-3,6");
+        assert!(parse_cpus("0-").is_err());
+        assert!(parse_cpus("a,1").is_err());
+        assert!(parse_nice("20").is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use std::{fmt, str::FromStr};
+
+/// Basic CSS color keywords.
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("black", [0, 0, 0]),
+    ("silver", [192, 192, 192]),
+    ("gray", [128, 128, 128]),
+    ("grey", [128, 128, 128]),
+    ("white", [255, 255, 255]),
+    ("maroon", [128, 0, 0]),
+    ("red", [255, 0, 0]),
+    ("purple", [128, 0, 128]),
+    ("fuchsia", [255, 0, 255]),
+    ("green", [0, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("olive", [128, 128, 0]),
+    ("yellow", [255, 255, 0]),
+    ("navy", [0, 0, 128]),
+    ("blue", [0, 0, 255]),
+    ("teal", [0, 128, 128]),
+    ("aqua", [0, 255, 255]),
+    ("orange", [255, 165, 0]),
+];
+
+/// An sRGB color, with an opacity between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: f64,
+}
+
+impl Color {
+    /// Create an opaque color.
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha: 1.0,
+        }
+    }
+
+    /// Relative luminance of the color, as defined by WCAG 2, between 0 for
+    /// black and 1 for white.
+    pub fn luminance(&self) -> f64 {
+        let linear = |channel: u8| {
+            let c = channel as f64 / 255.0;
+            match c <= 0.03928 {
+                true => c / 12.92,
+                false => ((c + 0.055) / 1.055).powf(2.4),
+            }
+        };
+        0.2126 * linear(self.red) + 0.7152 * linear(self.green) + 0.0722 * linear(self.blue)
+    }
+
+    /// Contrast ratio between two colors, as defined by WCAG 2, between 1 and
+    /// 21.
+    pub fn contrast(&self, other: &Color) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// Hue (in degrees), saturation and lightness (between 0 and 1) of the
+    /// color.
+    fn hsl(&self) -> (f64, f64, f64) {
+        let [r, g, b] = [self.red, self.green, self.blue].map(|c| c as f64 / 255.0);
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let lightness = (max + min) / 2.0;
+        let delta = max - min;
+        if delta == 0.0 {
+            return (0.0, 0.0, lightness);
+        }
+        let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+        let hue = if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        (hue, saturation, lightness)
+    }
+
+    /// Create a color from its hue, saturation and lightness, keeping an
+    /// opacity.
+    fn from_hsl(hue: f64, saturation: f64, lightness: f64, alpha: f64) -> Self {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+        let (r, g, b) = match (hue / 60.0) as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = lightness - chroma / 2.0;
+        let channel = |c: f64| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+        Self {
+            alpha,
+            ..Self::rgb(channel(r), channel(g), channel(b))
+        }
+    }
+
+    /// Lighten the color by `amount` percentage points of lightness, or darken
+    /// it with a negative amount.
+    pub fn lighten(&self, amount: f64) -> Self {
+        let (hue, saturation, lightness) = self.hsl();
+        let lightness = (lightness + amount / 100.0).clamp(0.0, 1.0);
+        Self::from_hsl(hue, saturation, lightness, self.alpha)
+    }
+
+    /// Mix two colors, `t` being the weight of the `other` color, between 0 and
+    /// 1.
+    pub fn mix(&self, other: &Color, t: f64) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        let channel = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Self {
+            red: channel(self.red, other.red),
+            green: channel(self.green, other.green),
+            blue: channel(self.blue, other.blue),
+            alpha: self.alpha + (other.alpha - self.alpha) * t,
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Parse a color: `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb(r, g, b)`,
+    /// `rgba(r, g, b, a)` or a basic CSS color name such as `navy`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid color {:?}", s);
+        let color = s.trim().to_lowercase();
+        if let Some(hex) = color.strip_prefix('#') {
+            let digits: Vec<u8> = hex
+                .chars()
+                .map(|c| c.to_digit(16).map(|d| d as u8))
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?;
+            let channels: Vec<u8> = match digits.len() {
+                3 | 4 => digits.iter().map(|d| d * 17).collect(),
+                6 | 8 => digits.chunks(2).map(|c| c[0] * 16 + c[1]).collect(),
+                _ => return Err(invalid()),
+            };
+            return Ok(Self {
+                red: channels[0],
+                green: channels[1],
+                blue: channels[2],
+                alpha: channels.get(3).map_or(1.0, |a| *a as f64 / 255.0),
+            });
+        }
+        if let Some(arguments) = color
+            .strip_prefix("rgba(")
+            .or_else(|| color.strip_prefix("rgb("))
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            let values: Vec<f64> = arguments
+                .split(',')
+                .map(|value| value.trim().parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid())?;
+            let channel = |value: f64| value.round().clamp(0.0, 255.0) as u8;
+            return match values[..] {
+                [red, green, blue] => Ok(Self::rgb(channel(red), channel(green), channel(blue))),
+                [red, green, blue, alpha] => Ok(Self {
+                    alpha: alpha.clamp(0.0, 1.0),
+                    ..Self::rgb(channel(red), channel(green), channel(blue))
+                }),
+                _ => Err(invalid()),
+            };
+        }
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| *name == color)
+            .map(|(_, [red, green, blue])| Self::rgb(*red, *green, *blue))
+            .ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for Color {
+    /// Format the color as `#rrggbb` when it is opaque, or as
+    /// `rgba(r, g, b, a)` otherwise.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let color = match self.alpha >= 1.0 {
+            true => format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue),
+            false => format!(
+                "rgba({}, {}, {}, {})",
+                self.red,
+                self.green,
+                self.blue,
+                (self.alpha * 1000.0).round() / 1000.0
+            ),
+        };
+        f.pad(&color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        let navy = Color::rgb(0, 0, 128);
+        assert_eq!("#000080".parse::<Color>(), Ok(navy));
+        assert_eq!("Navy".parse::<Color>(), Ok(navy));
+        assert_eq!("rgb(0, 0, 128)".parse::<Color>(), Ok(navy));
+        assert_eq!("#fff".parse::<Color>().unwrap().to_string(), "#ffffff");
+        assert_eq!(
+            "#00008080".parse::<Color>().unwrap().to_string(),
+            "rgba(0, 0, 128, 0.502)"
+        );
+        assert!("#12345".parse::<Color>().is_err());
+        assert!("rgb(1, 2)".parse::<Color>().is_err());
+
+        let (black, white) = (Color::rgb(0, 0, 0), Color::rgb(255, 255, 255));
+        assert!((black.contrast(&white) - 21.0).abs() < 1e-9);
+        assert_eq!(black.mix(&white, 0.5).to_string(), "#808080");
+        let red = Color::rgb(255, 0, 0);
+        assert_eq!(red.lighten(20.0).to_string(), "#ff6666");
+        assert_eq!(red.lighten(-20.0).to_string(), "#990000");
+        let blue = "#3b82f6".parse::<Color>().unwrap();
+        assert_eq!(blue.lighten(0.0), blue);
+    }
+}
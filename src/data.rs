@@ -0,0 +1,679 @@
+use crate::template::Record;
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use minijinja::{Environment, Value};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+/// Which occurrence of duplicated records is kept.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Keep {
+    /// Keep the first occurrence.
+    First,
+    /// Keep the last occurrence.
+    Last,
+}
+
+/// A sort key: a column, sorted in ascending or descending order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    /// Parse a sort key: `column`, `column:asc` or `column:desc`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, descending) = match s.rsplit_once(':') {
+            Some((column, "asc")) => (column, false),
+            Some((column, "desc")) => (column, true),
+            Some(_) => return Err(format!("invalid sort order in {:?}", s)),
+            None => (s, false),
+        };
+        if column.trim().is_empty() {
+            return Err(format!("invalid sort key {:?}", s));
+        }
+        Ok(Self {
+            column: column.trim().to_owned(),
+            descending,
+        })
+    }
+}
+
+/// Select the records matching the filters.
+///
+/// A filter is a `(column, value)` pair. The records are selected if, for each
+/// filtered column, their value is one of the filter values of this column.
+/// In other words, the filters of a same column are combined with OR, and the
+/// filters of different columns with AND.
+///
+/// The records are designated by their `indices` in `records`, and the indices
+/// of the selected records are returned.
+pub fn select(
+    records: &[Record],
+    indices: Vec<usize>,
+    filters: &[(String, String)],
+) -> Result<Vec<usize>, Report> {
+    if filters.is_empty() {
+        return Ok(indices);
+    }
+
+    // Group the filters by column.
+    let mut columns: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (column, value) in filters {
+        columns.entry(column).or_default().push(value);
+    }
+    check_columns(records, columns.keys().copied(), "select")?;
+
+    Ok(indices
+        .into_iter()
+        .filter(|&index| {
+            columns
+                .iter()
+                .all(|(column, values)| values.contains(&records[index][*column].as_str()))
+        })
+        .collect())
+}
+
+/// Drop the records sharing the same values in the `columns`.
+///
+/// Only the first or the last occurrence of each duplicated record is kept,
+/// depending on `keep`. The indices of the remaining records are returned in
+/// their original order, along with the number of dropped records.
+pub fn dedupe(
+    records: &[Record],
+    indices: Vec<usize>,
+    columns: &[String],
+    keep: Keep,
+) -> Result<(Vec<usize>, usize), Report> {
+    if columns.is_empty() {
+        return Ok((indices, 0));
+    }
+    check_columns(records, columns.iter().map(String::as_str), "dedupe")?;
+
+    let count = indices.len();
+    let mut seen = HashSet::new();
+    let mut key = |index: &usize| {
+        seen.insert(
+            columns
+                .iter()
+                .map(|column| records[*index][column].as_str())
+                .collect::<Vec<_>>(),
+        )
+    };
+    let kept: Vec<usize> = match keep {
+        Keep::First => indices.into_iter().filter(|i| key(i)).collect(),
+        Keep::Last => {
+            let mut kept: Vec<usize> = indices.into_iter().rev().filter(|i| key(i)).collect();
+            kept.reverse();
+            kept
+        }
+    };
+    let dropped = count - kept.len();
+
+    Ok((kept, dropped))
+}
+
+/// Sort the records by the sort keys.
+///
+/// The values are compared numerically when they are both numbers, and
+/// alphabetically otherwise, numbers coming first. The sort is stable, the
+/// records with equal keys keep their original order.
+pub fn sort(
+    records: &[Record],
+    mut indices: Vec<usize>,
+    keys: &[SortKey],
+) -> Result<Vec<usize>, Report> {
+    check_columns(records, keys.iter().map(|key| key.column.as_str()), "sort")?;
+    indices.sort_by(|&a, &b| {
+        keys.iter()
+            .map(|key| {
+                let ordering = compare(&records[a][&key.column], &records[b][&key.column]);
+                if key.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+
+    Ok(indices)
+}
+
+/// Compare two values, numerically if possible.
+fn compare(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// Ensure the records have the columns used by an operation.
+fn check_columns<'a>(
+    records: &[Record],
+    mut columns: impl Iterator<Item = &'a str>,
+    operation: &str,
+) -> Result<(), Report> {
+    if let Some(record) = records.first() {
+        if let Some(column) = columns.find(|column| !record.contains_key(*column)) {
+            return Err(eyre!(
+                "Cannot {} on the unknown column {:?}",
+                operation,
+                column
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A long to wide reshaping of the records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pivot {
+    /// Column holding the names of the new columns.
+    pub names: String,
+    /// Column holding the values of the new columns.
+    pub values: String,
+    /// Columns identifying the records of the reshaped data.
+    pub keys: Vec<String>,
+}
+
+/// Reshape long records, e.g. `city, metric, value`, into wide records, e.g.
+/// `city, lanes, tracks`.
+///
+/// The records sharing the same keys are merged into one record, which has a
+/// column for each name found in the `names` column, in order of appearance.
+/// The other columns are taken from the first record of each group, and the
+/// missing values are empty.
+pub fn pivot(records: &[Record], pivot: &Pivot) -> Result<Vec<Record>, Report> {
+    let columns = [&pivot.names, &pivot.values]
+        .into_iter()
+        .chain(&pivot.keys)
+        .map(String::as_str);
+    check_columns(records, columns, "pivot")?;
+
+    // Collect the new columns, in order of appearance.
+    let mut names: Vec<&str> = Vec::new();
+    for record in records {
+        let name = record[&pivot.names].as_str();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    if let Some(record) = records.first() {
+        if let Some(name) = names.iter().find(|name| record.contains_key(**name)) {
+            return Err(eyre!("Cannot pivot into the existing column {:?}", name));
+        }
+    }
+
+    let mut groups: Vec<Record> = Vec::new();
+    let mut positions: HashMap<Vec<&str>, usize> = HashMap::new();
+    for (index, record) in records.iter().enumerate() {
+        let key: Vec<&str> = pivot.keys.iter().map(|k| record[k].as_str()).collect();
+        let position = *positions.entry(key).or_insert_with(|| {
+            let mut group: Record = record
+                .iter()
+                .filter(|(column, _)| **column != pivot.names && **column != pivot.values)
+                .map(|(column, value)| (column.clone(), value.clone()))
+                .collect();
+            group.extend(names.iter().map(|name| (name.to_string(), String::new())));
+            groups.push(group);
+            groups.len() - 1
+        });
+        let name = &record[&pivot.names];
+        let value = &record[&pivot.values];
+        let group = &mut groups[position];
+        if !group[name].is_empty() {
+            return Err(eyre!(
+                "Record {} repeats the {} value {:?} of its group",
+                index + 1,
+                pivot.names,
+                name
+            ));
+        }
+        group[name] = value.clone();
+    }
+
+    Ok(groups)
+}
+
+/// Combine each record with each variant, in order: the variants of the first
+/// record come first.
+///
+/// The variants must not share any column with the records, so that every
+/// value of the combined records is unambiguous.
+pub fn cross(records: &[Record], variants: &[Record]) -> Result<Vec<Record>, Report> {
+    if let (Some(record), Some(variant)) = (records.first(), variants.first()) {
+        if let Some(column) = variant.keys().find(|column| record.contains_key(*column)) {
+            return Err(eyre!(
+                "The column {:?} exists in both the data and the variants",
+                column
+            ));
+        }
+    }
+
+    Ok(records
+        .iter()
+        .flat_map(|record| {
+            variants.iter().map(move |variant| {
+                let mut combined = record.clone();
+                combined.extend(variant.clone());
+                combined
+            })
+        })
+        .collect())
+}
+
+/// Add computed columns to the records.
+///
+/// Each column is a `(name, expression)` pair, the expression being evaluated
+/// with the values of the record, including the columns computed before it.
+/// The numeric values are converted to numbers, so that
+/// `population / area_sq_mi` divides them.
+pub fn compute(
+    env: &Environment,
+    records: &[Record],
+    columns: &[(String, String)],
+) -> Result<Vec<Record>, Report> {
+    let expressions = columns
+        .iter()
+        .map(|(name, expression)| {
+            env.compile_expression(expression)
+                .map_err(|e| eyre!("Invalid expression of the {} column: {}", name, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut computed = Vec::with_capacity(records.len());
+    for (index, record) in records.iter().enumerate() {
+        let mut record = record.clone();
+        for ((name, _), expression) in columns.iter().zip(&expressions) {
+            let context: BTreeMap<&str, Value> = record
+                .iter()
+                .map(|(column, value)| (column.as_str(), typed(value)))
+                .collect();
+            let value = expression.eval(&context).map_err(|e| {
+                eyre!(
+                    "Cannot compute the {} column of record {}: {}",
+                    name,
+                    index + 1,
+                    e
+                )
+            })?;
+            record.insert(name.clone(), value.to_string());
+        }
+        computed.push(record);
+    }
+
+    Ok(computed)
+}
+
+/// Convert a value to a number if possible.
+fn typed(value: &str) -> Value {
+    if let Ok(integer) = value.trim().parse::<i64>() {
+        Value::from(integer)
+    } else if let Ok(float) = value.trim().parse::<f64>() {
+        Value::from(float)
+    } else {
+        Value::from(value)
+    }
+}
+
+/// Skip the first `offset` records, and keep at most `limit` of the remaining
+/// ones.
+pub fn slice(indices: Vec<usize>, offset: usize, limit: Option<usize>) -> Vec<usize> {
+    indices
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Randomly sample `size` records, preserving their order.
+///
+/// The sampling is reproducible when a `seed` is provided.
+pub fn sample(indices: Vec<usize>, size: usize, seed: Option<u64>) -> Vec<usize> {
+    if size >= indices.len() {
+        return indices;
+    }
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut sampled = rand::seq::index::sample(&mut rng, indices.len(), size).into_vec();
+    sampled.sort_unstable();
+
+    sampled.into_iter().map(|i| indices[i]).collect()
+}
+
+/// Aggregate of a running total.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Mean,
+    Min,
+    Max,
+    Count,
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Mean => "mean",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Count => "count",
+        })
+    }
+}
+
+/// A running aggregate of a column, over the selected records in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Running {
+    pub column: String,
+    pub aggregate: Aggregate,
+}
+
+impl FromStr for Running {
+    type Err = String;
+
+    /// Parse a running aggregate: `column`, i.e. its running sum, or
+    /// `column:sum`, `column:mean`, `column:min`, `column:max` or
+    /// `column:count`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, aggregate) = match s.rsplit_once(':') {
+            Some((column, "sum")) => (column, Aggregate::Sum),
+            Some((column, "mean")) => (column, Aggregate::Mean),
+            Some((column, "min")) => (column, Aggregate::Min),
+            Some((column, "max")) => (column, Aggregate::Max),
+            Some((column, "count")) => (column, Aggregate::Count),
+            Some(_) => return Err(format!("invalid aggregate in {:?}", s)),
+            None => (s, Aggregate::Sum),
+        };
+        if column.trim().is_empty() {
+            return Err(format!("invalid running aggregate {:?}", s));
+        }
+        Ok(Self {
+            column: column.trim().to_owned(),
+            aggregate,
+        })
+    }
+}
+
+/// Numeric values of a column seen so far.
+#[derive(Debug, Default)]
+struct Accumulator {
+    sum: f64,
+    count: usize,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Accumulator {
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+    }
+
+    fn value(&self, aggregate: Aggregate) -> Value {
+        let value = match aggregate {
+            Aggregate::Sum => Some(self.sum),
+            Aggregate::Mean => (self.count > 0).then(|| self.sum / self.count as f64),
+            Aggregate::Min => self.min,
+            Aggregate::Max => self.max,
+            Aggregate::Count => Some(self.count as f64),
+        };
+        match value {
+            // The whole numbers are kept integers, e.g. for the populations.
+            Some(value) if value.fract() == 0.0 && value.abs() < 1e15 => Value::from(value as i64),
+            Some(value) => Value::from(value),
+            None => Value::from(()),
+        }
+    }
+}
+
+/// Compute the running aggregates of the selected records, in order.
+///
+/// The aggregates of each record cover the records before it and the record
+/// itself, as a map of the aggregates to the maps of the columns to their
+/// values, e.g. `{"sum": {"population": 1250000}}`. The values which are not
+/// numbers, such as the empty ones, are skipped.
+pub fn running(
+    records: &[Record],
+    indices: &[usize],
+    runnings: &[Running],
+) -> Result<Vec<Value>, Report> {
+    check_columns(
+        records,
+        runnings.iter().map(|running| running.column.as_str()),
+        "aggregate",
+    )?;
+    let mut accumulators: BTreeMap<&str, Accumulator> = runnings
+        .iter()
+        .map(|running| (running.column.as_str(), Accumulator::default()))
+        .collect();
+    let mut aggregates = Vec::with_capacity(indices.len());
+    for &index in indices {
+        for (column, accumulator) in accumulators.iter_mut() {
+            if let Ok(value) = records[index][*column].trim().parse::<f64>() {
+                accumulator.add(value);
+            }
+        }
+        let mut values: BTreeMap<String, BTreeMap<String, Value>> = BTreeMap::new();
+        for running in runnings {
+            values
+                .entry(running.aggregate.to_string())
+                .or_default()
+                .insert(
+                    running.column.clone(),
+                    accumulators[running.column.as_str()].value(running.aggregate),
+                );
+        }
+        aggregates.push(Value::from_serializable(&values));
+    }
+
+    Ok(aggregates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(city: &str, state: &str) -> Record {
+        Record::from([
+            (String::from("city"), String::from(city)),
+            (String::from("state"), String::from(state)),
+        ])
+    }
+
+    #[test]
+    fn test_select() {
+        let records = vec![
+            record("Austin", "TX"),
+            record("Provo", "UT"),
+            record("Houston", "TX"),
+        ];
+        let filters = vec![
+            (String::from("city"), String::from("Provo")),
+            (String::from("city"), String::from("Houston")),
+            (String::from("state"), String::from("TX")),
+        ];
+        assert_eq!(select(&records, vec![0, 1, 2], &filters).unwrap(), vec![2]);
+        let unknown = vec![(String::from("county"), String::from("Travis"))];
+        assert!(select(&records, vec![0, 1, 2], &unknown).is_err());
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let records = vec![
+            record("Austin", "TX"),
+            record("Provo", "UT"),
+            record("Austin", "TX"),
+            record("Austin", "MN"),
+        ];
+        let columns = vec![String::from("city"), String::from("state")];
+        assert_eq!(
+            dedupe(&records, vec![0, 1, 2, 3], &columns, Keep::First).unwrap(),
+            (vec![0, 1, 3], 1)
+        );
+        assert_eq!(
+            dedupe(&records, vec![0, 1, 2, 3], &columns, Keep::Last).unwrap(),
+            (vec![1, 2, 3], 1)
+        );
+    }
+
+    #[test]
+    fn test_sort() {
+        let records = vec![
+            record("Austin", "TX"),
+            record("Provo", "UT"),
+            record("Houston", "TX"),
+        ];
+        let keys = vec![
+            "state:desc".parse::<SortKey>().unwrap(),
+            "city".parse::<SortKey>().unwrap(),
+        ];
+        assert_eq!(sort(&records, vec![0, 1, 2], &keys).unwrap(), vec![1, 0, 2]);
+        assert!("city:up".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare("9", "10"), Ordering::Less);
+        assert_eq!(compare("2.5", "abc"), Ordering::Less);
+        assert_eq!(compare("abc", "abd"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_slice() {
+        assert_eq!(slice((0..10).collect(), 8, Some(5)), vec![8, 9]);
+        assert_eq!(slice((0..10).collect(), 2, Some(3)), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_sample() {
+        let sampled = sample((10..100).collect(), 5, Some(42));
+        assert_eq!(sampled.len(), 5);
+        assert!(sampled.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sampled, sample((10..100).collect(), 5, Some(42)));
+    }
+
+    #[test]
+    fn test_compute() {
+        let records = [Record::from([
+            (String::from("city"), String::from("Austin")),
+            (String::from("population"), String::from("961855")),
+            (String::from("area"), String::from("320")),
+        ])];
+        let columns = [
+            (String::from("density"), String::from("population / area")),
+            (
+                String::from("label"),
+                String::from("city|upper ~ ' ' ~ density|round"),
+            ),
+        ];
+        let computed = compute(&Environment::new(), &records, &columns).unwrap();
+        assert_eq!(computed[0]["density"], "3005.796875");
+        assert_eq!(computed[0]["label"], "AUSTIN 3006.0");
+
+        let columns = [(String::from("bad"), String::from("city / 2"))];
+        assert!(compute(&Environment::new(), &records, &columns).is_err());
+    }
+
+    #[test]
+    fn test_running() {
+        let city = |name: &str, population: &str| {
+            Record::from([
+                (String::from("city"), String::from(name)),
+                (String::from("population"), String::from(population)),
+            ])
+        };
+        let records = [
+            city("Austin", "961855"),
+            city("Provo", ""),
+            city("Dallas", "1304379.5"),
+        ];
+        let runnings = [
+            "population".parse::<Running>().unwrap(),
+            "population:max".parse::<Running>().unwrap(),
+            "population:count".parse::<Running>().unwrap(),
+        ];
+        let aggregates = running(&records, &[2, 1, 0], &runnings).unwrap();
+        let get = |i: usize, aggregate: &str| {
+            aggregates[i]
+                .get_attr(aggregate)
+                .and_then(|a| a.get_attr("population"))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(get(0, "sum"), "1304379.5");
+        assert_eq!(get(1, "count"), "1");
+        assert_eq!(get(2, "sum"), "2266234.5");
+        assert_eq!(get(2, "max"), "1304379.5");
+        assert!("population:median".parse::<Running>().is_err());
+        let runnings = ["area".parse::<Running>().unwrap()];
+        assert!(running(&records, &[0], &runnings).is_err());
+    }
+
+    #[test]
+    fn test_pivot() {
+        let long = |city: &str, metric: &str, value: &str| {
+            Record::from([
+                (String::from("city"), String::from(city)),
+                (String::from("state"), String::from("TX")),
+                (String::from("metric"), String::from(metric)),
+                (String::from("value"), String::from(value)),
+            ])
+        };
+        let records = [
+            long("Austin", "lanes", "430"),
+            long("Dallas", "lanes", "120"),
+            long("Austin", "tracks", "30"),
+        ];
+        let options = Pivot {
+            names: String::from("metric"),
+            values: String::from("value"),
+            keys: vec![String::from("city")],
+        };
+        let wide = pivot(&records, &options).unwrap();
+        assert_eq!(wide.len(), 2);
+        assert_eq!(
+            wide[0].keys().collect::<Vec<_>>(),
+            ["city", "state", "lanes", "tracks"]
+        );
+        assert_eq!(wide[0]["tracks"], "30");
+        assert_eq!(wide[1]["tracks"], "");
+
+        let records = [long("Austin", "lanes", "430"), long("Austin", "lanes", "1")];
+        assert!(pivot(&records, &options).is_err());
+    }
+
+    #[test]
+    fn test_cross() {
+        let record = |column: &str, value: &str| Record::from([(column.into(), value.into())]);
+        let cities = [record("city", "Austin"), record("city", "Dallas")];
+        let variants = [record("logo", "a.png"), record("logo", "b.png")];
+        let combined = cross(&cities, &variants).unwrap();
+        assert_eq!(combined.len(), 4);
+        assert_eq!(combined[1]["city"], "Austin");
+        assert_eq!(combined[1]["logo"], "b.png");
+        assert_eq!(combined[2]["city"], "Dallas");
+        assert!(cross(&cities, &cities).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    raster::{pixel_difference, rasterize},
+    snapshot::normalize,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{eyre::Report, Result};
+use minijinja::Environment;
+use serde::Serialize;
+use similar::TextDiff;
+use std::{collections::BTreeSet, ffi::OsStr, fs, path::Path};
+
+/// Template of the HTML report.
+const REPORT_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>svggloo diff</title>
+  <style>
+    body { font-family: sans-serif; margin: 2em; }
+    section { border-top: 1px solid #ccc; padding: 1em 0; }
+    .images { display: flex; gap: 1em; }
+    .images figure { margin: 0; flex: 1; }
+    .images img { max-width: 100%; border: 1px solid #ccc; }
+    pre { background: #f6f8fa; overflow-x: auto; padding: 1em; }
+  </style>
+</head>
+<body>
+  <h1>{{ changes | length }} changed file(s)</h1>
+  <p>Comparing <code>{{ old_dir }}</code> with <code>{{ new_dir }}</code>.</p>
+  {% for change in changes %}
+  <section id="{{ change.name }}">
+    <h2>{{ change.name }} ({{ change.status }})</h2>
+    {% if change.pixels is not none %}
+    <p>{{ (change.pixels * 100) | round(2) }}% of the pixels differ.</p>
+    {% endif %}
+    <div class="images">
+      <figure>
+        {% if change.old_image %}<img src="{{ change.old_image }}" alt="Old {{ change.name }}">{% endif %}
+        <figcaption>Old</figcaption>
+      </figure>
+      <figure>
+        {% if change.new_image %}<img src="{{ change.new_image }}" alt="New {{ change.name }}">{% endif %}
+        <figcaption>New</figcaption>
+      </figure>
+    </div>
+    {% if change.diff %}<pre>{{ change.diff }}</pre>{% endif %}
+  </section>
+  {% endfor %}
+</body>
+</html>
+"#;
+
+/// Status of a file between two output directories.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// The file only exists in the new directory.
+    Added,
+    /// The file only exists in the old directory.
+    Removed,
+    /// The file exists in both directories, with a different content.
+    Modified,
+}
+
+/// A file changed between two output directories.
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    /// Name of the file.
+    pub name: String,
+    /// Status of the file.
+    pub status: Status,
+    /// Unified diff of the contents of a modified file.
+    pub diff: Option<String>,
+    /// Fraction of differing pixels of a modified file, when rasterized.
+    pub pixels: Option<f64>,
+    /// Data URI of the old file.
+    old_image: Option<String>,
+    /// Data URI of the new file.
+    new_image: Option<String>,
+}
+
+/// Compare the SVG files of two output directories.
+///
+/// The files are paired by name, and compared once normalized. If a
+/// `tolerance` is provided, the modified files are also rasterized, and the
+/// files whose fraction of differing pixels does not exceed the tolerance are
+/// considered unchanged.
+pub fn diff_dirs(
+    old_dir: &Path,
+    new_dir: &Path,
+    tolerance: Option<f64>,
+) -> Result<Vec<Change>, Report> {
+    let old_files = svg_files(old_dir)?;
+    let new_files = svg_files(new_dir)?;
+
+    let mut changes = Vec::new();
+    for name in old_files.union(&new_files) {
+        let old = old_files
+            .contains(name)
+            .then(|| fs::read_to_string(old_dir.join(name)))
+            .transpose()?;
+        let new = new_files
+            .contains(name)
+            .then(|| fs::read_to_string(new_dir.join(name)))
+            .transpose()?;
+        let (status, diff, pixels) = match (&old, &new) {
+            (None, _) => (Status::Added, None, None),
+            (_, None) => (Status::Removed, None, None),
+            (Some(old), Some(new)) => {
+                let (old, new) = (normalize(old), normalize(new));
+                if old == new {
+                    continue;
+                }
+                let pixels = match tolerance {
+                    Some(tolerance) => {
+                        let pixels = pixel_difference(
+                            &rasterize(&old, old_dir, None)?,
+                            &rasterize(&new, new_dir, None)?,
+                        );
+                        if pixels <= tolerance {
+                            continue;
+                        }
+                        Some(pixels)
+                    }
+                    None => None,
+                };
+                let diff = TextDiff::from_lines(&old, &new)
+                    .unified_diff()
+                    .header(&format!("a/{}", name), &format!("b/{}", name))
+                    .to_string();
+                (Status::Modified, Some(diff), pixels)
+            }
+        };
+        changes.push(Change {
+            name: name.clone(),
+            status,
+            diff,
+            pixels,
+            old_image: old.as_deref().map(data_uri),
+            new_image: new.as_deref().map(data_uri),
+        });
+    }
+
+    Ok(changes)
+}
+
+/// Render the HTML report of the changes.
+pub fn html_report(changes: &[Change], old_dir: &Path, new_dir: &Path) -> Result<String, Report> {
+    #[derive(Serialize)]
+    struct ReportContext<'a> {
+        changes: &'a [Change],
+        old_dir: String,
+        new_dir: String,
+    }
+
+    let mut env = Environment::new();
+    env.add_template("report.html", REPORT_TEMPLATE)?;
+    let report = env.get_template("report.html")?.render(ReportContext {
+        changes,
+        old_dir: old_dir.display().to_string(),
+        new_dir: new_dir.display().to_string(),
+    })?;
+
+    Ok(report)
+}
+
+/// List the names of the SVG files of a directory.
+fn svg_files(dir: &Path) -> Result<BTreeSet<String>, Report> {
+    let mut names = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension() == Some(OsStr::new("svg")) {
+            if let Some(name) = path.file_name().and_then(OsStr::to_str) {
+                names.insert(name.to_owned());
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+/// Encode an SVG document as a data URI.
+fn data_uri(svg: &str) -> String {
+    format!("data:image/svg+xml;base64,{}", STANDARD.encode(svg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_dirs() {
+        let dir = std::env::temp_dir().join("svggloo-test-diff");
+        let _ = fs::remove_dir_all(&dir);
+        let (old_dir, new_dir) = (dir.join("old"), dir.join("new"));
+        fs::create_dir_all(&old_dir).unwrap();
+        fs::create_dir_all(&new_dir).unwrap();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><rect width="5" height="5"/></svg>"#;
+        fs::write(old_dir.join("same.svg"), svg).unwrap();
+        fs::write(new_dir.join("same.svg"), format!("{}\n", svg)).unwrap();
+        fs::write(old_dir.join("reformatted.svg"), svg).unwrap();
+        fs::write(new_dir.join("reformatted.svg"), svg.replace("\"5\"", "'5'")).unwrap();
+        fs::write(old_dir.join("removed.svg"), svg).unwrap();
+        fs::write(new_dir.join("added.svg"), svg).unwrap();
+
+        let changes = diff_dirs(&old_dir, &new_dir, None).unwrap();
+        let statuses: Vec<(&str, Status)> = changes
+            .iter()
+            .map(|c| (c.name.as_str(), c.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("added.svg", Status::Added),
+                ("reformatted.svg", Status::Modified),
+                ("removed.svg", Status::Removed),
+            ]
+        );
+
+        // The reformatted file is visually identical.
+        let changes = diff_dirs(&old_dir, &new_dir, Some(0.0)).unwrap();
+        assert_eq!(changes.len(), 2);
+        let report = html_report(&changes, &old_dir, &new_dir).unwrap();
+        assert!(report.contains("added.svg (added)"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
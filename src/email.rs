@@ -0,0 +1,246 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+/// Options controlling the delivery of the generated files by email.
+#[derive(Debug, Clone)]
+pub struct EmailOptions {
+    /// Column containing the address of the recipient of each record.
+    pub column: String,
+    /// URL of the SMTP server, `smtp://host[:port]` or `smtps://host[:port]`.
+    pub smtp: String,
+    /// Address of the sender.
+    pub from: String,
+    /// Template of the subject, rendered with the context of each record.
+    pub subject: String,
+    /// Template of the body, rendered with the context of each record.
+    pub body: String,
+    /// Maximum number of emails sent per minute.
+    pub rate: Option<u32>,
+    /// Report the emails instead of sending them.
+    pub dry_run: bool,
+}
+
+impl EmailOptions {
+    /// Delay to wait between two emails to respect the rate limit.
+    pub fn delay(&self) -> Duration {
+        match self.rate {
+            Some(rate) if rate > 0 => Duration::from_secs(60) / rate,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// An email delivering a generated file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    /// Address of the recipient.
+    pub to: String,
+    /// Rendered subject.
+    pub subject: String,
+    /// Rendered body.
+    pub body: String,
+    /// File attached to the email.
+    pub attachment: PathBuf,
+}
+
+/// Content types of the attachments, by extension.
+const CONTENT_TYPES: &[(&str, &str)] = &[
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+];
+
+/// Build the MIME message of an email: a text body followed by the attachment.
+///
+/// The addresses are checked for control characters, which could inject other
+/// headers, and the subject is encoded when it contains some.
+pub fn message(email: &Email, from: &str) -> Result<String, Report> {
+    for mailbox in [from, email.to.as_str()] {
+        if mailbox.chars().any(char::is_control) {
+            return Err(eyre!("Invalid email address {:?}", mailbox));
+        }
+    }
+    let content = fs::read(&email.attachment)?;
+    let file_name = email
+        .attachment
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let content_type = email
+        .attachment
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| CONTENT_TYPES.iter().find(|(e, _)| *e == ext))
+        .map_or("application/octet-stream", |(_, content_type)| content_type);
+    let boundary: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(24)
+        .map(char::from)
+        .collect();
+
+    let mut message = String::new();
+    message.push_str(&format!("From: {}\r\n", from));
+    message.push_str(&format!("To: {}\r\n", email.to));
+    message.push_str(&format!("Subject: {}\r\n", encode_header(&email.subject)));
+    message.push_str("MIME-Version: 1.0\r\n");
+    message.push_str(&format!(
+        "Content-Type: multipart/mixed; boundary=\"{}\"\r\n\r\n",
+        boundary
+    ));
+    message.push_str(&format!("--{}\r\n", boundary));
+    message.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+    message.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+    message.push_str(&wrap_base64(email.body.as_bytes()));
+    message.push_str(&format!("--{}\r\n", boundary));
+    message.push_str(&format!("Content-Type: {}\r\n", content_type));
+    message.push_str("Content-Transfer-Encoding: base64\r\n");
+    message.push_str(&format!(
+        "Content-Disposition: attachment; filename=\"{}\"\r\n\r\n",
+        file_name.replace('"', "")
+    ));
+    message.push_str(&wrap_base64(&content));
+    message.push_str(&format!("--{}--\r\n", boundary));
+
+    Ok(message)
+}
+
+/// Encode a header value as an RFC 2047 encoded word when it is not ASCII or
+/// contains control characters, such as line breaks.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() && !value.chars().any(|c| c.is_ascii_control()) {
+        value.to_owned()
+    } else {
+        format!("=?utf-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+/// Encode some content in base64, in lines of 76 characters.
+fn wrap_base64(content: &[u8]) -> String {
+    let encoded = STANDARD.encode(content);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / 38);
+    for line in encoded.as_bytes().chunks(76) {
+        // Base64 is ASCII, the chunks are valid UTF-8.
+        wrapped.push_str(std::str::from_utf8(line).unwrap());
+        wrapped.push_str("\r\n");
+    }
+    wrapped
+}
+
+/// Send an email through the SMTP server.
+///
+/// The email is handed over to `curl`, which reads the credentials from the
+/// URL or from the `.netrc` file, and upgrades the connection to TLS when the
+/// server supports it.
+pub fn send(email: &Email, options: &EmailOptions) -> Result<(), Report> {
+    let message = message(email, &options.from)?;
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--ssl", "--netrc-optional"])
+        .arg("--url")
+        .arg(&options.smtp)
+        .arg("--mail-from")
+        .arg(address(&options.from))
+        .arg("--mail-rcpt")
+        .arg(address(&email.to))
+        .args(["--upload-file", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| eyre!("cannot run curl: {}", e))?;
+    child
+        .stdin
+        .take()
+        .expect("the standard input is piped")
+        .write_all(message.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "curl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Extract the address of a mailbox, e.g. `Jane <jane@example.org>`.
+fn address(mailbox: &str) -> &str {
+    match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+/// Describe an email without sending it.
+pub fn describe(email: &Email) -> String {
+    format!(
+        "To: {}, Subject: {}, Attachment: {}",
+        email.to,
+        email.subject,
+        email.attachment.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let attachment = std::env::temp_dir().join("svggloo-email-test.svg");
+        fs::write(&attachment, "<svg/>").unwrap();
+        let email = Email {
+            to: String::from("mayor@austin.example"),
+            subject: String::from("Scorecard for Austin, ¡olé!"),
+            body: String::from("Hello"),
+            attachment: attachment.clone(),
+        };
+        let message = message(&email, "Jane <jane@example.org>").unwrap();
+        fs::remove_file(attachment).unwrap();
+        assert!(
+            message.starts_with("From: Jane <jane@example.org>\r\nTo: mayor@austin.example\r\n")
+        );
+        assert!(message.contains("Subject: =?utf-8?B?"));
+        assert!(message.contains("SGVsbG8=\r\n"));
+        assert!(message.contains("Content-Type: image/svg+xml\r\n"));
+        assert!(message.contains("filename=\"svggloo-email-test.svg\""));
+        assert!(message.contains("PHN2Zy8+\r\n"));
+    }
+
+    #[test]
+    fn test_message_injection() {
+        let attachment = std::env::temp_dir().join("svggloo-email-injection-test.svg");
+        fs::write(&attachment, "<svg/>").unwrap();
+        let mut email = Email {
+            to: String::from("a@x.org\r\nBcc: victim@y.org"),
+            subject: String::from("Scorecard"),
+            body: String::from("Hello"),
+            attachment: attachment.clone(),
+        };
+        assert!(message(&email, "jane@example.org").is_err());
+        email.to = String::from("a@x.org");
+        assert!(message(&email, "jane@example.org\nBcc: victim@y.org").is_err());
+        email.subject = String::from("Scorecard\r\nBcc: victim@y.org");
+        let message = message(&email, "jane@example.org").unwrap();
+        fs::remove_file(attachment).unwrap();
+        assert!(!message.contains("\r\nBcc:"));
+        assert!(message.contains("Subject: =?utf-8?B?"));
+    }
+
+    #[test]
+    fn test_address() {
+        assert_eq!(address("Jane <jane@example.org>"), "jane@example.org");
+        assert_eq!(address(" jane@example.org "), "jane@example.org");
+    }
+}
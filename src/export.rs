@@ -0,0 +1,545 @@
+#[cfg(feature = "native")]
+use crate::{
+    atomic::temp_path,
+    profile::{Profiler, Stage},
+};
+use clap::ValueEnum;
+use std::str::FromStr;
+#[cfg(feature = "native")]
+use std::{
+    collections::VecDeque,
+    env, fs, mem,
+    panic::resume_unwind,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Exporter {
+    Inkscape,
+    CairoSVG,
+    SVG2PDF,
+}
+
+/// Area of the document to export.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExportArea {
+    /// The bounding box of the drawing.
+    ///
+    /// Only supported by Inkscape.
+    Drawing,
+    /// The page, as defined by the `viewBox` of the document.
+    Page,
+    /// A custom area, in user units.
+    Custom {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+}
+
+impl FromStr for ExportArea {
+    type Err = String;
+
+    /// Parse an export area: `drawing`, `page` or `x:y:width:height`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drawing" => Ok(Self::Drawing),
+            "page" => Ok(Self::Page),
+            area => {
+                let values = area
+                    .split(':')
+                    .map(|v| v.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .ok()
+                    .filter(|v| v.len() == 4 && v[2] > 0.0 && v[3] > 0.0)
+                    .ok_or_else(|| format!("invalid export area {:?}", s))?;
+                Ok(Self::Custom {
+                    x: values[0],
+                    y: values[1],
+                    width: values[2],
+                    height: values[3],
+                })
+            }
+        }
+    }
+}
+
+impl Exporter {
+    /// Check whether the exporter supports an export area.
+    pub fn supports(&self, area: ExportArea) -> bool {
+        area != ExportArea::Drawing || *self == Exporter::Inkscape
+    }
+
+    /// Name of the binary of the exporter.
+    pub fn program(&self) -> &'static str {
+        match self {
+            Exporter::Inkscape => "inkscape",
+            Exporter::CairoSVG => "cairosvg",
+            Exporter::SVG2PDF => "svg2pdf",
+        }
+    }
+
+    /// Number of files exported at once.
+    ///
+    /// Inkscape takes seconds to start, and exports the files in batches. The
+    /// other exporters take one file per process.
+    pub fn batch_size(&self) -> usize {
+        match self {
+            Exporter::Inkscape => 16,
+            Exporter::CairoSVG | Exporter::SVG2PDF => 1,
+        }
+    }
+
+    /// Export SVG files as PDF files with the same names.
+    #[cfg(feature = "native")]
+    pub fn export(&self, srcs: &[PathBuf], area: Option<ExportArea>, limits: &ProcessLimits) {
+        match self {
+            Exporter::Inkscape => export_with_inkscape(srcs, area, limits),
+            Exporter::CairoSVG => export_with_cairosvg(srcs, limits),
+            Exporter::SVG2PDF => export_with_svg2pdf(srcs, limits),
+        }
+    }
+}
+
+/// Limits of the resources used by the exporter processes, so that the large
+/// runs do not starve the other users of a shared machine.
+///
+/// The limits are applied by running the exporters through `nice`, `ionice`
+/// and `taskset`, the last two being specific to Linux.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessLimits {
+    /// Niceness of the processes, from 0 to 19, the higher the lower their
+    /// priority.
+    pub nice: Option<u8>,
+    /// Run the processes in the idle I/O scheduling class.
+    pub idle_io: bool,
+    /// CPUs the processes are restricted to, as a list like `0-3,6`.
+    pub cpus: Option<String>,
+}
+
+impl ProcessLimits {
+    /// Prefix a command with the programs applying the limits.
+    #[cfg(feature = "native")]
+    fn wrap(&self, program: &str, args: &[String]) -> (String, Vec<String>) {
+        let mut command = Vec::new();
+        if let Some(nice) = self.nice {
+            command.extend(["nice".to_owned(), "-n".to_owned(), nice.to_string()]);
+        }
+        if self.idle_io {
+            command.extend(["ionice".to_owned(), "-c".to_owned(), "3".to_owned()]);
+        }
+        if let Some(cpus) = &self.cpus {
+            command.extend(["taskset".to_owned(), "-c".to_owned(), cpus.clone()]);
+        }
+        command.push(program.to_owned());
+        command.extend_from_slice(args);
+        let program = command.remove(0);
+
+        (program, command)
+    }
+}
+
+/// Availability of an exporter.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExporterInfo {
+    pub exporter: Exporter,
+    /// Resolved path of the binary, if it was found.
+    pub path: Option<PathBuf>,
+    /// First line printed by the binary with `--version`.
+    pub version: Option<String>,
+}
+
+/// Look for the binaries of all the supported exporters.
+#[cfg(feature = "native")]
+pub fn list_exporters() -> Vec<ExporterInfo> {
+    Exporter::value_variants()
+        .iter()
+        .map(|&exporter| {
+            let path = find_program(exporter.program());
+            let version = path.as_deref().and_then(program_version);
+            ExporterInfo {
+                exporter,
+                path,
+                version,
+            }
+        })
+        .collect()
+}
+
+/// Resolve a program in the directories of the `PATH` environment variable.
+#[cfg(feature = "native")]
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let names = match cfg!(windows) {
+        true => vec![format!("{}.exe", program), program.to_owned()],
+        false => vec![program.to_owned()],
+    };
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Read the version of a program, i.e. the first line it prints with
+/// `--version`.
+#[cfg(feature = "native")]
+fn program_version(path: &Path) -> Option<String> {
+    let output = Command::new(path).arg("--version").output().ok()?;
+    [output.stdout, output.stderr].iter().find_map(|stream| {
+        String::from_utf8_lossy(stream)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_owned)
+    })
+}
+
+/// Files waiting for an export.
+#[cfg(feature = "native")]
+#[derive(Debug, Default)]
+struct Queue {
+    pending: VecDeque<(Exporter, PathBuf)>,
+    /// Whether no more files will be queued.
+    closed: bool,
+}
+
+#[cfg(feature = "native")]
+impl Queue {
+    /// Take the next batch of files of the same exporter, once it is full or
+    /// once no more files will be queued.
+    fn take(&mut self) -> Option<(Exporter, Vec<PathBuf>)> {
+        let &(exporter, _) = self.pending.front()?;
+        let available = self
+            .pending
+            .iter()
+            .take_while(|(e, _)| *e == exporter)
+            .count();
+        if available < exporter.batch_size() && !self.closed {
+            return None;
+        }
+        let batch = self
+            .pending
+            .drain(..available.min(exporter.batch_size()))
+            .map(|(_, src)| src)
+            .collect();
+        Some((exporter, batch))
+    }
+}
+
+/// Pool of workers running the exporters while the files are still being
+/// rendered.
+///
+/// The files are queued as they are written, and the idle workers take the
+/// next batch, so that a few heavy exporter processes keep up with many
+/// rendering threads.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct ExportPool {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+#[cfg(feature = "native")]
+impl ExportPool {
+    /// Start `jobs` workers exporting the `area` of the documents.
+    pub fn new(
+        jobs: usize,
+        area: Option<ExportArea>,
+        limits: ProcessLimits,
+        profiler: Option<Arc<Profiler>>,
+    ) -> Self {
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        let workers = (0..jobs.max(1))
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let profiler = profiler.clone();
+                let limits = limits.clone();
+                thread::spawn(move || loop {
+                    let (exporter, srcs) = {
+                        let (lock, ready) = &*queue;
+                        let mut pending = lock.lock().unwrap();
+                        loop {
+                            if let Some(batch) = pending.take() {
+                                break batch;
+                            }
+                            if pending.closed && pending.pending.is_empty() {
+                                return;
+                            }
+                            pending = ready.wait(pending).unwrap();
+                        }
+                    };
+                    // Another batch may be ready for the other workers.
+                    queue.1.notify_one();
+                    let start = Instant::now();
+                    export_staged(exporter, &srcs, area, &limits);
+                    if let Some(profiler) = &profiler {
+                        let name = format!("{}: {} file(s)", exporter.program(), srcs.len());
+                        profiler.record(Stage::Export, &name, start);
+                    }
+                })
+            })
+            .collect();
+
+        Self { queue, workers }
+    }
+
+    /// Queue a file for an export.
+    pub fn push(&self, exporter: Exporter, src: PathBuf) {
+        let (lock, ready) = &*self.queue;
+        lock.lock().unwrap().pending.push_back((exporter, src));
+        ready.notify_one();
+    }
+
+    /// Export the files left, and wait for the workers.
+    pub fn finish(mut self) {
+        for worker in self.close() {
+            if let Err(panic) = worker.join() {
+                resume_unwind(panic);
+            }
+        }
+    }
+
+    /// Stop queueing files, and wake the workers up to export the last
+    /// batches.
+    fn close(&mut self) -> Vec<JoinHandle<()>> {
+        let (lock, ready) = &*self.queue;
+        lock.lock().unwrap().closed = true;
+        ready.notify_all();
+        mem::take(&mut self.workers)
+    }
+}
+
+#[cfg(feature = "native")]
+impl Drop for ExportPool {
+    /// Drop the files left when the run failed, and wait for the exports in
+    /// progress.
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.queue.0.lock() {
+            queue.pending.clear();
+        }
+        for worker in self.close() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Export SVG files through temporary links, and rename the PDF files once
+/// complete, so that an interrupted export leaves no truncated PDF behind.
+#[cfg(feature = "native")]
+fn export_staged(
+    exporter: Exporter,
+    srcs: &[PathBuf],
+    area: Option<ExportArea>,
+    limits: &ProcessLimits,
+) {
+    // The links stay in the directories of the files, to resolve the same
+    // relative links.
+    let staged: Vec<PathBuf> = srcs.iter().map(|src| temp_path(src)).collect();
+    for (src, stage) in srcs.iter().zip(&staged) {
+        let _ = fs::remove_file(stage);
+        if fs::hard_link(src, stage).is_err() {
+            let _ = fs::copy(src, stage);
+        }
+    }
+    exporter.export(&staged, area, limits);
+    for (src, stage) in srcs.iter().zip(&staged) {
+        let _ = fs::rename(stage.with_extension("pdf"), src.with_extension("pdf"));
+        let _ = fs::remove_file(stage);
+    }
+}
+
+/// Exports SVG files to PDF files with Inkscape.
+///
+/// Exports the SVG `srcs` files as PDF files with the same names, in a single
+/// Inkscape process run within the `limits`. This function panics if the
+/// process cannot be started, see [`find_program`] to check it beforehand.
+///
+/// The drawing area is exported by default. A custom `area` is expected to have
+/// been applied to the page beforehand with [`crate::svg::crop`].
+#[cfg(feature = "native")]
+pub fn export_with_inkscape(srcs: &[PathBuf], area: Option<ExportArea>, limits: &ProcessLimits) {
+    // Set the name of the Inkscape binary.
+    let program = Exporter::Inkscape.program();
+
+    // Prepare the Inkscape arguments.
+    let export_filenames = srcs
+        .iter()
+        .map(|s| s.clone().into_os_string())
+        .filter_map(|src| src.into_string().ok())
+        .collect::<Vec<String>>();
+    let area = match area {
+        Some(ExportArea::Drawing) | None => "--export-area-drawing",
+        Some(ExportArea::Page) | Some(ExportArea::Custom { .. }) => "--export-area-page",
+    };
+    let mut args = vec![
+        area.to_owned(),
+        "--batch-process".to_owned(),
+        "--export-type=pdf".to_owned(),
+    ];
+    args.extend(export_filenames);
+
+    export_with(program, &args, limits);
+}
+
+/// Export with a specific program and arguments, within the limits.
+///
+/// Panics if the process cannot be started. A failed export leaves no PDF
+/// file, which the callers check.
+#[cfg(feature = "native")]
+fn export_with(program: &str, args: &[String], limits: &ProcessLimits) {
+    let (program, args) = limits.wrap(program, args);
+    // Prepare the error message.
+    let error_msg = format!(
+        "Failed to execute command `{} {}`",
+        program,
+        &args.join(" ")
+    );
+    // Execute the export command.
+    let _output = Command::new(&program)
+        .args(&args)
+        .output()
+        .expect(&error_msg);
+}
+
+/// Exports SVG files to PDF files with CairoSVG.
+///
+/// Exports the SVG `srcs` files as PDF files with the same names, one CairoSVG
+/// process per file run within the `limits`. This function panics if a process
+/// cannot be started, see [`find_program`] to check it beforehand.
+#[cfg(feature = "native")]
+pub fn export_with_cairosvg(srcs: &[PathBuf], limits: &ProcessLimits) {
+    for src in srcs {
+        // Prepare the input/output values from the src argument.
+        let (in_svg, out_pdf) = get_in_out_file(src);
+
+        // Prepare the command.
+        let program = Exporter::CairoSVG.program();
+        let args = vec![
+            "-f".to_owned(),
+            "pdf".to_owned(),
+            "-o".to_owned(),
+            out_pdf,
+            in_svg,
+        ];
+
+        export_with(program, &args, limits);
+    }
+}
+
+/// Exports SVG files to PDF files with svg2pdf.
+///
+/// Exports the SVG `srcs` files as PDF files with the same names, one svg2pdf
+/// process per file run within the `limits`. This function panics if a process
+/// cannot be started, see [`find_program`] to check it beforehand.
+#[cfg(feature = "native")]
+pub fn export_with_svg2pdf(srcs: &[PathBuf], limits: &ProcessLimits) {
+    for src in srcs {
+        // Prepare the input/output values from the src argument.
+        let (in_svg, _out_pdf) = get_in_out_file(src);
+
+        // Prepare the command.
+        let program = Exporter::SVG2PDF.program();
+        let args = vec![in_svg];
+
+        export_with(program, &args, limits);
+    }
+}
+
+/// Get the input and output string representations of the provided file.
+#[cfg(feature = "native")]
+pub(crate) fn get_in_out_file<P>(src: P) -> (String, String)
+where
+    P: AsRef<Path>,
+{
+    let in_svg = src
+        .as_ref()
+        .to_str()
+        .expect("The src file path is not valid UTF-8.");
+    let dest = src.as_ref().with_extension("pdf");
+    let out_pdf = dest
+        .to_str()
+        .expect("The dest file path is not valid UTF-8");
+
+    (in_svg.into(), out_pdf.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(all(feature = "native", unix))]
+    fn test_find_program() {
+        let sh = find_program("sh").unwrap();
+        assert!(sh.is_absolute());
+        assert_eq!(sh.file_name().unwrap(), "sh");
+        assert_eq!(find_program("svggloo-missing-program"), None);
+        assert_eq!(program_version(Path::new("svggloo-missing-program")), None);
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_queue() {
+        let mut queue = Queue::default();
+        assert_eq!(queue.take(), None);
+        for i in 0..20 {
+            queue
+                .pending
+                .push_back((Exporter::Inkscape, PathBuf::from(format!("{}.svg", i))));
+        }
+        queue
+            .pending
+            .push_back((Exporter::CairoSVG, PathBuf::from("a.svg")));
+        let (exporter, batch) = queue.take().unwrap();
+        assert_eq!(exporter, Exporter::Inkscape);
+        assert_eq!(batch.len(), 16);
+        // The batch is not full yet.
+        assert_eq!(queue.take(), None);
+        queue.closed = true;
+        assert_eq!(queue.take().unwrap().1.len(), 4);
+        assert_eq!(
+            queue.take(),
+            Some((Exporter::CairoSVG, vec![PathBuf::from("a.svg")]))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_process_limits() {
+        let args = vec![String::from("a.svg")];
+        assert_eq!(
+            ProcessLimits::default().wrap("svg2pdf", &args),
+            (String::from("svg2pdf"), args.clone())
+        );
+        let limits = ProcessLimits {
+            nice: Some(10),
+            idle_io: true,
+            cpus: Some(String::from("0-3")),
+        };
+        let (program, args) = limits.wrap("svg2pdf", &args);
+        assert_eq!(program, "nice");
+        assert_eq!(
+            args.join(" "),
+            "-n 10 ionice -c 3 taskset -c 0-3 svg2pdf a.svg"
+        );
+    }
+
+    #[test]
+    fn test_export_area() {
+        assert_eq!("page".parse::<ExportArea>().unwrap(), ExportArea::Page);
+        assert_eq!(
+            "10:20:300:400".parse::<ExportArea>().unwrap(),
+            ExportArea::Custom {
+                x: 10.0,
+                y: 20.0,
+                width: 300.0,
+                height: 400.0
+            }
+        );
+        assert!("10:20:0:400".parse::<ExportArea>().is_err());
+    }
+}
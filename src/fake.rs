@@ -0,0 +1,329 @@
+use color_eyre::{eyre::Report, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{collections::HashSet, io::Write};
+
+/// Keywords, tests and variables which are not data columns.
+const RESERVED: &[&str] = &[
+    "and",
+    "as",
+    "autoescape",
+    "block",
+    "call",
+    "defined",
+    "elif",
+    "else",
+    "endautoescape",
+    "endblock",
+    "endcall",
+    "endfilter",
+    "endfor",
+    "endif",
+    "endmacro",
+    "endraw",
+    "endset",
+    "endwith",
+    "extends",
+    "false",
+    "False",
+    "filter",
+    "for",
+    "from",
+    "if",
+    "import",
+    "in",
+    "include",
+    "is",
+    "locale",
+    "loop",
+    "macro",
+    "none",
+    "None",
+    "not",
+    "or",
+    "raw",
+    "recursive",
+    "self",
+    "set",
+    "super",
+    "true",
+    "True",
+    "undefined",
+    "with",
+    "_all",
+    "_columns",
+    "_siblings",
+    "_theme",
+];
+
+const FIRST_NAMES: &[&str] = &[
+    "Alex", "Blake", "Casey", "Dana", "Emery", "Finley", "Jordan", "Morgan", "Riley", "Taylor",
+];
+const LAST_NAMES: &[&str] = &[
+    "Garcia", "Johnson", "Kim", "Lopez", "Miller", "Nguyen", "Patel", "Smith", "Wilson", "Young",
+];
+const CITIES: &[&str] = &[
+    "Austin",
+    "Boulder",
+    "Chicago",
+    "Madison",
+    "Minneapolis",
+    "Portland",
+    "Provo",
+    "Seattle",
+    "Tucson",
+    "Washington",
+];
+const STATES: &[&str] = &["AZ", "CA", "CO", "IL", "MN", "OR", "TX", "UT", "WA", "WI"];
+const COUNTRIES: &[&str] = &[
+    "Australia",
+    "Canada",
+    "Denmark",
+    "France",
+    "Germany",
+    "Japan",
+    "Mexico",
+    "Netherlands",
+    "Spain",
+    "United States",
+];
+const WORDS: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "tempor",
+];
+
+/// List the variables used by a template.
+///
+/// The template source is scanned for the identifiers used in its expressions
+/// and statements. Keywords, filters, tests, functions, attributes and the
+/// variables bound by the template itself (`for` loops and `set` statements)
+/// are left out. The variables are returned in order of first appearance.
+pub fn variables(source: &str) -> Vec<String> {
+    let mut variables: Vec<String> = Vec::new();
+    for (name, _) in variable_offsets(source) {
+        if !variables.iter().any(|v| v == name) {
+            variables.push(name.to_owned());
+        }
+    }
+
+    variables
+}
+
+/// List every occurrence of the variables used by a template, along with its
+/// byte offset in the source.
+///
+/// See [`variables`] for the identifiers which are left out.
+pub fn variable_offsets(source: &str) -> Vec<(&str, usize)> {
+    let mut occurrences = Vec::new();
+    let mut bound: HashSet<&str> = HashSet::new();
+    let mut rest = source;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start..];
+        let end_tag = match rest.get(..2) {
+            Some("{{") => "}}",
+            Some("{%") => "%}",
+            _ => {
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let end = rest.find(end_tag).unwrap_or(rest.len());
+        let tag = rest[2..end].trim_matches(|c: char| c == '-' || c == '+' || c.is_whitespace());
+        rest = &rest[end..];
+
+        // Identify the variables bound by the statement.
+        let binding = match tag.split_whitespace().next() {
+            Some("for") => tag.find(" in ").map(|i| &tag[3..i]),
+            Some("set") => tag.find('=').map(|i| &tag[3..i]),
+            _ => None,
+        };
+        if let Some(binding) = binding {
+            bound.extend(identifiers(binding).map(|(name, _)| name));
+        }
+        let skipped = binding.map_or(0, |binding| binding.len() + 3);
+
+        for (name, free) in identifiers(&tag[skipped..]) {
+            if free && !RESERVED.contains(&name) && !bound.contains(name) {
+                // The identifiers are slices of the source.
+                let offset = name.as_ptr() as usize - source.as_ptr() as usize;
+                occurrences.push((name, offset));
+            }
+        }
+    }
+
+    occurrences
+}
+
+/// Iterate over the identifiers of an expression.
+///
+/// Each identifier comes with a flag telling whether it can be a variable, i.e.
+/// it is not a filter, a test, an attribute, a function or a keyword argument.
+fn identifiers(expression: &str) -> impl Iterator<Item = (&str, bool)> {
+    let bytes = expression.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut previous = None;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'"' || c == b'\'' {
+            // Skip the string literals.
+            i += 1;
+            while i < bytes.len() && bytes[i] != c {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i += 1;
+            previous = Some(c);
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            let name = &expression[start..i];
+            let next = expression[i..].trim_start();
+            let attribute_or_filter = matches!(previous, Some(b'.') | Some(b'|'));
+            let test = matches!(tokens.last(), Some(("is", _)));
+            let call = next.starts_with('(');
+            let keyword_argument = next.starts_with('=') && !next.starts_with("==");
+            let free = !(attribute_or_filter || test || call || keyword_argument);
+            tokens.push((name, free));
+            previous = Some(b'a');
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                i += 1;
+            }
+            previous = Some(b'0');
+        } else {
+            if !c.is_ascii_whitespace() {
+                previous = Some(c);
+            }
+            i += 1;
+        }
+    }
+
+    tokens.into_iter()
+}
+
+/// Generate a plausible value for a column, guessed from its name.
+fn fake_value(column: &str, rng: &mut StdRng) -> String {
+    let name = column.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| name.contains(word));
+    // Short words only match a whole word of the name, e.g. `lat` must not
+    // match `population`.
+    let is = |words: &[&str]| {
+        name.split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|part| words.contains(&part))
+    };
+    let pick = |values: &[&str], rng: &mut StdRng| values.choose(rng).unwrap().to_string();
+
+    if has(&["first"]) {
+        pick(FIRST_NAMES, rng)
+    } else if has(&["last", "surname"]) {
+        pick(LAST_NAMES, rng)
+    } else if has(&["name"]) && !has(&["city", "state", "country"]) {
+        format!("{} {}", pick(FIRST_NAMES, rng), pick(LAST_NAMES, rng))
+    } else if has(&["city", "town"]) {
+        pick(CITIES, rng)
+    } else if has(&["state", "region"]) {
+        pick(STATES, rng)
+    } else if has(&["country"]) {
+        pick(COUNTRIES, rng)
+    } else if has(&["latitude"]) || is(&["lat"]) {
+        format!("{:.4}", rng.gen_range(25.0..49.0))
+    } else if has(&["longitude"]) || is(&["lon", "lng"]) {
+        format!("{:.4}", rng.gen_range(-124.0..-67.0))
+    } else if has(&["email"]) {
+        format!("{}@example.com", pick(FIRST_NAMES, rng).to_lowercase())
+    } else if has(&["link", "website"]) || is(&["url"]) {
+        format!("https://example.com/{}", pick(WORDS, rng))
+    } else if has(&["date"]) {
+        format!(
+            "2023-{:02}-{:02}",
+            rng.gen_range(1..=12),
+            rng.gen_range(1..=28)
+        )
+    } else if has(&["year"]) {
+        rng.gen_range(2000..=2023).to_string()
+    } else if has(&["score", "rating", "percent"]) || is(&["rank"]) {
+        format!("{:.1}", rng.gen_range(0.0..100.0))
+    } else if has(&["count", "number", "total", "amount", "population"])
+        || is(&["pop", "num", "qty"])
+    {
+        rng.gen_range(1..10_000).to_string()
+    } else if has(&["enabled", "visible"]) || is(&["is", "has", "show"]) {
+        pick(&["true", "false"], rng)
+    } else {
+        let mut words: Vec<String> = (0..rng.gen_range(1..=3))
+            .map(|_| pick(WORDS, rng))
+            .collect();
+        words[0] = words[0][..1].to_uppercase() + &words[0][1..];
+        words.join(" ")
+    }
+}
+
+/// Write a CSV file of fake records for the columns.
+///
+/// The generation is reproducible when a `seed` is provided.
+pub fn write_fake_csv<W: Write>(
+    writer: W,
+    columns: &[String],
+    rows: usize,
+    seed: Option<u64>,
+) -> Result<(), Report> {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(columns)?;
+    for _ in 0..rows {
+        let record: Vec<String> = columns
+            .iter()
+            .map(|column| fake_value(column, &mut rng))
+            .collect();
+        csv_writer.write_record(&record)?;
+    }
+    csv_writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variables() {
+        let source = r#"<svg>
+  <text>{{ city | upper }}, {{ state }}</text>
+  {% if score is defined and score > 50 %}{{ icon("star") }}{% endif %}
+  {% for item in items %}<text>{{ item.label }}</text>{% endfor %}
+  {% set total = count * 2 %}<text>{{ total }} {{ _theme }}</text>
+  <image href="{{ map_image(lat, lon, 12, '300x200') }}"/>
+</svg>"#;
+        assert_eq!(
+            variables(source),
+            vec!["city", "state", "score", "items", "count", "lat", "lon"]
+        );
+    }
+
+    #[test]
+    fn test_write_fake_csv() {
+        let columns = vec![String::from("city"), String::from("population")];
+        let mut csv = Vec::new();
+        write_fake_csv(&mut csv, &columns, 3, Some(1)).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "city,population");
+        let (city, population) = lines[1].split_once(',').unwrap();
+        assert!(CITIES.contains(&city));
+        assert!(population.parse::<u32>().is_ok());
+    }
+}
@@ -0,0 +1,948 @@
+use crate::{
+    barcode::{self, Symbology},
+    color::Color,
+    svg,
+};
+use minijinja::{value::ValueKind, Environment, Error, ErrorKind, Value};
+
+/// Register the svggloo filters and functions formatting the values of the
+/// records.
+pub fn register(env: &mut Environment<'_>) {
+    env.add_filter("convert", convert);
+    env.add_filter("km_to_mi", |value: Value, precision: Option<i32>| {
+        convert(value, String::from("km"), String::from("mi"), precision)
+    });
+    env.add_filter("m_to_ft", |value: Value, precision: Option<i32>| {
+        convert(value, String::from("m"), String::from("ft"), precision)
+    });
+    env.add_filter("c_to_f", |value: Value, precision: Option<i32>| {
+        convert(value, String::from("c"), String::from("f"), precision)
+    });
+    env.add_filter("phone", phone);
+    env.add_filter("join_nonempty", join_nonempty);
+    env.add_function("address", address);
+    env.add_filter("ordinal", ordinal);
+    env.add_filter("spell_number", spell_number);
+    env.add_filter("titlecase", titlecase);
+    env.add_filter("smart_quotes", smart_quotes);
+    env.add_filter("nbsp", nbsp);
+    env.add_function("contrast_color", contrast_color);
+    env.add_filter("lighten", |value: String, amount: f64| {
+        Ok::<_, Error>(color(&value)?.lighten(amount).to_string())
+    });
+    env.add_filter("darken", |value: String, amount: f64| {
+        Ok::<_, Error>(color(&value)?.lighten(-amount).to_string())
+    });
+    env.add_filter("with_alpha", with_alpha);
+    env.add_filter("mix", mix);
+    env.add_function("table", table);
+    env.add_filter("band", band);
+    env.add_function("legend", legend);
+    env.add_function("barcode", barcode);
+}
+
+/// Convert a template value to a number.
+///
+/// CSV values are always strings, therefore numbers are parsed from their
+/// string representation.
+pub(crate) fn number(value: &Value, name: &str) -> Result<f64, Error> {
+    value.to_string().trim().parse::<f64>().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("`{}` is not a valid number: {:?}", name, value.to_string()),
+        )
+    })
+}
+
+/// Round a number to `precision` decimals, if any.
+///
+/// A number rounded to no decimal is an integer, so that it is not displayed
+/// with a trailing `.0`.
+fn round(value: f64, precision: Option<i32>) -> Value {
+    match precision {
+        None => Value::from(value),
+        Some(precision) if precision <= 0 => Value::from(value.round() as i64),
+        Some(precision) => {
+            let factor = 10f64.powi(precision);
+            Value::from((value * factor).round() / factor)
+        }
+    }
+}
+
+/// Quantity of a unit, and its factor to the base unit of the quantity.
+///
+/// The temperatures are converted separately, as their scales have different
+/// origins.
+fn unit(name: &str) -> Option<(&'static str, f64)> {
+    let unit = match name.to_lowercase().as_str() {
+        "mm" => ("length", 0.001),
+        "cm" => ("length", 0.01),
+        "m" => ("length", 1.0),
+        "km" => ("length", 1000.0),
+        "in" => ("length", 0.0254),
+        "ft" => ("length", 0.3048),
+        "yd" => ("length", 0.9144),
+        "mi" => ("length", 1609.344),
+        "m2" => ("area", 1.0),
+        "ha" => ("area", 10_000.0),
+        "km2" => ("area", 1_000_000.0),
+        "ft2" => ("area", 0.092_903_04),
+        "acre" => ("area", 4_046.856_422_4),
+        "mi2" => ("area", 2_589_988.110_336),
+        "g" => ("mass", 0.001),
+        "kg" => ("mass", 1.0),
+        "t" => ("mass", 1000.0),
+        "oz" => ("mass", 0.028_349_523_125),
+        "lb" => ("mass", 0.453_592_37),
+        "m/s" => ("speed", 1.0),
+        "km/h" => ("speed", 1.0 / 3.6),
+        "mph" => ("speed", 0.447_04),
+        _ => return None,
+    };
+    Some(unit)
+}
+
+/// Convert a temperature to kelvins.
+fn to_kelvin(value: f64, unit: &str) -> Option<f64> {
+    match unit.to_lowercase().as_str() {
+        "c" => Some(value + 273.15),
+        "f" => Some((value - 32.0) * 5.0 / 9.0 + 273.15),
+        "k" => Some(value),
+        _ => None,
+    }
+}
+
+/// Convert a temperature from kelvins.
+fn from_kelvin(value: f64, unit: &str) -> Option<f64> {
+    match unit.to_lowercase().as_str() {
+        "c" => Some(value - 273.15),
+        "f" => Some((value - 273.15) * 9.0 / 5.0 + 32.0),
+        "k" => Some(value),
+        _ => None,
+    }
+}
+
+/// Convert a value between two units of the same quantity, e.g.
+/// `{{ distance|convert("km", "mi", 1) }}`, rounding it to `precision`
+/// decimals.
+///
+/// The empty values are left as is, so that the missing data stay blank.
+fn convert(value: Value, from: String, to: String, precision: Option<i32>) -> Result<Value, Error> {
+    if value.to_string().trim().is_empty() {
+        return Ok(value);
+    }
+    let number = number(&value, "value")?;
+    let converted = match (unit(&from), unit(&to)) {
+        (Some((from_quantity, from_factor)), Some((to_quantity, to_factor)))
+            if from_quantity == to_quantity =>
+        {
+            Some(number * from_factor / to_factor)
+        }
+        (None, None) => to_kelvin(number, &from).and_then(|kelvin| from_kelvin(kelvin, &to)),
+        _ => None,
+    };
+    match converted {
+        Some(converted) => Ok(round(converted, precision)),
+        None => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("cannot convert {} to {}", from, to),
+        )),
+    }
+}
+
+/// Country calling codes of the regions of the phone numbers.
+///
+/// The numbers of the North American Numbering Plan, i.e. with the code 1, are
+/// grouped as `(512) 555-0123`. The digits of the other regions, all using the
+/// trunk prefix 0, are not grouped.
+const CALLING_CODES: &[(&str, &str)] = &[
+    ("US", "1"),
+    ("CA", "1"),
+    ("GB", "44"),
+    ("IE", "353"),
+    ("FR", "33"),
+    ("BE", "32"),
+    ("NL", "31"),
+    ("DE", "49"),
+    ("AU", "61"),
+    ("NZ", "64"),
+];
+
+/// Format a phone number, e.g. `{{ phone|phone("international", "US") }}`.
+///
+/// The format is `national` (the default), `international`, `e164`, or a
+/// pattern whose `#` are replaced by the digits of the national number, e.g.
+/// `###.###.####`. The region defaults to `US`. The empty values are left as
+/// is.
+fn phone(value: Value, format: Option<String>, region: Option<String>) -> Result<Value, Error> {
+    let text = value.to_string();
+    if text.trim().is_empty() {
+        return Ok(value);
+    }
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid phone number {:?}", text),
+        )
+    };
+    let region = region.unwrap_or_else(|| String::from("US")).to_uppercase();
+    let code = CALLING_CODES
+        .iter()
+        .find(|(name, _)| *name == region)
+        .map(|(_, code)| *code)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("unsupported phone region {:?}", region),
+            )
+        })?;
+
+    // The national number is stripped of the country code and of the trunk
+    // prefix.
+    let digits: String = text.chars().filter(char::is_ascii_digit).collect();
+    let national = match text.trim_start().starts_with('+') {
+        true => digits.strip_prefix(code).ok_or_else(invalid)?,
+        false if code == "1" && digits.len() == 11 => {
+            digits.strip_prefix('1').ok_or_else(invalid)?
+        }
+        false => digits.strip_prefix('0').unwrap_or(&digits),
+    };
+    if national.is_empty() || (code == "1" && national.len() != 10) {
+        return Err(invalid());
+    }
+
+    let format = format.as_deref().unwrap_or("national");
+    let formatted = match format {
+        "e164" => format!("+{}{}", code, national),
+        "international" if code == "1" => format!(
+            "+1 {}-{}-{}",
+            &national[..3],
+            &national[3..6],
+            &national[6..]
+        ),
+        "international" => format!("+{} {}", code, national),
+        "national" if code == "1" => format!(
+            "({}) {}-{}",
+            &national[..3],
+            &national[3..6],
+            &national[6..]
+        ),
+        "national" => format!("0{}", national),
+        pattern if pattern.contains('#') => {
+            if pattern.matches('#').count() != national.len() {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("the pattern {:?} does not fit {:?}", pattern, text),
+                ));
+            }
+            let mut digits = national.chars();
+            pattern
+                .chars()
+                .map(|c| match c {
+                    '#' => digits.next().unwrap_or(c),
+                    c => c,
+                })
+                .collect()
+        }
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("invalid phone format {:?}", format),
+            ))
+        }
+    };
+
+    Ok(Value::from(formatted))
+}
+
+/// Join the items of a list which are not blank, trimmed, e.g.
+/// `{{ [street, suite, city]|join_nonempty(", ") }}`.
+///
+/// The separator defaults to `, `.
+fn join_nonempty(values: Vec<Value>, separator: Option<String>) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| value.trim().to_owned())
+        .collect::<Vec<_>>()
+        .join(separator.as_deref().unwrap_or(", "))
+}
+
+/// Format a postal address on one line, e.g. `123 Main St, Austin, TX 78701`,
+/// skipping the missing parts.
+fn address(
+    street: Option<Value>,
+    city: Option<Value>,
+    region: Option<Value>,
+    postal_code: Option<Value>,
+    country: Option<Value>,
+) -> String {
+    let part = |value: Option<Value>| {
+        value
+            .filter(|value| !value.is_undefined() && !value.is_none())
+            .unwrap_or_else(|| Value::from(""))
+    };
+    let region = join_nonempty(
+        vec![part(region), part(postal_code)],
+        Some(String::from(" ")),
+    );
+    join_nonempty(
+        vec![part(street), part(city), Value::from(region), part(country)],
+        None,
+    )
+}
+
+/// Language of a locale, e.g. `fr` for `fr-CA`, defaulting to English.
+fn language(locale: Option<String>) -> String {
+    locale
+        .as_deref()
+        .unwrap_or("en")
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Convert a template value to a whole number.
+fn integer(value: &Value) -> Result<i64, Error> {
+    let number = number(value, "value")?;
+    if number.fract() != 0.0 || number.abs() >= 1e15 {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("{} is not a whole number", number),
+        ));
+    }
+    Ok(number as i64)
+}
+
+/// Format a number as an ordinal, e.g. `{{ rank|ordinal }}` for `3rd`, in the
+/// language of a locale: `en` (the default), `fr`, `de`, `es`, `it`, `pt` or
+/// `nl`.
+fn ordinal(value: Value, locale: Option<String>) -> Result<Value, Error> {
+    if value.to_string().trim().is_empty() {
+        return Ok(value);
+    }
+    let n = integer(&value)?;
+    let suffix = match language(locale).as_str() {
+        "en" => match (n.abs() % 10, n.abs() % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        },
+        "fr" if n == 1 => "er",
+        "fr" => "e",
+        "de" => ".",
+        "es" => ".º",
+        "it" | "pt" => "º",
+        "nl" => "e",
+        language => {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("unsupported ordinal language {:?}", language),
+            ))
+        }
+    };
+
+    Ok(Value::from(format!("{}{}", n, suffix)))
+}
+
+/// English names of the numbers below twenty.
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+/// English names of the tens.
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+/// English names of the powers of a thousand.
+const SCALES: [&str; 5] = ["", "thousand", "million", "billion", "trillion"];
+
+/// Spell a number below a thousand in English.
+fn spell_hundreds(n: u64) -> String {
+    let (hundreds, rest) = (n / 100, n % 100);
+    let rest = match rest {
+        0 => String::new(),
+        1..=19 => ONES[rest as usize].to_owned(),
+        _ if rest % 10 == 0 => TENS[rest as usize / 10].to_owned(),
+        _ => format!("{}-{}", TENS[rest as usize / 10], ONES[rest as usize % 10]),
+    };
+    match (hundreds, rest.is_empty()) {
+        (0, _) => rest,
+        (_, true) => format!("{} hundred", ONES[hundreds as usize]),
+        (_, false) => format!("{} hundred {}", ONES[hundreds as usize], rest),
+    }
+}
+
+/// Spell a number in English, e.g. `twenty-five`.
+fn spell_english(n: i64) -> String {
+    if n == 0 {
+        return ONES[0].to_owned();
+    }
+    let mut groups = Vec::new();
+    let mut rest = n.unsigned_abs();
+    for scale in SCALES {
+        let group = rest % 1000;
+        if group > 0 {
+            groups.push(match scale {
+                "" => spell_hundreds(group),
+                scale => format!("{} {}", spell_hundreds(group), scale),
+            });
+        }
+        rest /= 1000;
+    }
+    groups.reverse();
+    let spelled = groups.join(" ");
+    match n < 0 {
+        true => format!("minus {}", spelled),
+        false => spelled,
+    }
+}
+
+/// Spell a whole number in words, e.g. `{{ miles|spell_number }}` for
+/// `twenty-five`.
+///
+/// Only English is supported, the locale is accepted for the templates rendered
+/// in several languages, and fails with the others.
+fn spell_number(value: Value, locale: Option<String>) -> Result<Value, Error> {
+    if value.to_string().trim().is_empty() {
+        return Ok(value);
+    }
+    match language(locale).as_str() {
+        "en" => Ok(Value::from(spell_english(integer(&value)?))),
+        language => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("unsupported spelling language {:?}", language),
+        )),
+    }
+}
+
+/// English words left in lowercase by [`titlecase`], unless they start or end
+/// the text.
+const SMALL_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "off", "on", "or", "per",
+    "so", "the", "to", "up", "via", "vs", "yet",
+];
+
+/// Capitalize the first letter of a word, and of the parts of a hyphenated one.
+fn capitalize(word: &str) -> String {
+    let mut capitalized = String::with_capacity(word.len());
+    let mut start = true;
+    for c in word.chars() {
+        match start && c.is_alphanumeric() {
+            true => capitalized.extend(c.to_uppercase()),
+            false => capitalized.push(c),
+        }
+        if c.is_alphanumeric() {
+            start = false;
+        } else if c == '-' || c == '/' {
+            start = true;
+        }
+    }
+    capitalized
+}
+
+/// Convert a text to title case, e.g. `BIKE LANES OF THE CITY` to
+/// `Bike Lanes of the City`.
+///
+/// The small words, such as `of` or `the`, stay in lowercase, except at the
+/// start or the end of the text, or after a colon.
+fn titlecase(value: String) -> String {
+    let lowercase = value.to_lowercase();
+    let words: Vec<&str> = lowercase.split(' ').collect();
+    let last = words.iter().rposition(|word| !word.is_empty());
+    let mut after_colon = true;
+    let mut titled = Vec::with_capacity(words.len());
+    for (i, word) in words.iter().enumerate() {
+        let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+        let small = SMALL_WORDS.contains(&bare) && !after_colon && Some(i) != last;
+        titled.push(match small {
+            true => word.to_string(),
+            false => capitalize(word),
+        });
+        if !word.is_empty() {
+            after_colon = word.ends_with(':');
+        }
+    }
+    titled.join(" ")
+}
+
+/// Replace the straight quotes of a text with typographic ones, e.g.
+/// `"Bike Friendly" city's` with `“Bike Friendly” city’s`.
+///
+/// A quote opens after a space, an opening bracket or another opening quote,
+/// or at the start of the text, and closes otherwise, which makes the
+/// apostrophes closing single quotes.
+fn smart_quotes(value: String) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    let mut previous: Option<char> = None;
+    for c in value.chars() {
+        let opening = match previous {
+            None => true,
+            Some(p) => p.is_whitespace() || matches!(p, '(' | '[' | '{' | '“' | '‘' | '—' | '–'),
+        };
+        let replaced = match (c, opening) {
+            ('"', true) => '“',
+            ('"', false) => '”',
+            ('\'', true) => '‘',
+            ('\'', false) => '’',
+            (c, _) => c,
+        };
+        quoted.push(replaced);
+        previous = Some(replaced);
+    }
+    quoted
+}
+
+/// Non-breaking space.
+const NBSP: char = '\u{a0}';
+
+/// Insert non-breaking spaces where a line must not break: before the last word
+/// of a text, so that it is not left alone on the last line, and after the
+/// numbers, so that they stay with their units, e.g. `25 miles`.
+///
+/// The spaces are inserted as characters, since SVG has no `&nbsp;` entity.
+fn nbsp(value: String) -> String {
+    let words: Vec<&str> = value.split(' ').collect();
+    let mut text = String::with_capacity(value.len());
+    for (i, word) in words.iter().enumerate() {
+        text.push_str(word);
+        if i + 1 == words.len() {
+            break;
+        }
+        let last_space = i + 2 == words.len() && !word.is_empty();
+        let number = word.chars().last().is_some_and(|c| c.is_ascii_digit());
+        text.push(match last_space || number {
+            true => NBSP,
+            false => ' ',
+        });
+    }
+    text
+}
+
+/// Parse a color passed to a template function.
+fn color(value: &str) -> Result<Color, Error> {
+    value
+        .parse()
+        .map_err(|e: String| Error::new(ErrorKind::InvalidOperation, e))
+}
+
+/// Pick the color of a text readable on a background color, e.g.
+/// `contrast_color(background)`: the `light` color (`#fff` by default) or the
+/// `dark` one (`#000` by default), whichever contrasts the most with the
+/// background, as defined by WCAG 2.
+fn contrast_color(
+    background: String,
+    light: Option<String>,
+    dark: Option<String>,
+) -> Result<String, Error> {
+    let background = color(&background)?;
+    let light = light.unwrap_or_else(|| String::from("#fff"));
+    let dark = dark.unwrap_or_else(|| String::from("#000"));
+    let light_contrast = background.contrast(&color(&light)?);
+    let dark_contrast = background.contrast(&color(&dark)?);
+    Ok(match light_contrast >= dark_contrast {
+        true => light,
+        false => dark,
+    })
+}
+
+/// Set the opacity of a color, between 0 and 1, e.g.
+/// `{{ brand|with_alpha(0.5) }}`.
+fn with_alpha(value: String, alpha: f64) -> Result<String, Error> {
+    let color = Color {
+        alpha: alpha.clamp(0.0, 1.0),
+        ..color(&value)?
+    };
+    Ok(color.to_string())
+}
+
+/// Mix a color with another one, `t` being the weight of the other color,
+/// between 0 and 1 (0.5 by default), e.g. `{{ brand|mix("#fff", 0.25) }}`.
+fn mix(value: String, other: String, t: Option<f64>) -> Result<String, Error> {
+    Ok(color(&value)?
+        .mix(&color(&other)?, t.unwrap_or(0.5))
+        .to_string())
+}
+
+/// Text of a table cell, blank when missing.
+fn cell(value: Value) -> String {
+    match value.is_undefined() || value.is_none() {
+        true => String::new(),
+        false => value.to_string(),
+    }
+}
+
+/// Lay out rows as a table, e.g. `table(projects, ["name", ["miles", "Miles"]],
+/// [160, 60], 18)`, with aligned texts and rule lines.
+///
+/// The rows are maps, whose columns are their keys, or lists, whose columns
+/// are their indices. A column is a key, used as its label, or a
+/// `[key, label]` pair. The widths of the columns default to 100, and the
+/// height of the rows to 20.
+fn table(
+    rows: Value,
+    columns: Vec<Value>,
+    widths: Option<Vec<f64>>,
+    row_height: Option<f64>,
+) -> Result<Value, Error> {
+    let (keys, header): (Vec<Value>, Vec<String>) = columns
+        .into_iter()
+        .map(|column| match column.kind() {
+            ValueKind::Seq => Ok((
+                column.get_item(&Value::from(0))?,
+                cell(column.get_item(&Value::from(1))?),
+            )),
+            _ => Ok((column.clone(), column.to_string())),
+        })
+        .collect::<Result<Vec<_>, Error>>()?
+        .into_iter()
+        .unzip();
+    let mut cells = Vec::new();
+    for row in rows.try_iter()? {
+        let values = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| match row.kind() {
+                ValueKind::Seq => row.get_item(&Value::from(i)).map(cell),
+                _ => row.get_item(key).map(cell),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        cells.push(values);
+    }
+    let mut widths = widths.unwrap_or_default();
+    widths.resize(header.len(), 100.0);
+
+    Ok(Value::from_safe_string(svg::table(
+        &header,
+        &cells,
+        &widths,
+        row_height.unwrap_or(20.0),
+    )))
+}
+
+/// Read score bands, i.e. maps with a `label`, a `color` and the `min` value
+/// where the band starts, which the lowest band may omit.
+fn bands(bands: &Value) -> Result<Vec<(Option<f64>, Value)>, Error> {
+    let mut read = Vec::new();
+    for band in bands.try_iter()? {
+        let min = band.get_attr("min")?;
+        let min = match min.is_undefined() || min.is_none() {
+            true => None,
+            false => Some(number(&min, "min")?),
+        };
+        read.push((min, band));
+    }
+    Ok(read)
+}
+
+/// Find the band of a score, e.g. `{{ score|band(bands) }}`, and return its
+/// `label`, or another attribute such as `{{ score|band(bands, "color") }}`.
+///
+/// The band of a score is the one starting the closest below it. A blank
+/// score, or a score below every band, has no band and gives none.
+fn band(value: Value, thresholds: Value, attribute: Option<String>) -> Result<Value, Error> {
+    if value.to_string().trim().is_empty() {
+        return Ok(Value::from(()));
+    }
+    let score = number(&value, "value")?;
+    let found = bands(&thresholds)?
+        .into_iter()
+        .filter(|(min, _)| match min {
+            Some(min) => *min <= score,
+            None => true,
+        })
+        .max_by(|(a, _), (b, _)| a.unwrap_or(f64::MIN).total_cmp(&b.unwrap_or(f64::MIN)));
+    match found {
+        Some((_, band)) => band.get_attr(attribute.as_deref().unwrap_or("label")),
+        None => Ok(Value::from(())),
+    }
+}
+
+/// Lay out a color legend of score bands, e.g. `{{ legend(bands) }}`, as an
+/// SVG fragment of a swatch and a label per band, of a size of `swatch` (12 by
+/// default).
+fn legend(thresholds: Value, swatch: Option<f64>) -> Result<Value, Error> {
+    let entries = bands(&thresholds)?
+        .into_iter()
+        .map(|(_, band)| {
+            let fill = band.get_attr("color")?.to_string();
+            color(&fill)?;
+            Ok((fill, cell(band.get_attr("label")?)))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(Value::from_safe_string(svg::legend(
+        &entries,
+        swatch.unwrap_or(12.0),
+    )))
+}
+
+/// Draw a barcode of a value, e.g. `{{ barcode(id, "code128", 40) }}`, as an
+/// SVG fragment of bars, `height` high (40 by default) and `module` wide per
+/// module (1 by default).
+///
+/// The type is `code128` (the default) or `ean13`. The barcode needs a blank
+/// margin of 10 modules on its sides to be scanned.
+fn barcode(
+    value: Value,
+    symbology: Option<String>,
+    height: Option<f64>,
+    module: Option<f64>,
+) -> Result<Value, Error> {
+    let symbology = match symbology {
+        Some(symbology) => symbology
+            .parse()
+            .map_err(|e: String| Error::new(ErrorKind::InvalidOperation, e))?,
+        None => Symbology::default(),
+    };
+    let modules = barcode::encode(&value.to_string(), symbology)
+        .map_err(|e| Error::new(ErrorKind::InvalidOperation, e))?;
+    Ok(Value::from_safe_string(svg::barcode(
+        &modules,
+        module.unwrap_or(1.0),
+        height.unwrap_or(40.0),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        let convert = |value: &str, from: &str, to: &str, precision: Option<i32>| {
+            convert(Value::from(value), from.into(), to.into(), precision).map(|v| v.to_string())
+        };
+        assert_eq!(convert("10", "km", "mi", Some(2)).unwrap(), "6.21");
+        assert_eq!(convert("100", "m", "ft", Some(0)).unwrap(), "328");
+        assert_eq!(convert("-40", "C", "F", None).unwrap(), "-40.0");
+        assert_eq!(convert("2.5", "ha", "acre", Some(1)).unwrap(), "6.2");
+        assert_eq!(convert(" ", "km", "mi", None).unwrap(), " ");
+        assert!(convert("10", "km", "lb", None).is_err());
+        assert!(convert("10", "km", "c", None).is_err());
+        assert!(convert("ten", "km", "mi", None).is_err());
+    }
+
+    #[test]
+    fn test_phone() {
+        let phone = |value: &str, format: Option<&str>, region: Option<&str>| {
+            phone(
+                Value::from(value),
+                format.map(str::to_owned),
+                region.map(str::to_owned),
+            )
+            .map(|v| v.to_string())
+        };
+        assert_eq!(phone("5125550123", None, None).unwrap(), "(512) 555-0123");
+        assert_eq!(
+            phone("1-512-555-0123", Some("international"), None).unwrap(),
+            "+1 512-555-0123"
+        );
+        assert_eq!(
+            phone("(512) 555 0123", Some("e164"), None).unwrap(),
+            "+15125550123"
+        );
+        assert_eq!(
+            phone("512 555 0123", Some("###.###.####"), None).unwrap(),
+            "512.555.0123"
+        );
+        assert_eq!(
+            phone("020 7946 0018", Some("e164"), Some("gb")).unwrap(),
+            "+442079460018"
+        );
+        assert_eq!(phone("", None, None).unwrap(), "");
+        assert!(phone("555-0123", None, None).is_err());
+        assert!(phone("5125550123", Some("##-##"), None).is_err());
+        assert!(phone("5125550123", None, Some("ZZ")).is_err());
+    }
+
+    #[test]
+    fn test_address() {
+        let value = |s: &str| Some(Value::from(s));
+        assert_eq!(
+            address(
+                value("123 Main St"),
+                value("Austin"),
+                value("TX"),
+                value("78701"),
+                None
+            ),
+            "123 Main St, Austin, TX 78701"
+        );
+        assert_eq!(
+            address(
+                None,
+                value("Provo"),
+                value(""),
+                value("84601"),
+                value("USA")
+            ),
+            "Provo, 84601, USA"
+        );
+        let parts = vec![
+            Value::from(" Suite 2 "),
+            Value::from(""),
+            Value::from("Austin"),
+        ];
+        assert_eq!(
+            join_nonempty(parts, Some(String::from("\n"))),
+            "Suite 2\nAustin"
+        );
+    }
+
+    #[test]
+    fn test_ordinal() {
+        let ordinal = |n: &str, locale: Option<&str>| {
+            ordinal(Value::from(n), locale.map(str::to_owned))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(ordinal("1", None), "1st");
+        assert_eq!(ordinal("22", None), "22nd");
+        assert_eq!(ordinal("113", Some("en-US")), "113th");
+        assert_eq!(ordinal("1", Some("fr-CA")), "1er");
+        assert_eq!(ordinal("3", Some("fr")), "3e");
+        assert!(super::ordinal(Value::from("1.5"), None).is_err());
+        assert!(super::ordinal(Value::from("1"), Some(String::from("ja"))).is_err());
+    }
+
+    #[test]
+    fn test_spell_number() {
+        let spell = |n: &str| spell_number(Value::from(n), None).unwrap().to_string();
+        assert_eq!(spell("0"), "zero");
+        assert_eq!(spell("25"), "twenty-five");
+        assert_eq!(spell("-40"), "minus forty");
+        assert_eq!(spell("1000000"), "one million");
+        assert_eq!(
+            spell("2024317"),
+            "two million twenty-four thousand three hundred seventeen"
+        );
+        assert!(spell_number(Value::from("5"), Some(String::from("fr"))).is_err());
+    }
+
+    #[test]
+    fn test_typography() {
+        assert_eq!(
+            titlecase(String::from("BIKE LANES OF THE CITY")),
+            "Bike Lanes of the City"
+        );
+        assert_eq!(
+            titlecase(String::from("the best: a guide to ride on")),
+            "The Best: A Guide to Ride On"
+        );
+        assert_eq!(titlecase(String::from("salt lake-city")), "Salt Lake-City");
+        assert_eq!(
+            smart_quotes(String::from(r#"The "Bike Friendly" city's ('best')"#)),
+            "The “Bike Friendly” city’s (‘best’)"
+        );
+        assert_eq!(
+            nbsp(String::from("Ride 25 miles to work")),
+            "Ride 25\u{a0}miles to\u{a0}work"
+        );
+        assert_eq!(nbsp(String::from("Austin")), "Austin");
+    }
+
+    #[test]
+    fn test_contrast_color() {
+        let contrast = |background: &str| contrast_color(background.into(), None, None).unwrap();
+        assert_eq!(contrast("#1e3a8a"), "#fff");
+        assert_eq!(contrast("#fde047"), "#000");
+        assert_eq!(
+            contrast_color("#bbb".into(), Some("white".into()), Some("#222".into())).unwrap(),
+            "#222"
+        );
+        assert!(contrast_color("blurple".into(), None, None).is_err());
+    }
+
+    #[test]
+    fn test_colors() {
+        assert_eq!(
+            with_alpha("#3b82f6".into(), 0.5).unwrap(),
+            "rgba(59, 130, 246, 0.5)"
+        );
+        assert_eq!(with_alpha("navy".into(), 1.0).unwrap(), "#000080");
+        assert_eq!(
+            mix("#000".into(), "#fff".into(), Some(0.25)).unwrap(),
+            "#404040"
+        );
+        assert!(mix("#000".into(), "ink".into(), None).is_err());
+    }
+
+    #[test]
+    fn test_table() {
+        let records = vec![
+            std::collections::BTreeMap::from([("name", "Bridge"), ("miles", "1.5")]),
+            std::collections::BTreeMap::from([("name", "Lanes"), ("miles", "")]),
+        ];
+        let rows = Value::from_serializable(&records);
+        let columns = vec![
+            Value::from("name"),
+            Value::from(vec![Value::from("miles"), Value::from("Miles")]),
+        ];
+        let svg = table(rows, columns, Some(vec![80.0]), None)
+            .unwrap()
+            .to_string();
+        assert!(svg.contains(">Bridge</text>"));
+        assert!(svg.contains(">Miles</text>"));
+        assert!(svg.contains("x2=\"180\""));
+
+        let rows = Value::from(vec![Value::from(vec!["Bridge", "1.5"])]);
+        let svg = table(rows, vec![Value::from("name")], None, None).unwrap();
+        assert!(svg.to_string().contains(">Bridge</text>"));
+    }
+
+    #[test]
+    fn test_band() {
+        let records = vec![
+            std::collections::BTreeMap::from([("label", "Low"), ("color", "#d73027")]),
+            std::collections::BTreeMap::from([
+                ("min", "60"),
+                ("label", "High"),
+                ("color", "#1a9850"),
+            ]),
+            std::collections::BTreeMap::from([
+                ("min", "40"),
+                ("label", "Fair"),
+                ("color", "#fee08b"),
+            ]),
+        ];
+        let bands = Value::from_serializable(&records);
+        let band = |value: &str, attribute: Option<&str>| {
+            band(value.into(), bands.clone(), attribute.map(String::from)).map(|v| v.to_string())
+        };
+        assert_eq!(band("12", None).unwrap(), "Low");
+        assert_eq!(band("40", None).unwrap(), "Fair");
+        assert_eq!(band("87.5", Some("color")).unwrap(), "#1a9850");
+        assert!(super::band("".into(), bands.clone(), None)
+            .unwrap()
+            .is_none());
+        assert!(band("high", None).is_err());
+
+        let svg = legend(bands.clone(), None).unwrap().to_string();
+        assert_eq!(svg.matches("<rect ").count(), 3);
+        assert!(svg.contains(">Fair</text>"));
+    }
+}
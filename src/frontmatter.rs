@@ -0,0 +1,191 @@
+use crate::{
+    export::Exporter,
+    svg::PageSize,
+    template::{read_template, Record},
+};
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use regex::Regex;
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// Name of the configuration file of a project.
+pub const CONFIG_FILE: &str = "svggloo.toml";
+
+/// Defaults carried by a template, in a front matter block, or by the
+/// configuration file of its project.
+///
+/// The options of the command line take precedence over them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter {
+    /// Data fields used to name the rendered files.
+    pub field: Option<Vec<String>>,
+    /// Separator used to join the naming fields.
+    pub separator: Option<String>,
+    /// Columns the data file must have.
+    pub required: Vec<String>,
+    /// Page size of the rendered files.
+    pub page_size: Option<PageSize>,
+    /// Exporter used to convert the rendered files.
+    pub exporter: Option<Exporter>,
+}
+
+/// Content of a front matter block or of a configuration file, as written.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawFrontMatter {
+    field: Option<Vec<String>>,
+    separator: Option<String>,
+    required: Vec<String>,
+    page_size: Option<String>,
+    exporter: Option<String>,
+}
+
+impl FrontMatter {
+    /// Read the front matter of a template file, or of the standard input if
+    /// the path is `-`.
+    pub fn from_template(template: &Path) -> Result<Self, Report> {
+        let source = read_template(template)?;
+        Self::parse(&source)
+            .map_err(|e| eyre!("Invalid front matter in {}: {}", template.display(), e))
+    }
+
+    /// Parse the front matter of a template.
+    ///
+    /// The front matter is a YAML block, either in a comment starting with
+    /// `svggloo:` or in a `<metadata id="svggloo">` element, optionally wrapped
+    /// in a CDATA section. A template without front matter has no defaults.
+    pub fn parse(source: &str) -> Result<Self, Report> {
+        let comment = Regex::new(r"(?s)<!--\s*svggloo:[ \t]*\r?\n(.*?)-->").unwrap();
+        let metadata = Regex::new(
+            r#"(?s)<metadata\b[^>]*\bid="svggloo"[^>]*>\s*(?:<!\[CDATA\[(.*?)\]\]>|(.*?))\s*</metadata>"#,
+        )
+        .unwrap();
+        let yaml = if let Some(caps) = comment.captures(source) {
+            caps[1].to_owned()
+        } else if let Some(caps) = metadata.captures(source) {
+            match (caps.get(1), caps.get(2)) {
+                (Some(cdata), _) => cdata.as_str().to_owned(),
+                (None, Some(text)) => quick_xml::escape::unescape(text.as_str())?.into_owned(),
+                (None, None) => String::new(),
+            }
+        } else {
+            return Ok(Self::default());
+        };
+        if yaml.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        Self::from_raw(serde_yaml::from_str(&yaml)?)
+    }
+
+    /// Read the `svggloo.toml` configuration of a project directory, which
+    /// holds the same keys as the front matter.
+    ///
+    /// A directory without configuration has no defaults.
+    pub fn from_project(dir: &Path) -> Result<Self, Report> {
+        let path = dir.join(CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let raw = toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| eyre!("Invalid configuration {}: {}", path.display(), e))?;
+        Self::from_raw(raw)
+    }
+
+    /// Fill the missing defaults with the ones of `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            field: self.field.or(other.field),
+            separator: self.separator.or(other.separator),
+            required: if self.required.is_empty() {
+                other.required
+            } else {
+                self.required
+            },
+            page_size: self.page_size.or(other.page_size),
+            exporter: self.exporter.or(other.exporter),
+        }
+    }
+
+    /// Convert the defaults as written.
+    fn from_raw(raw: RawFrontMatter) -> Result<Self, Report> {
+        Ok(Self {
+            field: raw.field,
+            separator: raw.separator,
+            required: raw.required,
+            page_size: raw
+                .page_size
+                .map(|size| size.parse())
+                .transpose()
+                .map_err(|e: String| eyre!(e))?,
+            exporter: raw
+                .exporter
+                .map(|name| {
+                    Exporter::from_str(&name, true)
+                        .map_err(|_| eyre!("Unknown exporter {:?}", name))
+                })
+                .transpose()?,
+        })
+    }
+
+    /// Check that the records have the required columns.
+    pub fn check_columns(&self, records: &[Record]) -> Result<(), Report> {
+        let Some(record) = records.first() else {
+            return Ok(());
+        };
+        match self
+            .required
+            .iter()
+            .find(|column| !record.contains_key(*column))
+        {
+            Some(column) => Err(eyre!(
+                "The template requires the {:?} column, missing from the data",
+                column
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let source = "<svg>
+<!-- svggloo:
+field: [state, city]
+separator: _
+required: [city]
+page_size: letter
+exporter: cairo-svg
+-->
+<text>{{ city }}</text>
+</svg>";
+        let front_matter = FrontMatter::parse(source).unwrap();
+        assert_eq!(
+            front_matter.field,
+            Some(vec![String::from("state"), String::from("city")])
+        );
+        assert_eq!(front_matter.separator.as_deref(), Some("_"));
+        assert_eq!(front_matter.page_size, "letter".parse().ok());
+        assert_eq!(front_matter.exporter, Some(Exporter::CairoSVG));
+        let record = Record::from([(String::from("state"), String::from("TX"))]);
+        assert!(front_matter.check_columns(&[record]).is_err());
+
+        let source = r#"<svg><metadata id="svggloo">separator: " &amp; "</metadata></svg>"#;
+        let front_matter = FrontMatter::parse(source).unwrap();
+        assert_eq!(front_matter.separator.as_deref(), Some(" & "));
+
+        assert_eq!(
+            FrontMatter::parse("<svg/>").unwrap(),
+            FrontMatter::default()
+        );
+        assert!(FrontMatter::parse("<!-- svggloo:\nfields: [city]\n-->").is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use color_eyre::{eyre::Report, Result};
+use minijinja::Environment;
+use serde::Serialize;
+
+/// Template of the gallery.
+const GALLERY_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>{{ title }}</title>
+  <style>
+    body { font-family: sans-serif; margin: 2em; }
+    input { font-size: 1em; margin-bottom: 1em; padding: 0.3em; width: 20em; }
+    .gallery { display: grid; gap: 1em; grid-template-columns: repeat(auto-fill, minmax(220px, 1fr)); }
+    figure { border: 1px solid #ccc; margin: 0; padding: 0.5em; }
+    figure img { display: block; height: 200px; margin: auto; max-width: 100%; object-fit: contain; }
+    figcaption { font-size: 0.85em; }
+    dl { display: grid; gap: 0 0.5em; grid-template-columns: auto 1fr; margin: 0.5em 0; }
+    dt { color: #666; }
+    dd { margin: 0; }
+  </style>
+</head>
+<body>
+  <h1>{{ title }}</h1>
+  <input type="search" placeholder="Filter" oninput="filter(this.value)">
+  <div class="gallery">
+    {% for entry in entries %}
+    <figure data-search="{{ entry.search }}">
+      <a href="{{ entry.file_name }}"><img src="{{ entry.thumbnail or entry.file_name }}" alt="{{ entry.file_name }}" loading="lazy"></a>
+      <figcaption>
+        <dl>
+          {% for column, value in entry.fields %}<dt>{{ column }}</dt><dd>{{ value }}</dd>{% endfor %}
+        </dl>
+        <a href="{{ entry.file_name }}">SVG</a>{% if entry.pdf %} · <a href="{{ entry.pdf }}">PDF</a>{% endif %}
+      </figcaption>
+    </figure>
+    {% endfor %}
+  </div>
+  <script>
+    function filter(text) {
+      text = text.toLowerCase();
+      for (const figure of document.querySelectorAll("figure")) {
+        figure.hidden = !figure.dataset.search.includes(text);
+      }
+    }
+  </script>
+</body>
+</html>
+"#;
+
+/// An entry of the gallery.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// Name of the rendered file, relatively to the gallery.
+    pub file_name: String,
+    /// Thumbnail of the rendered file, relatively to the gallery.
+    pub thumbnail: Option<String>,
+    /// Name of the exported PDF file, relatively to the gallery.
+    pub pdf: Option<String>,
+    /// Key fields of the record, as `(column, value)` pairs.
+    pub fields: Vec<(String, String)>,
+}
+
+/// Render the HTML gallery of the rendered files.
+///
+/// The gallery shows a thumbnail of each rendered file along with the key
+/// fields of its record, and links to the rendered and exported files. It is
+/// meant to be written in the output directory, next to the rendered files.
+pub fn gallery(title: &str, entries: &[Entry]) -> Result<String, Report> {
+    #[derive(Serialize)]
+    struct GalleryEntry<'a> {
+        #[serde(flatten)]
+        entry: &'a Entry,
+        search: String,
+    }
+    #[derive(Serialize)]
+    struct GalleryContext<'a> {
+        title: &'a str,
+        entries: Vec<GalleryEntry<'a>>,
+    }
+
+    let entries = entries
+        .iter()
+        .map(|entry| GalleryEntry {
+            entry,
+            search: entry
+                .fields
+                .iter()
+                .map(|(_, value)| value.to_lowercase())
+                .chain([entry.file_name.to_lowercase()])
+                .collect::<Vec<_>>()
+                .join(" "),
+        })
+        .collect();
+    let mut env = Environment::new();
+    env.add_template("index.html", GALLERY_TEMPLATE)?;
+    let gallery = env
+        .get_template("index.html")?
+        .render(GalleryContext { title, entries })?;
+
+    Ok(gallery)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gallery() {
+        let entries = vec![Entry {
+            file_name: String::from("austin-tx.svg"),
+            thumbnail: None,
+            pdf: Some(String::from("austin-tx.pdf")),
+            fields: vec![(String::from("city"), String::from("Austin <TX>"))],
+        }];
+        let html = gallery("Scorecards", &entries).unwrap();
+        assert!(html.contains(r#"<img src="austin-tx.svg""#));
+        assert!(html.contains(r#"<a href="austin-tx.pdf">PDF</a>"#));
+        assert!(html.contains("<dd>Austin &lt;TX&gt;</dd>"));
+        assert!(html.contains(r#"data-search="austin &lt;tx&gt; austin-tx.svg""#));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::template::Record;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use regex::{Captures, Regex};
+use std::{path::Path, process::Command, str::FromStr};
+
+/// A command run for each produced file.
+///
+/// The arguments may contain placeholders: `{input}` is replaced with the
+/// rendered SVG file, `{output}` with the final file (the exported file, or the
+/// rendered file without exporter), and `{column}` with the value of a column
+/// of the record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostCommand {
+    args: Vec<String>,
+}
+
+impl FromStr for PostCommand {
+    type Err = String;
+
+    /// Split a command line into arguments, following the shell quoting rules.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args = shell_words::split(s).map_err(|e| format!("invalid command: {}", e))?;
+        if args.is_empty() {
+            return Err(String::from("empty command"));
+        }
+        Ok(Self { args })
+    }
+}
+
+impl PostCommand {
+    /// Build the command for a produced file.
+    ///
+    /// The placeholders are replaced in each argument, and the command is run
+    /// without a shell, so that the values cannot be interpreted by it.
+    fn command(&self, input: &Path, output: &Path, record: &Record) -> Result<Command, Report> {
+        let placeholder = Regex::new(r"\{(\w[\w.-]*)\}").unwrap();
+        let mut args = Vec::new();
+        for arg in &self.args {
+            let mut unknown = None;
+            let arg = placeholder.replace_all(arg, |caps: &Captures| match &caps[1] {
+                "input" => input.to_string_lossy().into_owned(),
+                "output" => output.to_string_lossy().into_owned(),
+                name => match record.get(name) {
+                    Some(value) => value.clone(),
+                    None => {
+                        unknown = Some(name.to_owned());
+                        String::new()
+                    }
+                },
+            });
+            if let Some(name) = unknown {
+                return Err(eyre!("Unknown placeholder {{{}}}", name));
+            }
+            args.push(arg.into_owned());
+        }
+
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]);
+        Ok(command)
+    }
+
+    /// Run the command for a produced file.
+    pub fn run(&self, input: &Path, output: &Path, record: &Record) -> Result<(), Report> {
+        let result = self
+            .command(input, output, record)?
+            .output()
+            .map_err(|e| eyre!("cannot run {}: {}", self.args[0], e))?;
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            return Err(match stderr.trim() {
+                "" => eyre!("{} failed ({})", self.args[0], result.status),
+                stderr => eyre!("{} failed: {}", self.args[0], stderr),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let post_cmd: PostCommand = "stamp --label '{city} ({state})' {input} -o {output}"
+            .parse()
+            .unwrap();
+        let record = Record::from([
+            (String::from("city"), String::from("Austin; rm -rf /")),
+            (String::from("state"), String::from("TX")),
+        ]);
+        let command = post_cmd
+            .command(Path::new("austin.svg"), Path::new("austin.pdf"), &record)
+            .unwrap();
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(command.get_program(), "stamp");
+        assert_eq!(
+            args,
+            [
+                "--label",
+                "Austin; rm -rf / (TX)",
+                "austin.svg",
+                "-o",
+                "austin.pdf"
+            ]
+        );
+
+        let post_cmd: PostCommand = "stamp {county}".parse().unwrap();
+        assert!(post_cmd
+            .command(Path::new("austin.svg"), Path::new("austin.pdf"), &record)
+            .is_err());
+        assert!("".parse::<PostCommand>().is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use minijinja::{Error, ErrorKind, Value};
+use std::{fs, path::Path};
+
+/// Load an icon from the icon directory and inline it.
+///
+/// The icon named `name` is looked up as `<icon_dir>/<name>.svg`. The outer
+/// `<svg>` element is stripped so that the content can be placed directly in
+/// the template, usually inside a positioned `<g>` element.
+pub fn icon(icon_dir: &Path, name: &str) -> Result<Value, Error> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid icon name {:?}", name),
+        ));
+    }
+    let path = match Path::new(name).extension() {
+        Some(_) => icon_dir.join(name),
+        None => icon_dir.join(format!("{}.svg", name)),
+    };
+    let source = fs::read_to_string(&path).map_err(|e| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("cannot load icon {:?} from {}: {}", name, path.display(), e),
+        )
+    })?;
+
+    Ok(Value::from_safe_string(
+        strip_svg_element(&source).to_owned(),
+    ))
+}
+
+/// Strip the outer `<svg>` element of an SVG document.
+///
+/// Everything preceding the opening tag (XML declaration, doctype, comments) is
+/// dropped as well.
+fn strip_svg_element(source: &str) -> &str {
+    let start = match source.find("<svg") {
+        Some(start) => start,
+        None => return source.trim(),
+    };
+    let open_end = match source[start..].find('>') {
+        Some(end) => start + end,
+        None => return "",
+    };
+    // A self-closing root element has no content.
+    if source[..open_end].ends_with('/') {
+        return "";
+    }
+    let close = source.rfind("</svg>").unwrap_or(source.len());
+    if close <= open_end {
+        return "";
+    }
+
+    source[open_end + 1..close].trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_svg_element() {
+        let source = r#"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+  <circle r="5"/>
+</svg>
+"#;
+        assert_eq!(strip_svg_element(source), r#"<circle r="5"/>"#);
+        assert_eq!(strip_svg_element("<svg/>"), "");
+    }
+}
@@ -0,0 +1,81 @@
+use crate::frontmatter::CONFIG_FILE;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use std::{fs, path::Path};
+
+/// Starter template, using the columns of the starter data.
+const TEMPLATE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="600" height="300" viewBox="0 0 600 300">
+  <rect width="600" height="300" fill="#f4f6f8"/>
+  <text x="40" y="80" font-family="sans-serif" font-size="40" font-weight="bold">{{ city }}, {{ state }}</text>
+  <text x="40" y="130" font-family="sans-serif" font-size="24">Score: {{ score }} / 100</text>
+  <rect x="40" y="160" width="500" height="30" fill="#d0d7de"/>
+  <rect x="40" y="160" width="{{ score|int * 5 }}" height="30" fill="#2da44e"/>
+  {% if note %}
+  <text x="40" y="240" font-family="sans-serif" font-size="18">{{ note }}</text>
+  {% endif %}
+</svg>
+"##;
+
+/// Starter data.
+const DATA: &str = "city,state,score,note
+Austin,TX,72,Most improved city
+Provo,UT,64,
+Portland,OR,81,Top 10 city
+";
+
+/// Starter configuration, naming the rendered files after the state and the
+/// city.
+const CONFIG: &str = r#"# Defaults of svggloo, overridden by the options of the command line.
+field = ["state", "city"]
+separator = "-"
+required = ["city", "state", "score"]
+# page_size = "letter"
+# exporter = "cairo-svg"
+"#;
+
+/// Name of the starter template, the data file being next to it.
+pub const TEMPLATE_FILE: &str = "scorecard.svg";
+
+/// Scaffold a new project: a starter template, its data file, and a
+/// configuration file.
+///
+/// The directory is created if needed, and must be empty otherwise.
+pub fn init(dir: &Path) -> Result<(), Report> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+        return Err(eyre!("The directory {} is not empty", dir.display()));
+    }
+    fs::create_dir_all(dir)?;
+    let template = dir.join(TEMPLATE_FILE);
+    fs::write(&template, TEMPLATE)?;
+    fs::write(template.with_extension("csv"), DATA)?;
+    fs::write(dir.join(CONFIG_FILE), CONFIG)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frontmatter::FrontMatter,
+        template::{read_records, render_record},
+    };
+
+    #[test]
+    fn test_init() {
+        let dir = std::env::temp_dir().join("svggloo-test-init");
+        let _ = fs::remove_dir_all(&dir);
+        init(&dir).unwrap();
+        assert!(init(&dir).is_err());
+
+        let config = FrontMatter::from_project(&dir).unwrap();
+        assert_eq!(config.separator.as_deref(), Some("-"));
+        let records = read_records(&dir.join("scorecard.csv")).unwrap();
+        config.check_columns(&records).unwrap();
+        let svg = render_record(TEMPLATE, &records[0]).unwrap();
+        assert!(svg.contains("Austin, TX"));
+        assert!(svg.contains(r#"width="360""#));
+    }
+}
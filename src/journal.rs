@@ -0,0 +1,94 @@
+use color_eyre::{eyre::Report, Result};
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Name of the journal file, in the output directory.
+pub const JOURNAL_FILE: &str = ".svggloo-journal";
+
+/// Journal of the files produced by a run, used to resume it once interrupted.
+///
+/// Each line records the key and the name of a file produced in the output
+/// directory, as soon as it is done, so that the journal survives a crash.
+#[derive(Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    done: HashSet<(String, String)>,
+    file: File,
+}
+
+impl Journal {
+    /// Open the journal of an output directory.
+    ///
+    /// When resuming, the entries of the previous run are kept. Otherwise the
+    /// journal starts empty.
+    pub fn open(dir: &Path, resume: bool) -> Result<Self, Report> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(JOURNAL_FILE);
+        let done = match fs::read_to_string(&path) {
+            Ok(journal) if resume => journal
+                .lines()
+                .filter_map(|line| line.split_once(' '))
+                .map(|(key, file_name)| (key.to_owned(), file_name.to_owned()))
+                .collect(),
+            _ => HashSet::new(),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            done,
+            file,
+        })
+    }
+
+    /// Return the content of a file produced by the previous run, if it was
+    /// recorded with the same key.
+    pub fn get(&self, key: &str, file_name: &str) -> Option<String> {
+        if !self.done.contains(&(key.to_owned(), file_name.to_owned())) {
+            return None;
+        }
+        fs::read_to_string(self.dir.join(file_name)).ok()
+    }
+
+    /// Record a file produced in the output directory.
+    pub fn record(&self, key: &str, file_name: &str) -> Result<(), Report> {
+        writeln!(&self.file, "{} {}", key, file_name)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal() {
+        let dir = std::env::temp_dir().join("svggloo-test-journal");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("austin.svg"), "<svg/>").unwrap();
+
+        let journal = Journal::open(&dir, false).unwrap();
+        journal.record("0123", "austin.svg").unwrap();
+        assert_eq!(journal.get("0123", "austin.svg"), None);
+
+        let journal = Journal::open(&dir, true).unwrap();
+        assert_eq!(journal.get("0123", "austin.svg").as_deref(), Some("<svg/>"));
+        assert_eq!(journal.get("4567", "austin.svg"), None);
+
+        let journal = Journal::open(&dir, false).unwrap();
+        drop(journal);
+        let journal = Journal::open(&dir, true).unwrap();
+        assert_eq!(journal.get("0123", "austin.svg"), None);
+    }
+}
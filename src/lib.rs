@@ -1,14 +1,148 @@
 #![doc = include_str!("../README.md")]
+
+/// Print an informational message on the standard error, unless the quiet mode
+/// is enabled.
+#[macro_export]
+macro_rules! note {
+    ($($arg:tt)*) => {
+        if !$crate::is_quiet() {
+            $crate::report(format!($($arg)*));
+        }
+    };
+}
+
+#[cfg(feature = "native")]
+pub mod atomic;
+pub mod barcode;
+#[cfg(feature = "native")]
+pub mod blob;
+#[cfg(feature = "native")]
+pub mod cache;
+#[cfg(feature = "native")]
 pub mod cli;
+pub mod color;
+pub mod data;
+#[cfg(feature = "native")]
+pub mod diff;
+#[cfg(feature = "native")]
+pub mod email;
+pub mod export;
+pub mod fake;
+pub mod filters;
+#[cfg(feature = "native")]
+pub mod frontmatter;
+pub mod gallery;
+#[cfg(feature = "native")]
+pub mod hook;
+#[cfg(feature = "native")]
+pub mod icon;
+#[cfg(feature = "native")]
+pub mod init;
+#[cfg(feature = "native")]
+pub mod journal;
+#[cfg(feature = "native")]
+pub mod lint;
+#[cfg(feature = "native")]
+pub mod manifest;
+pub mod map;
+#[cfg(feature = "native")]
+pub mod notify;
+#[cfg(feature = "native")]
+pub mod pdf;
+#[cfg(feature = "native")]
+pub mod print;
+#[cfg(feature = "native")]
+pub mod profile;
+#[cfg(feature = "native")]
+pub mod queue;
+#[cfg(feature = "native")]
+pub mod raster;
+#[cfg(feature = "native")]
+pub mod reader;
+pub mod schema;
+#[cfg(feature = "native")]
+pub mod server;
+#[cfg(feature = "sheets")]
+pub mod sheets;
+#[cfg(feature = "native")]
+pub mod snapshot;
+pub mod source;
+#[cfg(feature = "native")]
+pub mod space;
+pub mod svg;
 pub mod template;
+pub mod theme;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "native")]
+pub mod upload;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use color_eyre::{
+    config::{HookBuilder, Theme},
+    eyre::Report,
+    Result,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+/// Whether the informational messages are suppressed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppress the informational messages, only the errors being reported.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Tell whether the informational messages are suppressed.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Messages captured instead of being printed, see [`capture`].
+static CAPTURED: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Print a message on the standard error, or capture it while [`capture`]
+/// runs.
+pub fn report(message: String) {
+    match CAPTURED.lock().unwrap().as_mut() {
+        Some(captured) => captured.push(message),
+        None => eprintln!("{}", message),
+    }
+}
 
-use color_eyre::{eyre::Report, Result};
+/// Run a function, capturing the messages reported meanwhile instead of
+/// printing them, e.g. while a terminal interface owns the screen.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    *CAPTURED.lock().unwrap() = Some(Vec::new());
+    let result = f();
+    let captured = CAPTURED.lock().unwrap().take().unwrap_or_default();
+    (result, captured)
+}
 
 /// Setup the application.
 ///
-/// Set up the `color_eyre` hooks.
+/// Set up the `color_eyre` hooks, without colors if the `NO_COLOR` environment
+/// variable is set to a non-empty value.
 pub fn setup() -> Result<(), Report> {
-    color_eyre::install()?;
+    setup_with_color(true)
+}
+
+/// Setup the application, with or without colors.
+///
+/// Set up the `color_eyre` hooks, without colors if `color` is false or if the
+/// `NO_COLOR` environment variable is set to a non-empty value.
+pub fn setup_with_color(color: bool) -> Result<(), Report> {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let theme = if color && !no_color {
+        Theme::dark()
+    } else {
+        Theme::new()
+    };
+    HookBuilder::default().theme(theme).install()?;
 
     Ok(())
 }
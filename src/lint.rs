@@ -0,0 +1,336 @@
+use crate::fake::variable_offsets;
+use minijinja::Environment;
+use regex::Regex;
+use std::{collections::HashSet, fmt, path::Path};
+
+/// Filters available in the templates.
+const FILTERS: &[&str] = &[
+    "abs",
+    "attr",
+    "band",
+    "batch",
+    "bool",
+    "c_to_f",
+    "capitalize",
+    "convert",
+    "count",
+    "d",
+    "darken",
+    "default",
+    "dictsort",
+    "e",
+    "escape",
+    "first",
+    "float",
+    "indent",
+    "int",
+    "items",
+    "join",
+    "join_nonempty",
+    "km_to_mi",
+    "last",
+    "length",
+    "lighten",
+    "lines",
+    "list",
+    "lower",
+    "m_to_ft",
+    "map",
+    "max",
+    "min",
+    "mix",
+    "nbsp",
+    "ordinal",
+    "phone",
+    "pprint",
+    "reject",
+    "rejectattr",
+    "replace",
+    "reverse",
+    "round",
+    "safe",
+    "select",
+    "selectattr",
+    "slice",
+    "smart_quotes",
+    "sort",
+    "spell_number",
+    "string",
+    "sum",
+    "title",
+    "titlecase",
+    "tojson",
+    "trim",
+    "unique",
+    "upper",
+    "urlencode",
+    "with_alpha",
+];
+
+/// A problem found in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// Line of the problem, starting at 1.
+    pub line: usize,
+    /// Column of the problem, in characters, starting at 1.
+    pub column: usize,
+    /// Description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// A tag of a template: an expression, a statement or a comment.
+struct Tag<'a> {
+    /// Offset of the opening delimiter.
+    start: usize,
+    /// Offset following the closing delimiter.
+    end: usize,
+    /// Second character of the opening delimiter: `{`, `%` or `#`.
+    kind: char,
+    /// Content between the delimiters.
+    inner: &'a str,
+}
+
+/// Split a template into its tags, in order.
+///
+/// An unterminated tag ends the scan, the syntax check reports it.
+fn tags(source: &str) -> Vec<Tag<'_>> {
+    let mut tags = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = source[offset..].find('{') {
+        let start = offset + found;
+        let (kind, close) = match source[start + 1..].chars().next() {
+            Some('{') => ('{', "}}"),
+            Some('%') => ('%', "%}"),
+            Some('#') => ('#', "#}"),
+            _ => {
+                offset = start + 1;
+                continue;
+            }
+        };
+        let Some(length) = source[start + 2..].find(close) else {
+            break;
+        };
+        let end = start + 2 + length + 2;
+        tags.push(Tag {
+            start,
+            end,
+            kind,
+            inner: &source[start + 2..end - 2],
+        });
+        offset = end;
+    }
+
+    tags
+}
+
+/// Convert a byte offset into a line and a column.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |i| i + 1)..]
+        .chars()
+        .count()
+        + 1;
+
+    (line, column)
+}
+
+/// Lint a template.
+///
+/// The problems are reported in order of appearance:
+/// - the syntax errors,
+/// - the variables not matching any of the `columns` of the data file, when
+///   they are known,
+/// - the whitespace control markers joining the words of a text,
+/// - the `&` of the literal text which do not start an entity,
+/// - the included or imported files missing from the `template_dir`,
+/// - the unknown filters.
+pub fn lint_template(
+    source: &str,
+    columns: Option<&[String]>,
+    template_dir: &Path,
+) -> Vec<Problem> {
+    let mut problems: Vec<(usize, String)> = Vec::new();
+
+    let mut env = Environment::new();
+    if let Err(e) = env.add_template("template", source) {
+        // The parser only reports the line of the error.
+        let offset = e
+            .line()
+            .and_then(|line| source.split_inclusive('\n').nth(line - 1))
+            .map_or(0, |line| line.as_ptr() as usize - source.as_ptr() as usize);
+        problems.push((offset, format!("Syntax error: {}", e)));
+    }
+
+    if let Some(columns) = columns {
+        let mut reported = HashSet::new();
+        for (name, offset) in variable_offsets(source) {
+            if !columns.iter().any(|column| column == name) && reported.insert(name) {
+                problems.push((
+                    offset,
+                    format!(
+                        "Variable {} does not match any column of the data file",
+                        name
+                    ),
+                ));
+            }
+        }
+    }
+
+    let tags = tags(source);
+    check_ampersands(source, &tags, &mut problems);
+    for tag in &tags {
+        if tag.kind == '#' {
+            continue;
+        }
+        check_whitespace_control(source, tag, &mut problems);
+        if tag.kind == '%' {
+            check_include(tag, template_dir, &mut problems);
+        }
+        check_filters(tag, &mut problems);
+    }
+
+    problems.sort_by_key(|(offset, _)| *offset);
+    problems
+        .into_iter()
+        .map(|(offset, message)| {
+            let (line, column) = position(source, offset);
+            Problem {
+                line,
+                column,
+                message,
+            }
+        })
+        .collect()
+}
+
+/// Report the `&` of the literal text which do not start an entity.
+///
+/// The comments and the CDATA sections of the SVG are skipped.
+fn check_ampersands(source: &str, tags: &[Tag], problems: &mut Vec<(usize, String)>) {
+    let entity = Regex::new(r"^&(?:#[0-9]+|#x[0-9a-fA-F]+|[A-Za-z][A-Za-z0-9]*);").unwrap();
+    let skipped = Regex::new(r"(?s)<!--.*?-->|<!\[CDATA\[.*?\]\]>").unwrap();
+    let sections: Vec<_> = skipped.find_iter(source).map(|m| m.range()).collect();
+    for (offset, _) in source.match_indices('&') {
+        let in_tag = tags
+            .iter()
+            .any(|tag| (tag.start..tag.end).contains(&offset));
+        let in_section = sections.iter().any(|section| section.contains(&offset));
+        if !in_tag && !in_section && !entity.is_match(&source[offset..]) {
+            problems.push((
+                offset,
+                String::from("Unescaped & in the text, use &amp; instead"),
+            ));
+        }
+    }
+}
+
+/// Report the whitespace control markers stripping the space between two
+/// words, e.g. `Hello {{- name }}` rendered as `HelloAlex`.
+fn check_whitespace_control(source: &str, tag: &Tag, problems: &mut Vec<(usize, String)>) {
+    let is_word = |c: Option<char>| c.is_some_and(|c| !c.is_whitespace() && c != '>' && c != '<');
+    let is_space = |c: char| c == ' ' || c == '\t';
+
+    if tag.inner.starts_with('-') {
+        let before = source[..tag.start].trim_end_matches(is_space);
+        if before.len() < tag.start && is_word(before.chars().next_back()) {
+            problems.push((
+                tag.start,
+                String::from("Whitespace control strips the space before the tag"),
+            ));
+        }
+    }
+    if tag.inner.ends_with('-') {
+        let after = source[tag.end..].trim_start_matches(is_space);
+        if after.len() < source.len() - tag.end && is_word(after.chars().next()) {
+            problems.push((
+                tag.end - 3,
+                String::from("Whitespace control strips the space after the tag"),
+            ));
+        }
+    }
+}
+
+/// Report the included, imported or extended files which do not exist.
+fn check_include(tag: &Tag, template_dir: &Path, problems: &mut Vec<(usize, String)>) {
+    let statement =
+        Regex::new(r#"^[-+]?\s*(?:include|import|extends|from)\s+["']([^"']+)["']"#).unwrap();
+    if let Some(caps) = statement.captures(tag.inner) {
+        let ignored = tag.inner.contains("ignore missing");
+        if !ignored && !template_dir.join(&caps[1]).is_file() {
+            problems.push((
+                tag.start,
+                format!("Included file {} does not exist", &caps[1]),
+            ));
+        }
+    }
+}
+
+/// Report the unknown filters.
+fn check_filters(tag: &Tag, problems: &mut Vec<(usize, String)>) {
+    let offset = tag.start + 2;
+    let bytes = tag.inner.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'"' || c == b'\'' {
+            // Skip the string literals.
+            i += 1;
+            while i < bytes.len() && bytes[i] != c {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+        } else if c == b'|' {
+            let rest = &tag.inner[i + 1..];
+            let start = i + 1 + rest.len() - rest.trim_start().len();
+            let end = tag.inner[start..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .map_or(tag.inner.len(), |length| start + length);
+            let name = &tag.inner[start..end];
+            if !name.is_empty() && !FILTERS.contains(&name) {
+                problems.push((offset + start, format!("Unknown filter {}", name)));
+            }
+        }
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_template() {
+        let source = "<svg>
+  <text>{{ city|upper }}, {{ sate }}</text>
+  <text>Hello {{- name|shout }} &amp; Fish & Chips</text>
+  <!-- Fish & Chips -->
+  {% include 'footer.svg' %}
+</svg>";
+        let columns = [String::from("city"), String::from("name")];
+        let problems: Vec<String> =
+            lint_template(source, Some(&columns), Path::new("/nonexistent"))
+                .iter()
+                .map(|problem| problem.to_string())
+                .collect();
+        assert_eq!(
+            problems,
+            [
+                "2:30: Variable sate does not match any column of the data file",
+                "3:15: Whitespace control strips the space before the tag",
+                "3:24: Unknown filter shout",
+                "3:44: Unescaped & in the text, use &amp; instead",
+                "5:3: Included file footer.svg does not exist",
+            ]
+        );
+
+        let problems = lint_template("<text>{% if %}</text>", None, Path::new(""));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.starts_with("Syntax error"));
+    }
+}
@@ -1,24 +1,539 @@
-use clap::Parser;
-use color_eyre::{eyre::Report, Result};
-use svggloo::cli::Opts;
-use svggloo::setup;
-use svggloo::template::render;
+use clap::{Parser, ValueEnum};
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use svggloo::cli::{
+    Command, DiffArgs, FakeArgs, InitArgs, LintArgs, Opts, QueueArgs, RenderArgs, ServeApiArgs,
+    SnapshotArgs, TuiArgs, VerifyArgs,
+};
+use svggloo::data::Pivot;
+use svggloo::diff::{diff_dirs, html_report};
+use svggloo::email::EmailOptions;
+use svggloo::export::{Exporter, ProcessLimits};
+use svggloo::fake::{variables, write_fake_csv};
+use svggloo::frontmatter::FrontMatter;
+use svggloo::init::TEMPLATE_FILE;
+use svggloo::lint::lint_template;
+use svggloo::manifest::Manifest;
+use svggloo::map::MapOptions;
+use svggloo::pdf::{Booklet, Navigation, SignOptions};
+use svggloo::print::PrintOptions;
+use svggloo::profile::{Profiler, Stage};
+use svggloo::queue::{watch, QueueOptions};
+use svggloo::reader::{
+    data_file, fetch_data_file, find_data_file, is_url, read_records_with, ReadOptions,
+};
+use svggloo::schema::Schema;
+use svggloo::server::serve;
+#[cfg(feature = "sheets")]
+use svggloo::sheets::SheetsAuth;
+use svggloo::snapshot::{compare, Outcome};
+use svggloo::template::{
+    render_records_with_summary, render_to_strings, Record, RenderOptions, SingleOutput,
+};
+use svggloo::theme::load_themes;
+use svggloo::upload::UploadOptions;
+use svggloo::{note, set_quiet, setup_with_color};
 
 fn main() -> Result<(), Report> {
-    // Setup the application.
-    setup()?;
-
     // Setup the CLI.
     let opts: Opts = svggloo::cli::Opts::parse();
-    dbg!(&opts);
-
-    let _ = render(
-        &opts.template,
-        &opts.output_dir,
-        opts.exporter,
-        opts.field,
-        Some(&opts.separator),
+
+    // Setup the application.
+    setup_with_color(!opts.no_color)?;
+    set_quiet(opts.quiet);
+
+    match opts.command {
+        Some(Command::Render(args)) => render(args),
+        Some(Command::Init(args)) => init(args),
+        Some(Command::Fake(args)) => fake(args),
+        Some(Command::Snapshot(args)) => snapshot(args),
+        Some(Command::Diff(args)) => diff(args),
+        Some(Command::Verify(args)) => verify(args),
+        Some(Command::Lint(args)) => lint(args),
+        Some(Command::Tui(args)) => tui(args),
+        Some(Command::ServeApi(args)) => serve_api(args),
+        Some(Command::Queue(args)) => queue(args),
+        None => render(opts.render),
+    }
+}
+
+/// Render a template.
+fn render(args: RenderArgs) -> Result<(), Report> {
+    if args.list_exporters {
+        return list_exporters(args.template.as_deref(), args.exporter);
+    }
+    let template = args.template.expect("The template is a required argument.");
+
+    // A template read from the standard input has no sibling data file.
+    let no_data = args.data.is_none() && args.sheet.is_none() && args.set.is_empty();
+    if template == Path::new("-") && no_data {
+        return Err(eyre!(
+            "The --data option is required when the template is read from the standard input"
+        ));
+    }
+
+    // Prepare the render options, the ones of the command line taking
+    // precedence over the front matter of the template, and the front matter
+    // over the configuration of the project.
+    let project_dir = template.parent().unwrap_or_else(|| Path::new(""));
+    let front_matter =
+        FrontMatter::from_template(&template)?.or(FrontMatter::from_project(project_dir)?);
+    let read_options = ReadOptions {
+        delimiter: args.delimiter,
+        encoding: args.encoding,
+        normalize_headers: args.normalize_headers,
+        rename_headers: args.rename_headers,
+    };
+    let single_output = match (args.stdout, args.output_file) {
+        (true, _) => Some(SingleOutput::Stdout),
+        (false, Some(path)) => Some(SingleOutput::File(path)),
+        (false, None) => None,
+    };
+    let options = RenderOptions {
+        template_source: None,
+        // The standard output only receives SVG files.
+        exporter: args
+            .exporter
+            .or(front_matter.exporter.filter(|_| !args.stdout)),
+        exporter_column: args.exporter_column,
+        field_based_name: args.field.or_else(|| front_matter.field.clone()),
+        separator: args.separator.or_else(|| front_matter.separator.clone()),
+        ascii_names: args.ascii_names,
+        disambiguate_names: args.disambiguate_names,
+        on_empty_name: args.on_empty_name,
+        name_placeholder: args.name_placeholder,
+        map: MapOptions {
+            provider: args.map_provider,
+            api_key: args.map_api_key,
+            cache_dir: args.map_cache_dir,
+        },
+        icon_dir: args.icon_dir,
+        themes: load_themes(&args.theme)?,
+        locales: args.locales,
+        locale_column: args.locale_column,
+        toggle_layers: args.toggle_layers,
+        strip_metadata: args.strip_metadata,
+        autoescape: !args.no_autoescape,
+        trim_blocks: !args.no_trim_blocks,
+        lstrip_blocks: !args.no_lstrip_blocks,
+        validate: !args.no_validate,
+        pivot: args.pivot.map(|(names, values)| Pivot {
+            names,
+            values,
+            keys: args.pivot_key,
+        }),
+        // The delimiter of the variants is detected separately.
+        cross: args
+            .cross
+            .map(|path| {
+                let read_options = ReadOptions {
+                    delimiter: None,
+                    ..read_options.clone()
+                };
+                read_records_with(&path, &read_options)
+            })
+            .transpose()?,
+        computed: args.compute,
+        json_columns: args.parse_json_columns,
+        nest_columns: args.nest_columns,
+        record_mapper: None,
+        env_hook: None,
+        expose_all: args.expose_all,
+        siblings_by: args.siblings_by,
+        expose_neighbors: args.expose_neighbors,
+        stylesheet: args.inject_css.map(fs::read_to_string).transpose()?,
+        embed_images: args.embed_images,
+        page_size: args.page_size.or(front_matter.page_size),
+        page_fit: args.page_fit,
+        scale: args.scale,
+        title_column: args.title_column,
+        lang: args.lang,
+        lang_column: args.lang_column,
+        image_alts: args.image_alt,
+        watermark_text: args.watermark_text,
+        overlay: args.overlay.map(fs::read_to_string).transpose()?,
+        export_area: args.export_area,
+        single_output,
+        schema: args.schema.as_deref().map(Schema::from_path).transpose()?,
+        on_invalid: args.on_invalid,
+        only: args.only,
+        dedupe_by: args.dedupe_by,
+        dedupe_keep: args.dedupe_keep,
+        sort_by: args.sort_by,
+        expose_rank: args.rank,
+        running: args.running,
+        offset: args.offset,
+        limit: args.limit,
+        sample: args.sample,
+        seed: args.seed,
+        pick: None,
+        gallery: args.gallery,
+        thumbnails: args.thumbnails,
+        upload: args.upload.map(|destination| UploadOptions {
+            destination,
+            content_types: args.content_type,
+            concurrency: args.upload_concurrency,
+        }),
+        notify_url: args.notify_url,
+        email: args.email_column.map(|column| EmailOptions {
+            column,
+            smtp: args.smtp.unwrap_or_default(),
+            from: args.email_from.unwrap_or_default(),
+            subject: args.email_subject,
+            body: args.email_body,
+            rate: args.email_rate,
+            dry_run: args.email_dry_run,
+        }),
+        cache_dir: args.cache_dir,
+        resume: args.resume,
+        allow_empty: args.allow_empty,
+        post_cmd: args.post_cmd,
+        optimize_pdf: args.optimize_pdf,
+        pdf_standard: args.pdf_standard,
+        icc_profile: args.icc_profile,
+        pdf_password: args.pdf_password,
+        pdf_password_column: args.pdf_password_column,
+        sign: args.sign_cert.map(|cert| SignOptions {
+            cert,
+            password: args.sign_pass.unwrap_or_default(),
+        }),
+        print: args.print.then_some(PrintOptions {
+            printer: args.printer,
+            copies: args.copies,
+            duplex: args.duplex,
+        }),
+        profiler: (args.profile || args.profile_trace.is_some()).then(|| Arc::new(Profiler::new())),
+        large_template: args.large_template,
+        render_jobs: args.render_jobs,
+        max_outputs: Some(args.max_outputs),
+        export_jobs: args.export_jobs,
+        export_limits: ProcessLimits {
+            nice: args.export_nice,
+            idle_io: args.export_idle_io,
+            cpus: args.export_cpus,
+        },
+        space_check: !args.no_space_check,
+        checksums: args.checksums,
+        append_to: args.append_to,
+        navigation: Navigation {
+            page_numbers: args.page_numbers,
+            outline: args.outline,
+        },
+        booklet: args.booklet.then_some(Booklet {
+            sheet: args.booklet_sheet,
+        }),
+    };
+
+    // Read the records, either from the command line or from the data file.
+    let records = if let Some(sheet) = &args.sheet {
+        let auth = (&args.sheets_api_key, &args.sheets_credentials);
+        read_sheet(sheet, args.sheet_range.as_deref(), auth, &read_options)?
+    } else if args.set.is_empty() {
+        let data = match args.data {
+            Some(url) if is_url(&url) => fetch_data_file(&url, &args.data_cache_dir)?,
+            Some(path) => PathBuf::from(path),
+            None => find_data_file(&template)?,
+        };
+        match &options.profiler {
+            Some(profiler) => profiler.time(Stage::Read, &data.to_string_lossy(), || {
+                read_records_with(&data, &read_options)
+            })?,
+            None => read_records_with(&data, &read_options)?,
+        }
+    } else {
+        vec![args.set.into_iter().collect::<Record>()]
+    };
+
+    front_matter.check_columns(&records)?;
+
+    // Summarize the batches, whatever their outcome.
+    let (summary, result) =
+        render_records_with_summary(&template, &records, &args.output_dir, &options);
+    if options.single_output.is_none() {
+        note!("{}", summary.to_string().trim_end());
+    }
+    if let Some(profiler) = &options.profiler {
+        eprint!("{}", profiler.report());
+        if let Some(path) = &args.profile_trace {
+            profiler.write_trace(path)?;
+        }
+    }
+    result
+}
+
+/// List the exporters, and the one selected by the command line, the front
+/// matter of the template or the configuration of the project.
+fn list_exporters(template: Option<&Path>, exporter: Option<Exporter>) -> Result<(), Report> {
+    let front_matter = match template {
+        Some(template) => FrontMatter::from_template(template)?.or(FrontMatter::from_project(
+            template.parent().unwrap_or_else(|| Path::new("")),
+        )?),
+        None => FrontMatter::from_project(Path::new(""))?,
+    };
+    let name = |exporter: Exporter| {
+        exporter
+            .to_possible_value()
+            .map(|value| value.get_name().to_owned())
+            .unwrap_or_default()
+    };
+
+    println!("{:<10} {:<5} {:<40} VERSION", "EXPORTER", "FOUND", "PATH");
+    for info in svggloo::export::list_exporters() {
+        println!(
+            "{:<10} {:<5} {:<40} {}",
+            name(info.exporter),
+            if info.path.is_some() { "yes" } else { "no" },
+            info.path
+                .as_deref()
+                .map_or(String::from("-"), |path| path.display().to_string()),
+            info.version.as_deref().unwrap_or("-")
+        );
+    }
+    match exporter.or(front_matter.exporter) {
+        Some(exporter) => println!("Selected exporter: {}", name(exporter)),
+        None => println!("No exporter selected, the files are not exported"),
+    }
+
+    Ok(())
+}
+
+/// Read the records of a Google Sheets spreadsheet.
+#[cfg(feature = "sheets")]
+fn read_sheet(
+    sheet: &str,
+    range: Option<&str>,
+    (api_key, credentials): (&Option<String>, &Option<PathBuf>),
+    read_options: &ReadOptions,
+) -> Result<Vec<Record>, Report> {
+    let auth = match (credentials, api_key) {
+        (Some(key_file), _) => SheetsAuth::ServiceAccount(key_file.clone()),
+        (None, Some(key)) => SheetsAuth::ApiKey(key.clone()),
+        (None, None) => {
+            return Err(eyre!(
+                "Reading a spreadsheet requires --sheets-api-key or --sheets-credentials"
+            ))
+        }
+    };
+    svggloo::sheets::read_sheet(sheet, range, &auth, read_options)
+}
+
+/// Fail, the Google Sheets support is not compiled in.
+#[cfg(not(feature = "sheets"))]
+fn read_sheet(
+    _sheet: &str,
+    _range: Option<&str>,
+    _auth: (&Option<String>, &Option<PathBuf>),
+    _read_options: &ReadOptions,
+) -> Result<Vec<Record>, Report> {
+    Err(eyre!("svggloo was built without the `sheets` feature"))
+}
+
+/// Create a new project.
+fn init(args: InitArgs) -> Result<(), Report> {
+    svggloo::init::init(&args.directory)?;
+    let template = args.directory.join(TEMPLATE_FILE);
+    note!(
+        "Created {}, render it with: svggloo {} {}",
+        args.directory.display(),
+        template.display(),
+        args.directory.join("output").display()
     );
 
     Ok(())
 }
+
+/// Review the records of a template in a terminal interface.
+#[cfg(feature = "tui")]
+fn tui(args: TuiArgs) -> Result<(), Report> {
+    let project_dir = args.template.parent().unwrap_or_else(|| Path::new(""));
+    let front_matter =
+        FrontMatter::from_template(&args.template)?.or(FrontMatter::from_project(project_dir)?);
+    let records = read_records_with(&data_file(&args.template), &ReadOptions::default())?;
+    front_matter.check_columns(&records)?;
+    let options = RenderOptions {
+        exporter: args.exporter.or(front_matter.exporter),
+        field_based_name: args.field.or(front_matter.field),
+        separator: args.separator.or(front_matter.separator),
+        page_size: front_matter.page_size,
+        ..Default::default()
+    };
+
+    svggloo::tui::review(&args.template, &records, &args.output_dir, &options)
+}
+
+/// Fail, the terminal interface is not compiled in.
+#[cfg(not(feature = "tui"))]
+fn tui(_args: TuiArgs) -> Result<(), Report> {
+    Err(eyre!("svggloo was built without the `tui` feature"))
+}
+
+/// Generate sample data for a template.
+fn fake(args: FakeArgs) -> Result<(), Report> {
+    let source = fs::read_to_string(&args.template)?;
+    let columns = variables(&source);
+    if columns.is_empty() {
+        return Err(eyre!(
+            "The template {} does not use any variable",
+            args.template.display()
+        ));
+    }
+
+    match args.output {
+        Some(path) => write_fake_csv(fs::File::create(path)?, &columns, args.rows, args.seed),
+        None => write_fake_csv(io::stdout().lock(), &columns, args.rows, args.seed),
+    }
+}
+
+/// Compare the rendered files with golden files.
+fn snapshot(args: SnapshotArgs) -> Result<(), Report> {
+    let data = match args.data {
+        Some(data) => data,
+        None => find_data_file(&args.template)?,
+    };
+    let read_options = ReadOptions {
+        delimiter: args.delimiter,
+        encoding: args.encoding,
+        normalize_headers: args.normalize_headers,
+        rename_headers: args.rename_headers,
+    };
+    let records = read_records_with(&data, &read_options)?;
+    let options = RenderOptions {
+        field_based_name: args.field,
+        separator: Some(args.separator),
+        ..Default::default()
+    };
+    let outputs = render_to_strings(&args.template, &records, &options)?;
+    let outcomes = compare(&outputs, &args.golden_dir, args.update)?;
+
+    let mut differences = 0;
+    for (file_name, outcome) in &outcomes {
+        match outcome {
+            Outcome::Unchanged => continue,
+            Outcome::Changed(diff) => print!("{}", diff),
+            Outcome::New => eprintln!("New snapshot {}", file_name),
+            Outcome::Removed => eprintln!("Removed snapshot {}", file_name),
+        }
+        differences += 1;
+    }
+    if differences > 0 && !args.update {
+        return Err(eyre!(
+            "{} of {} snapshot(s) differ, run with --update to accept the changes",
+            differences,
+            outcomes.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Compare the rendered files of two output directories.
+fn diff(args: DiffArgs) -> Result<(), Report> {
+    let tolerance = args.rasterize.then_some(args.tolerance);
+    let changes = diff_dirs(&args.old_dir, &args.new_dir, tolerance)?;
+    for change in &changes {
+        eprintln!("{:?} {}", change.status, change.name);
+    }
+    fs::write(
+        &args.report,
+        html_report(&changes, &args.old_dir, &args.new_dir)?,
+    )?;
+    eprintln!(
+        "{} changed file(s), see {}",
+        changes.len(),
+        args.report.display()
+    );
+
+    Ok(())
+}
+
+/// Check the files of an output directory against their checksums.
+fn verify(args: VerifyArgs) -> Result<(), Report> {
+    let verification = Manifest::read(&args.output_dir)?.verify(&args.output_dir)?;
+    for path in &verification.missing {
+        eprintln!("Missing {}", path);
+    }
+    for path in &verification.modified {
+        eprintln!("Modified {}", path);
+    }
+    if verification.unchecked > 0 {
+        note!(
+            "{} file(s) have no checksum, render with --checksums to record them",
+            verification.unchecked
+        );
+    }
+    if !verification.is_intact() {
+        return Err(eyre!(
+            "{} file(s) missing, {} file(s) modified",
+            verification.missing.len(),
+            verification.modified.len()
+        ));
+    }
+    note!("Verified {} file(s)", verification.verified.len());
+
+    Ok(())
+}
+
+/// Report the common problems of a template.
+fn lint(args: LintArgs) -> Result<(), Report> {
+    let source = fs::read_to_string(&args.template)?;
+    let template_dir = args.template.parent().unwrap_or_else(|| Path::new(""));
+
+    // The variables are checked only when the columns are known.
+    let data = args.data.unwrap_or_else(|| data_file(&args.template));
+    let columns = if data.is_file() {
+        read_records_with(&data, &ReadOptions::default())?
+            .first()
+            .map(|record| record.keys().cloned().collect::<Vec<_>>())
+    } else {
+        None
+    };
+
+    let problems = lint_template(&source, columns.as_deref(), template_dir);
+    for problem in &problems {
+        println!("{}:{}", args.template.display(), problem);
+    }
+    if !problems.is_empty() {
+        return Err(eyre!("{} problem(s) found", problems.len()));
+    }
+
+    Ok(())
+}
+
+/// Serve the rendering of a template over HTTP.
+fn serve_api(args: ServeApiArgs) -> Result<(), Report> {
+    let options = RenderOptions {
+        exporter: args.exporter,
+        field_based_name: args.field,
+        ..Default::default()
+    };
+
+    serve(&args.bind, &args.template, &options)
+}
+
+/// Process the job files of a spool directory.
+fn queue(args: QueueArgs) -> Result<(), Report> {
+    let options = QueueOptions {
+        interval: Duration::from_secs(args.interval),
+        once: args.once,
+        read: ReadOptions {
+            delimiter: args.delimiter,
+            encoding: args.encoding,
+            normalize_headers: args.normalize_headers,
+            rename_headers: args.rename_headers,
+        },
+        base: RenderOptions {
+            cache_dir: args.cache_dir,
+            ..Default::default()
+        },
+    };
+
+    watch(&args.spool, &options)
+}
@@ -0,0 +1,162 @@
+use color_eyre::{
+    eyre::{eyre, Report, WrapErr},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Name of the manifest file, in the output directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Manifest of the files generated by a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Generated files.
+    pub files: Vec<ManifestFile>,
+}
+
+/// A file of the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// Path of the file, relatively to the output directory.
+    pub path: String,
+    /// URL of the uploaded file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// SHA-256 checksum of the file, in hexadecimal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Outcome of the verification of the files of a manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verification {
+    /// Files matching their checksums.
+    pub verified: Vec<String>,
+    /// Files which do not exist anymore.
+    pub missing: Vec<String>,
+    /// Files which do not match their checksums.
+    pub modified: Vec<String>,
+    /// Number of files recorded without checksums.
+    pub unchecked: usize,
+}
+
+impl Verification {
+    /// Check whether all the files match their checksums.
+    pub fn is_intact(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty()
+    }
+}
+
+impl Manifest {
+    /// Write the manifest in the output directory, and return its path.
+    pub fn write(&self, output_dir: &Path) -> Result<PathBuf, Report> {
+        let path = output_dir.join(MANIFEST_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(path)
+    }
+
+    /// Read the manifest of an output directory.
+    pub fn read(output_dir: &Path) -> Result<Self, Report> {
+        let path = output_dir.join(MANIFEST_FILE);
+        let manifest = fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Cannot read the manifest {}", path.display()))?;
+        serde_json::from_str(&manifest)
+            .map_err(|e| eyre!("Invalid manifest {}: {}", path.display(), e))
+    }
+
+    /// Hash the files of the manifest again, and compare them with their
+    /// checksums.
+    pub fn verify(&self, output_dir: &Path) -> Result<Verification, Report> {
+        let mut verification = Verification::default();
+        for file in &self.files {
+            let Some(expected) = &file.sha256 else {
+                verification.unchecked += 1;
+                continue;
+            };
+            let path = output_dir.join(&file.path);
+            if !path.is_file() {
+                verification.missing.push(file.path.clone());
+            } else if sha256(&path)? != *expected {
+                verification.modified.push(file.path.clone());
+            } else {
+                verification.verified.push(file.path.clone());
+            }
+        }
+
+        Ok(verification)
+    }
+}
+
+/// Compute the SHA-256 checksum of a file, in hexadecimal.
+pub fn sha256(path: &Path) -> Result<String, Report> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Compute the SHA-256 digest of a sequence of parts, in hexadecimal, e.g. to
+/// name a cached file after its inputs.
+///
+/// Each part is prefixed with its length, so that moving bytes from a part to
+/// the next one changes the digest.
+pub fn digest<T: AsRef<[u8]>>(parts: &[T]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        let part = part.as_ref();
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            digest(&["abc"]),
+            "c3494ca1a2cf8eeb8a11ded316fb55b83c3bbbedb6313cd50415251e5d09e12f"
+        );
+        assert_ne!(digest(&["ab", "c"]), digest(&["a", "bc"]));
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = std::env::temp_dir().join("svggloo-test-manifest");
+        fs::create_dir_all(&dir).unwrap();
+        let files = ["austin.svg", "provo.svg", "orem.svg"];
+        for file in files {
+            fs::write(dir.join(file), format!("<svg>{}</svg>", file)).unwrap();
+        }
+        let mut manifest = Manifest::default();
+        for file in files {
+            manifest.files.push(ManifestFile {
+                path: file.to_owned(),
+                url: None,
+                sha256: Some(sha256(&dir.join(file)).unwrap()),
+            });
+        }
+        manifest.write(&dir).unwrap();
+        assert_eq!(
+            sha256(&dir.join("austin.svg")).unwrap(),
+            "53100eef8df6cbbf9fe569ba458226109f499eac1b0dd02559d5b21ad7b1817c"
+        );
+
+        fs::write(dir.join("provo.svg"), "<svg/>").unwrap();
+        fs::remove_file(dir.join("orem.svg")).unwrap();
+        let verification = Manifest::read(&dir).unwrap().verify(&dir).unwrap();
+        assert_eq!(verification.verified, ["austin.svg"]);
+        assert_eq!(verification.modified, ["provo.svg"]);
+        assert_eq!(verification.missing, ["orem.svg"]);
+        assert!(!verification.is_intact());
+    }
+}
@@ -0,0 +1,156 @@
+#[cfg(feature = "native")]
+use crate::{filters::number, manifest::digest};
+#[cfg(feature = "native")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(feature = "native")]
+use minijinja::{Error, ErrorKind, Value};
+use std::path::PathBuf;
+#[cfg(feature = "native")]
+use std::{fs, io::Read};
+
+/// Default static map provider.
+///
+/// The `{lat}`, `{lon}`, `{zoom}`, `{width}`, `{height}` and `{key}`
+/// placeholders are substituted when building the request URL.
+pub const DEFAULT_MAP_PROVIDER: &str = "https://api.mapbox.com/styles/v1/mapbox/streets-v12/static/{lon},{lat},{zoom}/{width}x{height}?access_token={key}";
+
+/// Configuration of the `map_image` template function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapOptions {
+    /// URL template of the static map provider.
+    pub provider: String,
+    /// API key substituted to the `{key}` placeholder.
+    pub api_key: Option<String>,
+    /// Directory where the fetched snapshots are cached.
+    pub cache_dir: PathBuf,
+}
+
+impl Default for MapOptions {
+    fn default() -> Self {
+        Self {
+            provider: DEFAULT_MAP_PROVIDER.to_owned(),
+            api_key: None,
+            cache_dir: PathBuf::from(".svggloo-cache/maps"),
+        }
+    }
+}
+
+/// Fetch a static map snapshot and return it as a data URI.
+///
+/// The snapshot is fetched only once and served from the cache directory
+/// afterwards. The `size` can either be a single number for a square image, or
+/// a `WIDTHxHEIGHT` string.
+#[cfg(feature = "native")]
+pub fn map_image(
+    options: &MapOptions,
+    lat: Value,
+    lon: Value,
+    zoom: Value,
+    size: Value,
+) -> Result<Value, Error> {
+    let lat = number(&lat, "lat")?;
+    let lon = number(&lon, "lon")?;
+    let zoom = number(&zoom, "zoom")?;
+    let (width, height) = parse_size(&size)?;
+
+    // Build the request URL.
+    let url = options
+        .provider
+        .replace("{lat}", &lat.to_string())
+        .replace("{lon}", &lon.to_string())
+        .replace("{zoom}", &zoom.to_string())
+        .replace("{width}", &width.to_string())
+        .replace("{height}", &height.to_string());
+
+    // The API key is left out of the cache key on purpose, rotating it must not
+    // invalidate the cache.
+    let cached = options.cache_dir.join(digest(&[&url]));
+
+    let bytes = match fs::read(&cached) {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            let url = url.replace("{key}", options.api_key.as_deref().unwrap_or_default());
+            let bytes = fetch(&url)?;
+            fs::create_dir_all(&options.cache_dir)
+                .and_then(|_| fs::write(&cached, &bytes))
+                .map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidOperation,
+                        format!("cannot cache the map snapshot: {}", e),
+                    )
+                })?;
+            bytes
+        }
+    };
+
+    Ok(Value::from_safe_string(format!(
+        "data:{};base64,{}",
+        mime_type(&bytes),
+        STANDARD.encode(bytes)
+    )))
+}
+
+/// Fetch the content of a URL.
+#[cfg(feature = "native")]
+fn fetch(url: &str) -> Result<Vec<u8>, Error> {
+    let fetch_error = |e: String| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("cannot fetch the map snapshot: {}", e),
+        )
+    };
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| fetch_error(e.to_string()))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut bytes)
+        .map_err(|e| fetch_error(e.to_string()))?;
+
+    Ok(bytes)
+}
+
+/// Parse a `WIDTHxHEIGHT` or a single `SIZE` value.
+#[cfg(feature = "native")]
+fn parse_size(size: &Value) -> Result<(u32, u32), Error> {
+    let size = size.to_string();
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid map size {:?}", size),
+        )
+    };
+    let parse = |s: &str| s.trim().parse::<u32>().map_err(|_| invalid());
+    match size.split_once('x') {
+        Some((width, height)) => Ok((parse(width)?, parse(height)?)),
+        None => {
+            let side = parse(&size)?;
+            Ok((side, side))
+        }
+    }
+}
+
+/// Guess the MIME type of an image from its magic bytes.
+#[cfg(feature = "native")]
+pub(crate) fn mime_type(bytes: &[u8]) -> &'static str {
+    match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, ..] => "image/jpeg",
+        [b'G', b'I', b'F', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size(&Value::from("300x200")).unwrap(), (300, 200));
+        assert_eq!(parse_size(&Value::from(128)).unwrap(), (128, 128));
+        assert!(parse_size(&Value::from("big")).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use serde::Serialize;
+use std::fmt;
+
+/// Summary of a rendering run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunSummary {
+    /// Path of the template.
+    pub template: String,
+    /// Whether the run succeeded.
+    pub success: bool,
+    /// Error which stopped the run.
+    pub error: Option<String>,
+    /// Number of records read.
+    pub records: usize,
+    /// Number of rendered files.
+    pub rendered: usize,
+    /// Number of records left out by the selection, the deduplication or the
+    /// validation.
+    pub skipped: usize,
+    /// Number of exported files.
+    pub exported: usize,
+    /// Number of printed files.
+    pub printed: usize,
+    /// Number of uploaded files.
+    pub uploaded: usize,
+    /// Number of emails sent.
+    pub emailed: usize,
+    /// Failures which did not stop the run, e.g. failed uploads or emails.
+    pub failures: Vec<String>,
+    /// Path of the manifest.
+    pub manifest: Option<String>,
+    /// Duration of the run, in seconds.
+    pub duration: f64,
+    /// Name of the file which took the longest to render.
+    pub slowest: Option<String>,
+    /// Rendering duration of the slowest file, in seconds.
+    pub slowest_duration: f64,
+    /// Size of the output directory, in bytes.
+    pub output_size: u64,
+}
+
+impl fmt::Display for RunSummary {
+    /// Format the summary as a table, for the operators to check at a glance
+    /// whether a run behaved normally.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let slowest = match &self.slowest {
+            Some(name) => format!("{} ({:.2} s)", name, self.slowest_duration),
+            None => String::from("-"),
+        };
+        let rows = [
+            ("Records read", self.records.to_string()),
+            ("Rendered", self.rendered.to_string()),
+            ("Exported", self.exported.to_string()),
+            ("Skipped", self.skipped.to_string()),
+            ("Failed", self.failures.len().to_string()),
+            ("Duration", format!("{:.2} s", self.duration)),
+            ("Slowest record", slowest),
+            ("Output size", human_size(self.output_size)),
+        ];
+        for (label, value) in rows {
+            writeln!(f, "{:<16}{}", label, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Format a size in bytes with a binary unit.
+pub(crate) fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB", "GiB"] {
+        if size < 1024.0 || unit == "GiB" {
+            return match unit {
+                "B" => format!("{} B", bytes),
+                _ => format!("{:.1} {}", size, unit),
+            };
+        }
+        size /= 1024.0;
+    }
+    unreachable!()
+}
+
+/// Notify a webhook with the summary of a run.
+///
+/// The summary is sent as a JSON document in the body of a POST request.
+pub fn notify(url: &str, summary: &RunSummary) -> Result<(), Report> {
+    ureq::post(url)
+        .set("content-type", "application/json")
+        .send_string(&serde_json::to_string(summary)?)
+        .map_err(|e| eyre!("Cannot notify {}: {}", url, e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_json() {
+        let summary = RunSummary {
+            template: String::from("scorecard.svg"),
+            success: true,
+            records: 2,
+            rendered: 2,
+            ..Default::default()
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&summary).unwrap()).unwrap();
+        assert_eq!(json["rendered"], 2);
+        assert_eq!(json["manifest"], serde_json::Value::Null);
+        assert_eq!(json["failures"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_summary_table() {
+        let summary = RunSummary {
+            records: 3,
+            rendered: 2,
+            skipped: 1,
+            duration: 1.5,
+            slowest: Some(String::from("austin.svg")),
+            slowest_duration: 0.25,
+            output_size: 1536,
+            ..Default::default()
+        };
+        let table = summary.to_string();
+        assert!(table.contains("Records read    3\n"));
+        assert!(table.contains("Skipped         1\n"));
+        assert!(table.contains("Slowest record  austin.svg (0.25 s)\n"));
+        assert!(table.contains("Output size     1.5 KiB\n"));
+        assert_eq!(human_size(512), "512 B");
+    }
+}
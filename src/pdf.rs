@@ -0,0 +1,1019 @@
+use crate::{atomic::write_atomic, cache::discard, svg::PageSize};
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use regex::Regex;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Ghostscript presets used to optimize the exported files.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Optimization {
+    /// Low resolution images (72 dpi), for on-screen viewing.
+    Screen,
+    /// Medium resolution images (150 dpi), for ebooks and email.
+    Ebook,
+    /// High resolution images (300 dpi) with the colors preserved, for
+    /// professional printing.
+    Prepress,
+}
+
+impl Optimization {
+    /// Value of the Ghostscript `PDFSETTINGS` parameter.
+    fn settings(&self) -> &'static str {
+        match self {
+            Optimization::Screen => "/screen",
+            Optimization::Ebook => "/ebook",
+            Optimization::Prepress => "/prepress",
+        }
+    }
+}
+
+/// PDF standards the exported files can conform to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, ValueEnum)]
+pub enum PdfStandard {
+    /// PDF/A-2b, for long-term archiving.
+    #[value(name = "pdfa-2b")]
+    PdfA2b,
+    /// PDF/X-4, for print production.
+    #[value(name = "pdfx-4")]
+    PdfX4,
+}
+
+impl fmt::Display for PdfStandard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PdfStandard::PdfA2b => write!(f, "PDF/A-2b"),
+            PdfStandard::PdfX4 => write!(f, "PDF/X-4"),
+        }
+    }
+}
+
+impl PdfStandard {
+    /// Ghostscript arguments producing the standard.
+    fn args(&self) -> &'static [&'static str] {
+        match self {
+            PdfStandard::PdfA2b => &[
+                "-dPDFA=2",
+                "-dPDFACompatibilityPolicy=1",
+                "-sColorConversionStrategy=RGB",
+            ],
+            PdfStandard::PdfX4 => &["-dPDFX=4", "-sColorConversionStrategy=CMYK"],
+        }
+    }
+
+    /// PostScript definitions of the output intent required by the standard,
+    /// embedding an ICC color profile.
+    fn definitions(&self, profile: &Path) -> String {
+        let (components, subtype, condition, extra) = match self {
+            PdfStandard::PdfA2b => (3, "GTS_PDFA1", "sRGB", ""),
+            PdfStandard::PdfX4 => (
+                4,
+                "GTS_PDFX",
+                "Custom",
+                "[ /Trapped /False /DOCINFO pdfmark\n",
+            ),
+        };
+        format!(
+            "{extra}\
+             /ICCProfile ({profile}) def\n\
+             [/_objdef {{icc}} /type /stream /OBJ pdfmark\n\
+             [{{icc}} << /N {components} >> /PUT pdfmark\n\
+             [{{icc}} ICCProfile (r) file /PUT pdfmark\n\
+             [/_objdef {{intent}} /type /dict /OBJ pdfmark\n\
+             [{{intent}} << /Type /OutputIntent /S /{subtype} /DestOutputProfile {{icc}} \
+             /OutputConditionIdentifier ({condition}) >> /PUT pdfmark\n\
+             [{{Catalog}} << /OutputIntents [ {{intent}} ] >> /PUT pdfmark\n",
+            profile = escape(&profile.to_string_lossy()),
+        )
+    }
+}
+
+/// Escape a PostScript string.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)")
+}
+
+/// Build the Ghostscript command writing the PDF file `dst` from input files,
+/// with extra arguments.
+fn gs(args: &[OsString], dst: &Path, inputs: &[&Path]) -> Command {
+    let mut command = Command::new("gs");
+    command
+        .arg("-sDEVICE=pdfwrite")
+        .args(["-dNOPAUSE", "-dBATCH", "-dQUIET", "-dSAFER"])
+        .args(args)
+        .arg(concat_os("-sOutputFile=", dst))
+        .args(inputs);
+    command
+}
+
+/// Run a Ghostscript command, and report its errors.
+fn run(mut command: Command, pdf: &Path, dst: &Path) -> Result<(), Report> {
+    let output = command
+        .output()
+        .map_err(|e| eyre!("cannot run gs: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(dst);
+        return Err(eyre!(
+            "Cannot rewrite {}: {}",
+            pdf.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Concatenate a prefix and a path into a command argument.
+fn concat_os(prefix: &str, path: &Path) -> OsString {
+    let mut arg = OsString::from(prefix);
+    arg.push(path);
+    arg
+}
+
+/// Optimize PDF files in place with Ghostscript, downsampling their images and
+/// compressing their streams.
+///
+/// An optimized file larger than the original is dropped. Returns the total
+/// sizes of the files before and after the optimization.
+pub fn optimize(pdfs: &[PathBuf], optimization: Optimization) -> Result<(u64, u64), Report> {
+    let (mut before, mut after) = (0, 0);
+    for pdf in pdfs {
+        let optimized = pdf.with_extension("optimized.pdf");
+        let args = [
+            OsString::from("-dCompatibilityLevel=1.5"),
+            OsString::from(format!("-dPDFSETTINGS={}", optimization.settings())),
+        ];
+        run(gs(&args, &optimized, &[pdf]), pdf, &optimized)?;
+
+        // The file is replaced rather than overwritten, since it may be
+        // hard-linked from the cache.
+        let size = fs::metadata(pdf)?.len();
+        let optimized_size = fs::metadata(&optimized)?.len();
+        before += size;
+        if optimized_size < size {
+            fs::rename(&optimized, pdf)?;
+            after += optimized_size;
+        } else {
+            fs::remove_file(&optimized)?;
+            after += size;
+        }
+    }
+
+    Ok((before, after))
+}
+
+/// Document metadata of a PDF file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Title of the document, displayed by the viewers instead of its file name.
+    pub title: Option<String>,
+    /// Natural language of the document, as a language tag, e.g. `en-US`.
+    pub lang: Option<String>,
+}
+
+impl Metadata {
+    /// Whether there is no metadata to set.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.lang.is_none()
+    }
+
+    /// PostScript definitions setting the metadata.
+    fn pdfmarks(&self) -> String {
+        let mut pdfmarks = String::new();
+        if let Some(title) = &self.title {
+            pdfmarks.push_str(&format!("[ /Title {} /DOCINFO pdfmark\n", utf16(title)));
+            pdfmarks.push_str(
+                "[ {Catalog} << /ViewerPreferences << /DisplayDocTitle true >> >> /PUT pdfmark\n",
+            );
+        }
+        if let Some(lang) = &self.lang {
+            pdfmarks.push_str(&format!(
+                "[ {{Catalog}} << /Lang ({}) >> /PUT pdfmark\n",
+                escape(lang)
+            ));
+        }
+        pdfmarks
+    }
+}
+
+/// Encode a PDF text string in UTF-16, as a hexadecimal PostScript string.
+fn utf16(s: &str) -> String {
+    let hex: String = s
+        .encode_utf16()
+        .map(|unit| format!("{:04X}", unit))
+        .collect();
+    format!("<FEFF{}>", hex)
+}
+
+/// Rewrite PDF files in place with Ghostscript, to set their metadata and to
+/// make them conform to a PDF standard.
+///
+/// The standards require the ICC `profile` of their output intent, such as an
+/// sRGB profile for PDF/A-2b, or the CMYK profile of the printing condition
+/// for PDF/X-4. Without a standard, the files without metadata are left
+/// untouched.
+pub fn finish(
+    pdfs: &[(PathBuf, Metadata)],
+    standard: Option<PdfStandard>,
+    profile: Option<&Path>,
+) -> Result<(), Report> {
+    let profile = match (standard, profile) {
+        (None, _) => None,
+        (_, Some(profile)) => Some(profile.canonicalize()?),
+        (Some(standard), None) => {
+            return Err(eyre!(
+                "{} requires an ICC profile, specify it with --icc-profile",
+                standard
+            ))
+        }
+    };
+    for (pdf, metadata) in pdfs {
+        let mut definitions = String::from("%!\n");
+        let mut args = Vec::new();
+        match (standard, &profile) {
+            (Some(standard), Some(profile)) => {
+                definitions.push_str(&standard.definitions(profile));
+                args.extend(standard.args().iter().map(OsString::from));
+                args.push(concat_os("--permit-file-read=", profile));
+            }
+            _ if metadata.is_empty() => continue,
+            _ => (),
+        }
+        definitions.push_str(&metadata.pdfmarks());
+
+        let definitions_file = pdf.with_extension("def.ps");
+        fs::write(&definitions_file, definitions)?;
+        let rewritten = pdf.with_extension("rewritten.pdf");
+        let command = gs(&args, &rewritten, &[&definitions_file, pdf]);
+        let result = run(command, pdf, &rewritten);
+        fs::remove_file(&definitions_file)?;
+        result?;
+
+        // The file is replaced rather than overwritten, since it may be
+        // hard-linked from the cache.
+        fs::rename(&rewritten, pdf)?;
+    }
+
+    Ok(())
+}
+
+/// Arguments of the `qpdf` command encrypting a PDF file with AES-256.
+///
+/// The passwords are passed through the standard input rather than the command
+/// line, where they would be visible to the other users.
+fn qpdf_args(src: &Path, dst: &Path, user: &str, owner: &str) -> String {
+    [
+        "--encrypt",
+        user,
+        owner,
+        "256",
+        "--",
+        &src.to_string_lossy(),
+        &dst.to_string_lossy(),
+    ]
+    .join("\n")
+}
+
+/// Encrypt a PDF file in place with qpdf, so that it requires a password to be
+/// opened.
+///
+/// The owner password, which would allow to lift the restrictions, is random
+/// and discarded.
+pub fn encrypt(pdf: &Path, password: &str) -> Result<(), Report> {
+    let encrypted = pdf.with_extension("encrypted.pdf");
+    let owner: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    let mut child = Command::new("qpdf")
+        .arg("@-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| eyre!("cannot run qpdf: {}", e))?;
+    child
+        .stdin
+        .take()
+        .expect("the standard input is piped")
+        .write_all(qpdf_args(pdf, &encrypted, password, &owner).as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&encrypted);
+        return Err(eyre!(
+            "Cannot encrypt {}: {}",
+            pdf.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // The file is replaced rather than overwritten, since it may be hard-linked
+    // from the cache.
+    fs::rename(&encrypted, pdf)?;
+
+    Ok(())
+}
+
+/// Build the qpdf command writing the pages of a report, if it exists, then
+/// of the PDF files, to `dst`.
+fn qpdf_append(report: Option<&Path>, pdfs: &[PathBuf], dst: &Path) -> Command {
+    let mut command = Command::new("qpdf");
+    command
+        .args(["--empty", "--pages"])
+        .args(report)
+        .args(pdfs)
+        .arg("--")
+        .arg(dst);
+    command
+}
+
+/// Navigation aids of a report.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Navigation {
+    /// Stamp "Page X of Y" at the bottom of the pages.
+    pub page_numbers: bool,
+    /// Add a bookmark per appended file, titled after its record.
+    pub outline: bool,
+}
+
+impl Navigation {
+    /// Whether there is no navigation aid to add.
+    pub fn is_empty(&self) -> bool {
+        !self.page_numbers && !self.outline
+    }
+
+    /// PostScript definitions stamping the page numbers, given the number of
+    /// pages of the report.
+    ///
+    /// The pages are counted by the procedure itself, since the PDF interpreter
+    /// resets the count of the device for every page.
+    fn definitions(&self, total: usize) -> String {
+        if !self.page_numbers {
+            return String::from("%!\n");
+        }
+        format!(
+            "%!\n\
+             userdict /svggloo_page 0 put\n\
+             /svggloo_label {{ (Page ) svggloo_page 12 string cvs ( of {}) }} bind def\n\
+             << /EndPage {{\n\
+             exch pop dup 2 lt {{\n\
+             pop userdict /svggloo_page svggloo_page 1 add put\n\
+             gsave initgraphics /Helvetica findfont 9 scalefont setfont 0 setgray\n\
+             currentpagedevice /PageSize get 0 get\n\
+             svggloo_label 3 {{ stringwidth pop 3 1 roll }} repeat add add\n\
+             sub 2 div 18 moveto\n\
+             svggloo_label 3 -1 roll show exch show show\n\
+             grestore true\n\
+             }} {{ pop false }} ifelse\n\
+             }} >> setpagedevice\n",
+            total
+        )
+    }
+
+    /// Bookmarks of the appended files, pointing to their first pages.
+    fn pdfmarks(&self, entries: &[LedgerEntry]) -> String {
+        let mut pdfmarks = String::new();
+        if !self.outline {
+            return pdfmarks;
+        }
+        let mut page = 1;
+        for entry in entries {
+            pdfmarks.push_str(&format!(
+                "[ /Title {} /Page {} /View [ /XYZ null null null ] /OUT pdfmark\n",
+                utf16(&entry.title),
+                page
+            ));
+            page += entry.pages;
+        }
+        pdfmarks
+    }
+}
+
+/// Count the pages of a PDF file with qpdf.
+pub fn page_count(pdf: &Path) -> Result<usize, Report> {
+    let output = Command::new("qpdf")
+        .arg("--show-npages")
+        .arg(pdf)
+        .output()
+        .map_err(|e| eyre!("cannot run qpdf: {}", e))?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|_| {
+            eyre!(
+                "Cannot count the pages of {}: {}",
+                pdf.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        })
+}
+
+/// Path of the pages of a report without navigation aids, e.g.
+/// `report.pages.pdf`, kept to add the aids again once more pages are appended.
+pub fn pages_path(report: &Path) -> PathBuf {
+    report.with_extension("pages.pdf")
+}
+
+/// Add navigation aids to the pages of a report with Ghostscript.
+fn navigate(
+    pages: &Path,
+    dst: &Path,
+    entries: &[LedgerEntry],
+    navigation: Navigation,
+) -> Result<(), Report> {
+    let definitions_file = dst.with_extension("def.ps");
+    let pdfmarks_file = dst.with_extension("marks.ps");
+    let result = page_count(pages).and_then(|total| {
+        fs::write(&definitions_file, navigation.definitions(total))?;
+        fs::write(&pdfmarks_file, navigation.pdfmarks(entries))?;
+        // The bookmarks follow the pages they point to.
+        let command = gs(&[], dst, &[&definitions_file, pages, &pdfmarks_file]);
+        run(command, pages, dst)
+    });
+    let _ = fs::remove_file(&definitions_file);
+    let _ = fs::remove_file(&pdfmarks_file);
+    result
+}
+
+/// Append the pages of PDF files to a report with qpdf, creating the report if
+/// it does not exist yet.
+///
+/// With navigation aids, the pages are appended to the pages of the report
+/// without them, kept next to it, and the aids of the whole report are added
+/// again. The original report is kept as a backup, e.g. `report.bak.pdf`, whose
+/// path is returned.
+pub fn append(
+    report: &Path,
+    pdfs: &[PathBuf],
+    entries: &[LedgerEntry],
+    navigation: Navigation,
+) -> Result<Option<PathBuf>, Report> {
+    let pages = pages_path(report);
+    let appended = report.with_extension("appended.pdf");
+    let original = [pages.as_path(), report].into_iter().find(|p| p.is_file());
+    let output = qpdf_append(original, pdfs, &appended)
+        .output()
+        .map_err(|e| eyre!("cannot run qpdf: {}", e))?;
+    // qpdf exits with 3 when it succeeded with warnings.
+    if !matches!(output.status.code(), Some(0) | Some(3)) {
+        let _ = fs::remove_file(&appended);
+        return Err(eyre!(
+            "Cannot append to {}: {}",
+            report.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let appended = if navigation.is_empty() {
+        if pages.is_file() {
+            fs::remove_file(&pages)?;
+        }
+        appended
+    } else {
+        let mut all = read_entries(report)?;
+        all.extend_from_slice(entries);
+        let navigated = report.with_extension("navigated.pdf");
+        if let Err(e) = navigate(&appended, &navigated, &all, navigation) {
+            let _ = fs::remove_file(&appended);
+            return Err(e);
+        }
+        fs::rename(&appended, &pages)?;
+        navigated
+    };
+    let backup = match report.is_file() {
+        true => {
+            let backup = report.with_extension("bak.pdf");
+            fs::rename(report, &backup)?;
+            Some(backup)
+        }
+        false => None,
+    };
+    fs::rename(&appended, report)?;
+
+    Ok(backup)
+}
+
+/// File appended to a report, as recorded in its ledger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerEntry {
+    /// Name of the rendered file, relative to the output directory.
+    pub name: String,
+    /// Number of pages of the exported file.
+    pub pages: usize,
+    /// Title of the bookmark of the file.
+    pub title: String,
+}
+
+impl LedgerEntry {
+    /// Read an entry from a line of a ledger, i.e. the name, the number of pages
+    /// and the title, separated by tabs.
+    ///
+    /// The first ledgers listed the names only, of single page files.
+    fn parse(line: &str) -> Self {
+        let mut fields = line.splitn(3, '\t');
+        let name = fields.next().unwrap_or_default().to_owned();
+        let pages = fields.next().and_then(|p| p.parse().ok()).unwrap_or(1);
+        let title = fields.next().map_or_else(|| name.clone(), str::to_owned);
+        Self { name, pages, title }
+    }
+}
+
+impl fmt::Display for LedgerEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let title: String = self
+            .title
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        write!(f, "{}\t{}\t{}", self.name, self.pages, title)
+    }
+}
+
+/// Path of the ledger of a report, listing the files appended to it, e.g. `report.appended.txt`.
+pub fn ledger_path(report: &Path) -> PathBuf {
+    report.with_extension("appended.txt")
+}
+
+/// Read the names of the files appended to a report.
+///
+/// A report without a ledger was not built by appending, and cannot be
+/// appended to without duplicating its pages.
+pub fn read_ledger(report: &Path) -> Result<HashSet<String>, Report> {
+    Ok(read_entries(report)?
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect())
+}
+
+/// Read the files appended to a report, in order.
+fn read_entries(report: &Path) -> Result<Vec<LedgerEntry>, Report> {
+    let ledger = ledger_path(report);
+    match fs::read_to_string(&ledger) {
+        Ok(lines) => Ok(lines.lines().map(LedgerEntry::parse).collect()),
+        Err(_) if !report.exists() => Ok(Vec::new()),
+        Err(_) => Err(eyre!(
+            "{} has no ledger {} of the files appended to it",
+            report.display(),
+            ledger.display()
+        )),
+    }
+}
+
+/// Record files appended to a report in its ledger.
+pub fn record_ledger(report: &Path, entries: &[LedgerEntry]) -> Result<(), Report> {
+    let ledger = ledger_path(report);
+    let mut content = fs::read_to_string(&ledger).unwrap_or_default();
+    for entry in entries {
+        content.push_str(&entry.to_string());
+        content.push('\n');
+    }
+    write_atomic(&ledger, |out| Ok(out.write_all(content.as_bytes())?))
+}
+
+/// Imposition of a report for saddle-stitch printing.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Booklet {
+    /// Size of the sheets the two pages of a spread are placed on side by
+    /// side, scaled to fit, or `None` to keep a page per sheet.
+    pub sheet: Option<PageSize>,
+}
+
+/// Path of the booklet of a report, e.g. `report.booklet.pdf`.
+pub fn booklet_path(report: &Path) -> PathBuf {
+    report.with_extension("booklet.pdf")
+}
+
+/// Order of the pages of a booklet, in spreads: the last page and the first
+/// one, then the second one and the second to last one, etc.
+///
+/// The pages are padded to a multiple of four with blank pages, i.e. `None`.
+fn booklet_order(pages: usize) -> Vec<Option<usize>> {
+    let count = pages.div_ceil(4) * 4;
+    let page = |n: usize| (n <= pages).then_some(n);
+    (0..count / 4)
+        .flat_map(|sheet| {
+            let (low, high) = (2 * sheet + 1, count - 2 * sheet);
+            [page(high), page(low), page(low + 1), page(high - 1)]
+        })
+        .collect()
+}
+
+/// Run a qpdf command reading a PDF file, and return its output.
+fn qpdf_show(args: &[&str], pdf: &Path) -> Result<String, Report> {
+    let output = Command::new("qpdf")
+        .args(args)
+        .arg(pdf)
+        .output()
+        .map_err(|e| eyre!("cannot run qpdf: {}", e))?;
+    if !matches!(output.status.code(), Some(0) | Some(3)) {
+        return Err(eyre!(
+            "Cannot read {}: {}",
+            pdf.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Size of the first page of a PDF file, in points, read from its media box.
+fn first_page_size(pdf: &Path) -> Result<(f64, f64), Report> {
+    let invalid = || eyre!("Cannot read the page size of {}", pdf.display());
+    let pages = qpdf_show(&["--show-pages"], pdf)?;
+    let page = Regex::new(r"page 1: (\d+) (\d+) R").unwrap();
+    let object = page.captures(&pages).ok_or_else(invalid)?;
+    let dictionary = qpdf_show(
+        &[&format!("--show-object={},{}", &object[1], &object[2])],
+        pdf,
+    )?;
+    parse_media_box(&dictionary).ok_or_else(invalid)
+}
+
+/// Read the size of a page from its dictionary, as printed by qpdf.
+fn parse_media_box(dictionary: &str) -> Option<(f64, f64)> {
+    let number = r"\s*(-?[\d.]+)";
+    let media_box = Regex::new(&format!(r"/MediaBox\s*\[{0}{0}{0}{0}\s*\]", number)).unwrap();
+    let captures = media_box.captures(dictionary)?;
+    let value = |i: usize| captures[i].parse::<f64>().ok();
+    Some((value(3)? - value(1)?, value(4)? - value(2)?))
+}
+
+/// Minimal PDF file of a blank page.
+fn blank_pdf(width: f64, height: f64) -> Vec<u8> {
+    let objects = [
+        String::from("<< /Type /Catalog /Pages 2 0 R >>"),
+        String::from("<< /Type /Pages /Kids [ 3 0 R ] /Count 1 >>"),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [ 0 0 {} {} ] >>",
+            width, height
+        ),
+    ];
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+    let xref = pdf.len();
+    pdf.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    ));
+    pdf.into_bytes()
+}
+
+/// Build the qpdf command writing the pages of a report to `dst` in the order
+/// of a booklet, the missing pages being taken from a blank PDF file.
+fn qpdf_impose(report: &Path, blank: &Path, order: &[Option<usize>], dst: &Path) -> Command {
+    let mut command = Command::new("qpdf");
+    command.args(["--empty", "--pages"]);
+    for page in order {
+        match page {
+            Some(page) => command.arg(report).arg(page.to_string()),
+            None => command.arg(blank).arg("1"),
+        };
+    }
+    command.arg("--").arg(dst);
+    command
+}
+
+/// Impose the pages of a report for saddle-stitch printing, and return the path
+/// of the booklet.
+///
+/// The pages are reordered with qpdf, then placed two per sheet with
+/// Ghostscript when a sheet size is given.
+pub fn impose(report: &Path, booklet: Booklet) -> Result<PathBuf, Report> {
+    let dst = booklet_path(report);
+    let blank = dst.with_extension("blank.pdf");
+    let (width, height) = first_page_size(report)?;
+    fs::write(&blank, blank_pdf(width, height))?;
+    let ordered = match booklet.sheet {
+        Some(_) => dst.with_extension("ordered.pdf"),
+        None => dst.clone(),
+    };
+    let output = qpdf_impose(
+        report,
+        &blank,
+        &booklet_order(page_count(report)?),
+        &ordered,
+    )
+    .output()
+    .map_err(|e| eyre!("cannot run qpdf: {}", e));
+    let _ = fs::remove_file(&blank);
+    let output = output?;
+    if !matches!(output.status.code(), Some(0) | Some(3)) {
+        let _ = fs::remove_file(&ordered);
+        return Err(eyre!(
+            "Cannot impose {}: {}",
+            report.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    if let Some(sheet) = booklet.sheet {
+        // The spreads are laid out on the sheets in landscape.
+        let points = |mm: f64| (mm / 25.4 * 72.0).round();
+        let (long, short) = match sheet.width > sheet.height {
+            true => (sheet.width, sheet.height),
+            false => (sheet.height, sheet.width),
+        };
+        let args = [
+            OsString::from("-sNupControl=2x1"),
+            OsString::from("-dFIXEDMEDIA"),
+            OsString::from(format!("-dDEVICEWIDTHPOINTS={}", points(long))),
+            OsString::from(format!("-dDEVICEHEIGHTPOINTS={}", points(short))),
+        ];
+        let result = run(gs(&args, &dst, &[&ordered]), report, &dst);
+        fs::remove_file(&ordered)?;
+        result?;
+    }
+
+    Ok(dst)
+}
+
+/// Options of the digital signature of the exported files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignOptions {
+    /// PKCS#12 file holding the certificate and the private key.
+    pub cert: PathBuf,
+    /// Passphrase of the PKCS#12 file.
+    pub password: String,
+}
+
+/// Build the pyHanko command signing a PDF file.
+fn pyhanko(src: &Path, dst: &Path, cert: &Path, passfile: &Path) -> Command {
+    let mut command = Command::new("pyhanko");
+    command
+        .args([
+            "sign",
+            "addsig",
+            "--field",
+            "Signature",
+            "pkcs12",
+            "--passfile",
+        ])
+        .arg(passfile)
+        .args([src, dst, cert]);
+    command
+}
+
+/// Sign a PDF file in place with pyHanko.
+///
+/// The passphrase is written to a temporary file readable by the user only,
+/// rather than passed on the command line.
+pub fn sign(pdf: &Path, options: &SignOptions) -> Result<(), Report> {
+    let signed = pdf.with_extension("signed.pdf");
+    let passfile = pdf.with_extension("pass");
+    discard(&passfile)?;
+    let mut open_options = OpenOptions::new();
+    open_options.write(true).create_new(true);
+    #[cfg(unix)]
+    open_options.mode(0o600);
+    open_options
+        .open(&passfile)?
+        .write_all(options.password.as_bytes())?;
+    let output = pyhanko(pdf, &signed, &options.cert, &passfile).output();
+    fs::remove_file(&passfile)?;
+    let output = output.map_err(|e| eyre!("cannot run pyhanko: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&signed);
+        return Err(eyre!(
+            "Cannot sign {}: {}",
+            pdf.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // The file is replaced rather than overwritten, since it may be hard-linked
+    // from the cache.
+    fs::rename(&signed, pdf)?;
+
+    Ok(())
+}
+
+/// Verify the conformance of PDF files with veraPDF, when it is installed.
+///
+/// Only PDF/A can be verified. Returns the files failing the verification, or
+/// `None` when it could not run.
+pub fn verify(pdfs: &[PathBuf], standard: PdfStandard) -> Result<Option<Vec<PathBuf>>, Report> {
+    if standard != PdfStandard::PdfA2b || pdfs.is_empty() {
+        return Ok(None);
+    }
+    let output = match Command::new("verapdf")
+        .args(["--flavour", "2b", "--format", "text"])
+        .args(pdfs)
+        .output()
+    {
+        Ok(output) => output,
+        Err(_) => return Ok(None),
+    };
+    let report = String::from_utf8_lossy(&output.stdout);
+    let failures: Vec<&str> = report
+        .lines()
+        .filter_map(|line| line.strip_prefix("FAIL "))
+        .collect();
+    let failed = pdfs
+        .iter()
+        .filter(|pdf| {
+            let pdf = pdf.to_string_lossy();
+            failures
+                .iter()
+                .any(|failure| failure.contains(pdf.as_ref()))
+        })
+        .cloned()
+        .collect();
+
+    Ok(Some(failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gs() {
+        let args = [OsString::from("-dPDFSETTINGS=/ebook")];
+        let command = gs(
+            &args,
+            Path::new("austin.optimized.pdf"),
+            &[Path::new("austin.pdf")],
+        );
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args,
+            [
+                "-sDEVICE=pdfwrite",
+                "-dNOPAUSE",
+                "-dBATCH",
+                "-dQUIET",
+                "-dSAFER",
+                "-dPDFSETTINGS=/ebook",
+                "-sOutputFile=austin.optimized.pdf",
+                "austin.pdf"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_qpdf_args() {
+        assert_eq!(
+            qpdf_args(
+                Path::new("austin.pdf"),
+                Path::new("austin.encrypted.pdf"),
+                "s3cret",
+                "0wner"
+            ),
+            "--encrypt\ns3cret\n0wner\n256\n--\naustin.pdf\naustin.encrypted.pdf"
+        );
+    }
+
+    #[test]
+    fn test_append() {
+        let command = qpdf_append(
+            Some(Path::new("report.pdf")),
+            &[PathBuf::from("austin.pdf"), PathBuf::from("provo.pdf")],
+            Path::new("report.appended.pdf"),
+        );
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args.join(" "),
+            "--empty --pages report.pdf austin.pdf provo.pdf -- report.appended.pdf"
+        );
+
+        let dir = std::env::temp_dir().join("svggloo-test-ledger");
+        fs::create_dir_all(&dir).unwrap();
+        let report = dir.join("report.pdf");
+        let _ = fs::remove_file(&report);
+        let _ = fs::remove_file(ledger_path(&report));
+        assert!(read_ledger(&report).unwrap().is_empty());
+        fs::write(ledger_path(&report), "austin.svg\n").unwrap();
+        let provo = LedgerEntry {
+            name: String::from("provo.svg"),
+            pages: 2,
+            title: String::from("Provo,\nUT"),
+        };
+        record_ledger(&report, &[provo]).unwrap();
+        assert_eq!(read_ledger(&report).unwrap().len(), 2);
+        let entries = read_entries(&report).unwrap();
+        assert_eq!(entries[0].title, "austin.svg");
+        assert_eq!(
+            (entries[1].pages, entries[1].title.as_str()),
+            (2, "Provo, UT")
+        );
+        let navigation = Navigation {
+            page_numbers: true,
+            outline: true,
+        };
+        assert!(navigation.definitions(3).contains("( of 3)"));
+        let pdfmarks = navigation.pdfmarks(&entries);
+        assert!(pdfmarks.ends_with("/Page 2 /View [ /XYZ null null null ] /OUT pdfmark\n"));
+        assert_eq!(Navigation::default().pdfmarks(&entries), "");
+        fs::write(&report, "%PDF").unwrap();
+        fs::remove_file(ledger_path(&report)).unwrap();
+        assert!(read_ledger(&report).is_err());
+    }
+
+    #[test]
+    fn test_booklet() {
+        let order: Vec<_> = booklet_order(6)
+            .into_iter()
+            .map(|p| p.unwrap_or(0))
+            .collect();
+        assert_eq!(order, [0, 1, 2, 0, 6, 3, 4, 5]);
+        assert_eq!(booklet_order(4), [Some(4), Some(1), Some(2), Some(3)]);
+        assert!(booklet_order(0).is_empty());
+
+        let dictionary = "<< /Contents 4 0 R /MediaBox [ 0 0 612 792 ] /Parent 2 0 R >>";
+        assert_eq!(parse_media_box(dictionary), Some((612.0, 792.0)));
+        assert_eq!(parse_media_box("<< /Type /Page >>"), None);
+
+        let blank = String::from_utf8(blank_pdf(612.0, 792.0)).unwrap();
+        let xref = blank.find("xref").unwrap();
+        assert!(blank.ends_with(&format!("startxref\n{}\n%%EOF\n", xref)));
+        let command = qpdf_impose(
+            Path::new("report.pdf"),
+            Path::new("blank.pdf"),
+            &[Some(2), None],
+            Path::new("booklet.pdf"),
+        );
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args.join(" "),
+            "--empty --pages report.pdf 2 blank.pdf 1 -- booklet.pdf"
+        );
+    }
+
+    #[test]
+    fn test_pyhanko() {
+        let command = pyhanko(
+            Path::new("austin.pdf"),
+            Path::new("austin.signed.pdf"),
+            Path::new("cert.p12"),
+            Path::new("austin.pass"),
+        );
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args,
+            [
+                "sign",
+                "addsig",
+                "--field",
+                "Signature",
+                "pkcs12",
+                "--passfile",
+                "austin.pass",
+                "austin.pdf",
+                "austin.signed.pdf",
+                "cert.p12"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pdfmarks() {
+        let metadata = Metadata {
+            title: Some(String::from("Austin, TX")),
+            lang: Some(String::from("en-US")),
+        };
+        assert_eq!(
+            metadata.pdfmarks(),
+            "[ /Title <FEFF00410075007300740069006E002C002000540058> /DOCINFO pdfmark\n\
+             [ {Catalog} << /ViewerPreferences << /DisplayDocTitle true >> >> /PUT pdfmark\n\
+             [ {Catalog} << /Lang (en-US) >> /PUT pdfmark\n"
+        );
+        assert!(Metadata::default().is_empty());
+    }
+
+    #[test]
+    fn test_definitions() {
+        let definitions = PdfStandard::PdfX4.definitions(Path::new("/icc/Coated (v2).icc"));
+        assert!(definitions.contains("/ICCProfile (/icc/Coated \\(v2\\).icc) def"));
+        assert!(definitions.contains("/N 4"));
+        assert!(definitions.contains("/S /GTS_PDFX"));
+        assert!(definitions.contains("/Trapped /False"));
+    }
+}
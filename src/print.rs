@@ -0,0 +1,114 @@
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use std::{path::Path, process::Command};
+
+/// How the pages are printed on the sheets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Duplex {
+    /// Print on one side of the sheets.
+    OneSided,
+    /// Print on both sides, flipping along the long edge.
+    LongEdge,
+    /// Print on both sides, flipping along the short edge.
+    ShortEdge,
+}
+
+impl Duplex {
+    /// Value of the CUPS `sides` option.
+    fn sides(&self) -> &'static str {
+        match self {
+            Duplex::OneSided => "one-sided",
+            Duplex::LongEdge => "two-sided-long-edge",
+            Duplex::ShortEdge => "two-sided-short-edge",
+        }
+    }
+}
+
+/// Options controlling the printing of the exported files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// Printer queue, the default destination when not specified.
+    pub printer: Option<String>,
+    /// Number of copies of each file.
+    pub copies: u32,
+    /// How the pages are printed on the sheets, the printer default when not
+    /// specified.
+    pub duplex: Option<Duplex>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            printer: None,
+            copies: 1,
+            duplex: None,
+        }
+    }
+}
+
+/// Build the `lp` command printing a file.
+fn lp(file: &Path, options: &PrintOptions) -> Command {
+    let mut command = Command::new("lp");
+    if let Some(printer) = &options.printer {
+        command.arg("-d").arg(printer);
+    }
+    command.arg("-n").arg(options.copies.to_string());
+    if let Some(duplex) = options.duplex {
+        command.arg("-o").arg(format!("sides={}", duplex.sides()));
+    }
+    command.arg("--").arg(file);
+    command
+}
+
+/// Send files to a printer queue with the CUPS `lp` command, one job per file.
+///
+/// The printing stops at the first file the queue refuses.
+pub fn print(files: &[impl AsRef<Path>], options: &PrintOptions) -> Result<(), Report> {
+    for file in files {
+        let file = file.as_ref();
+        let output = lp(file, options)
+            .output()
+            .map_err(|e| eyre!("cannot run lp: {}", e))?;
+        if !output.status.success() {
+            return Err(eyre!(
+                "Cannot print {}: {}",
+                file.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lp() {
+        let options = PrintOptions {
+            printer: Some(String::from("office")),
+            copies: 2,
+            duplex: Some(Duplex::LongEdge),
+        };
+        let command = lp(Path::new("austin.pdf"), &options);
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args,
+            [
+                "-d",
+                "office",
+                "-n",
+                "2",
+                "-o",
+                "sides=two-sided-long-edge",
+                "--",
+                "austin.pdf"
+            ]
+        );
+    }
+}
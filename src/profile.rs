@@ -0,0 +1,198 @@
+use color_eyre::{eyre::Report, Result};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Stage of a run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// Reading and parsing the data file.
+    Read,
+    /// Rendering the template for a record.
+    Render,
+    /// Writing a rendered file.
+    Write,
+    /// Running an exporter.
+    Export,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Stage::Read => "read",
+            Stage::Render => "render",
+            Stage::Write => "write",
+            Stage::Export => "export",
+        })
+    }
+}
+
+/// A timed operation.
+#[derive(Debug, Clone)]
+struct Span {
+    stage: Stage,
+    /// What the operation worked on, e.g. the data file or the rendered file.
+    name: String,
+    /// Start of the operation, since the start of the profiler.
+    start: Duration,
+    duration: Duration,
+}
+
+/// Time spent in the stages of a run.
+#[derive(Debug)]
+pub struct Profiler {
+    origin: Instant,
+    spans: Mutex<Vec<Span>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    /// Start profiling.
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            spans: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record an operation which started at `start` and ends now.
+    pub fn record(&self, stage: Stage, name: &str, start: Instant) {
+        let span = Span {
+            stage,
+            name: name.to_owned(),
+            start: start.saturating_duration_since(self.origin),
+            duration: start.elapsed(),
+        };
+        self.spans.lock().unwrap().push(span);
+    }
+
+    /// Time an operation.
+    pub fn time<T>(&self, stage: Stage, name: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(stage, name, start);
+        result
+    }
+
+    /// Format the time spent per stage, then per file.
+    pub fn report(&self) -> String {
+        let spans = self.spans.lock().unwrap();
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        let mut stages: BTreeMap<Stage, Vec<Duration>> = BTreeMap::new();
+        for span in spans.iter() {
+            stages.entry(span.stage).or_default().push(span.duration);
+        }
+        let mut report = format!(
+            "{:<8} {:>6} {:>12} {:>12} {:>12}\n",
+            "STAGE", "COUNT", "TOTAL (ms)", "MEAN (ms)", "MAX (ms)"
+        );
+        for (stage, durations) in &stages {
+            let total: Duration = durations.iter().sum();
+            report += &format!(
+                "{:<8} {:>6} {:>12.2} {:>12.2} {:>12.2}\n",
+                stage,
+                durations.len(),
+                ms(total),
+                ms(total) / durations.len() as f64,
+                ms(durations.iter().max().copied().unwrap_or_default())
+            );
+        }
+
+        // The files keep the order of the run.
+        let mut files: Vec<(&str, BTreeMap<Stage, Duration>)> = Vec::new();
+        for span in spans.iter().filter(|span| span.stage != Stage::Read) {
+            let index = match files.iter().position(|(name, _)| *name == span.name) {
+                Some(index) => index,
+                None => {
+                    files.push((&span.name, BTreeMap::new()));
+                    files.len() - 1
+                }
+            };
+            *files[index].1.entry(span.stage).or_default() += span.duration;
+        }
+        if !files.is_empty() {
+            report += &format!(
+                "\n{:<40} {:>12} {:>12} {:>12}\n",
+                "FILE", "RENDER (ms)", "WRITE (ms)", "EXPORT (ms)"
+            );
+        }
+        for (name, stages) in &files {
+            let stage = |stage| {
+                stages
+                    .get(&stage)
+                    .map_or(String::from("-"), |d| format!("{:.2}", ms(*d)))
+            };
+            report += &format!(
+                "{:<40} {:>12} {:>12} {:>12}\n",
+                name,
+                stage(Stage::Render),
+                stage(Stage::Write),
+                stage(Stage::Export)
+            );
+        }
+
+        report
+    }
+
+    /// Write the operations in the Chrome tracing format, which can be opened
+    /// with `chrome://tracing` or Perfetto.
+    pub fn write_trace(&self, path: &Path) -> Result<(), Report> {
+        let spans = self.spans.lock().unwrap();
+        let events: Vec<_> = spans
+            .iter()
+            .map(|span| {
+                json!({
+                    "name": span.name,
+                    "cat": span.stage.to_string(),
+                    "ph": "X",
+                    "ts": span.start.as_micros() as u64,
+                    "dur": span.duration.as_micros() as u64,
+                    "pid": 1,
+                    "tid": 1,
+                })
+            })
+            .collect();
+        fs::write(path, json!({ "traceEvents": events }).to_string())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiler() {
+        let profiler = Profiler::new();
+        profiler.time(Stage::Read, "cities.csv", || ());
+        profiler.time(Stage::Render, "austin.svg", || ());
+        profiler.time(Stage::Write, "austin.svg", || ());
+        profiler.time(Stage::Render, "provo.svg", || ());
+        let report = profiler.report();
+        assert!(report.contains("\nrender        2 "));
+        assert!(report.contains("\nread          1 "));
+        let files: Vec<&str> = report.lines().skip_while(|l| !l.is_empty()).collect();
+        assert!(files[2].starts_with("austin.svg"));
+        assert!(files[3].starts_with("provo.svg"));
+        assert!(files[3].ends_with(" -            -"));
+
+        let path = std::env::temp_dir().join("svggloo-test-trace.json");
+        profiler.write_trace(&path).unwrap();
+        let trace: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(trace["traceEvents"].as_array().unwrap().len(), 4);
+        assert_eq!(trace["traceEvents"][1]["cat"], "render");
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    export::Exporter,
+    notify::RunSummary,
+    reader::{find_data_file, read_records_with, ReadOptions},
+    template::{render_records_with_summary, RenderOptions},
+};
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use serde::Deserialize;
+use std::{
+    any::Any,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Subdirectories of the spool directory, one per job state.
+const PENDING: &str = "pending";
+const PROCESSING: &str = "processing";
+const DONE: &str = "done";
+const FAILED: &str = "failed";
+
+/// A job file, describing a rendering.
+///
+/// The relative paths are resolved against the spool directory.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    /// Template to render.
+    pub template: PathBuf,
+    /// Data file, the CSV or TSV file next to the template by default.
+    pub data: Option<PathBuf>,
+    /// Output directory.
+    pub output: PathBuf,
+    /// Exporter used to convert the rendered files.
+    pub exporter: Option<String>,
+    /// Data fields used to name the rendered files.
+    pub field: Option<Vec<String>>,
+    /// Separator used to join the naming fields.
+    pub separator: Option<String>,
+    /// Generate an HTML gallery of the rendered files.
+    #[serde(default)]
+    pub gallery: bool,
+    /// Width of the PNG thumbnails of the rendered files.
+    pub thumbnails: Option<u32>,
+    /// URL notified with the summary of the run.
+    pub notify_url: Option<String>,
+}
+
+impl Job {
+    /// Build the rendering options of the job.
+    fn options(&self, base: &RenderOptions) -> Result<RenderOptions, Report> {
+        let exporter = self
+            .exporter
+            .as_deref()
+            .map(|name| {
+                Exporter::from_str(name, true).map_err(|_| eyre!("Unknown exporter {:?}", name))
+            })
+            .transpose()?;
+
+        Ok(RenderOptions {
+            exporter,
+            field_based_name: self.field.clone(),
+            separator: self.separator.clone(),
+            gallery: self.gallery,
+            thumbnails: self.thumbnails,
+            notify_url: self.notify_url.clone(),
+            ..base.clone()
+        })
+    }
+}
+
+/// Options of the queue.
+#[derive(Debug, Clone)]
+pub struct QueueOptions {
+    /// Delay between two scans of the pending jobs.
+    pub interval: Duration,
+    /// Stop once there are no more pending jobs, instead of waiting for more.
+    pub once: bool,
+    /// Options of the data readers shared by all the jobs.
+    pub read: ReadOptions,
+    /// Rendering options shared by all the jobs.
+    pub base: RenderOptions,
+}
+
+/// Process the jobs of a spool directory, one at a time.
+///
+/// The job files (YAML or JSON) dropped in the `pending` subdirectory are
+/// moved to `processing` while they are rendered, then to `done` or `failed`
+/// along with a `<job>.status.json` file summarizing the run.
+///
+/// The jobs found in `processing` when the queue starts were interrupted by a
+/// crash: they are resumed first, skipping the files they already produced.
+pub fn watch(spool: &Path, options: &QueueOptions) -> Result<(), Report> {
+    for state in [PENDING, PROCESSING, DONE, FAILED] {
+        fs::create_dir_all(spool.join(state))?;
+    }
+
+    for name in job_names(&spool.join(PROCESSING))? {
+        crate::note!("Resuming {}", name);
+        process(spool, &name, true, options)?;
+    }
+    loop {
+        let names = job_names(&spool.join(PENDING))?;
+        if names.is_empty() && options.once {
+            return Ok(());
+        }
+        for name in names {
+            fs::rename(
+                spool.join(PENDING).join(&name),
+                spool.join(PROCESSING).join(&name),
+            )?;
+            crate::note!("Processing {}", name);
+            process(spool, &name, false, options)?;
+        }
+        if !options.once {
+            thread::sleep(options.interval);
+        }
+    }
+}
+
+/// List the job files of a directory, in alphabetical order.
+fn job_names(dir: &Path) -> Result<Vec<String>, Report> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_job = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yaml" | "yml" | "json")
+        );
+        if is_job && path.is_file() {
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                names.push(name.to_owned());
+            }
+        }
+    }
+    names.sort();
+
+    Ok(names)
+}
+
+/// Process a job of the `processing` subdirectory, and file it with its status.
+///
+/// The exporters panic when they cannot run, which fails the job instead of
+/// stopping the queue.
+fn process(spool: &Path, name: &str, resume: bool, options: &QueueOptions) -> Result<(), Report> {
+    let path = spool.join(PROCESSING).join(name);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(spool, &path, resume, options)));
+    let summary = match result {
+        Ok(Ok(summary)) => summary,
+        Ok(Err(e)) => RunSummary {
+            error: Some(format!("{:#}", e)),
+            ..Default::default()
+        },
+        Err(payload) => RunSummary {
+            error: Some(format!("panicked: {}", panic_message(payload.as_ref()))),
+            ..Default::default()
+        },
+    };
+    match &summary.error {
+        Some(error) => eprintln!("{} failed: {}", name, error),
+        None => crate::note!("{} done", name),
+    }
+
+    let state = if summary.success { DONE } else { FAILED };
+    let stem = Path::new(name).with_extension("");
+    let status = spool
+        .join(state)
+        .join(format!("{}.status.json", stem.display()));
+    fs::write(status, serde_json::to_string_pretty(&summary)?)?;
+    fs::rename(&path, spool.join(state).join(name))?;
+
+    Ok(())
+}
+
+/// Get the message of a panic.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("unknown error", String::as_str),
+    }
+}
+
+/// Render a job file.
+fn run(
+    spool: &Path,
+    path: &Path,
+    resume: bool,
+    queue_options: &QueueOptions,
+) -> Result<RunSummary, Report> {
+    let job: Job = serde_yaml::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| eyre!("Invalid job file: {}", e))?;
+    let options = RenderOptions {
+        resume,
+        ..job.options(&queue_options.base)?
+    };
+    let template = spool.join(&job.template);
+    let data = match &job.data {
+        Some(data) => spool.join(data),
+        None => find_data_file(&template)?,
+    };
+    let records = read_records_with(&data, &queue_options.read)?;
+
+    let (summary, _) =
+        render_records_with_summary(&template, &records, &spool.join(&job.output), &options);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_once() {
+        let spool = std::env::temp_dir().join("svggloo-test-queue");
+        let _ = fs::remove_dir_all(&spool);
+        fs::create_dir_all(spool.join(PENDING)).unwrap();
+        fs::write(spool.join("t.svg"), "<svg><text>{{ city }}</text></svg>").unwrap();
+        fs::write(spool.join("t.csv"), "city\nAustin\n").unwrap();
+        fs::write(
+            spool.join(PENDING).join("a.yaml"),
+            "template: t.svg\noutput: out\n",
+        )
+        .unwrap();
+        fs::write(spool.join(PENDING).join("b.yaml"), "template: t.svg\n").unwrap();
+
+        let options = QueueOptions {
+            interval: Duration::ZERO,
+            once: true,
+            read: ReadOptions::default(),
+            base: RenderOptions::default(),
+        };
+        watch(&spool, &options).unwrap();
+        assert!(spool.join("out/austin.svg").is_file());
+        assert!(spool.join(DONE).join("a.yaml").is_file());
+        assert!(spool.join(DONE).join("a.status.json").is_file());
+        assert!(spool.join(FAILED).join("b.yaml").is_file());
+        assert!(job_names(&spool.join(PENDING)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload = panic::catch_unwind(|| panic!("cannot find {}", "inkscape")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "cannot find inkscape");
+        let payload = panic::catch_unwind(|| panic!("failed")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "failed");
+    }
+}
@@ -0,0 +1,77 @@
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use std::path::Path;
+use tiny_skia::{Pixmap, Transform};
+use usvg::{FitTo, Options, Tree};
+
+/// Rasterize an SVG document.
+///
+/// The relative paths of the linked resources are resolved from
+/// `resources_dir`. The image keeps the size of the document, unless a `width`
+/// is provided, in which case it is scaled to this width and keeps its aspect
+/// ratio.
+pub fn rasterize(svg: &str, resources_dir: &Path, width: Option<u32>) -> Result<Pixmap, Report> {
+    let mut options = Options {
+        resources_dir: Some(resources_dir.to_path_buf()),
+        ..Default::default()
+    };
+    options.fontdb.load_system_fonts();
+    let tree = Tree::from_str(svg, &options.to_ref())
+        .map_err(|e| eyre!("Cannot rasterize the SVG document: {}", e))?;
+
+    let fit_to = width.map_or(FitTo::Original, FitTo::Width);
+    let size = fit_to
+        .fit_to(tree.svg_node().size.to_screen_size())
+        .ok_or_else(|| eyre!("Invalid raster size"))?;
+    let mut pixmap = Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| eyre!("Invalid raster size {}x{}", size.width(), size.height()))?;
+    resvg::render(&tree, fit_to, Transform::default(), pixmap.as_mut())
+        .ok_or_else(|| eyre!("Cannot rasterize the SVG document"))?;
+
+    Ok(pixmap)
+}
+
+/// Compute the fraction of pixels differing between two images.
+///
+/// Images of different sizes are entirely different.
+pub fn pixel_difference(a: &Pixmap, b: &Pixmap) -> f64 {
+    if a.width() != b.width() || a.height() != b.height() {
+        return 1.0;
+    }
+    let pixels = a.data().len() / 4;
+    if pixels == 0 {
+        return 0.0;
+    }
+    let different = a
+        .data()
+        .chunks_exact(4)
+        .zip(b.data().chunks_exact(4))
+        .filter(|(a, b)| a != b)
+        .count();
+
+    different as f64 / pixels as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rasterize() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+  <rect width="10" height="10" fill="red"/>
+</svg>"#;
+        let pixmap = rasterize(svg, Path::new("."), None).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (20, 10));
+        let thumbnail = rasterize(svg, Path::new("."), Some(10)).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (10, 5));
+
+        let moved = svg.replace("<rect", r#"<rect x="10""#);
+        let other = rasterize(&moved, Path::new("."), None).unwrap();
+        assert_eq!(pixel_difference(&pixmap, &pixmap), 0.0);
+        assert_eq!(pixel_difference(&pixmap, &other), 1.0);
+        assert_eq!(pixel_difference(&pixmap, &thumbnail), 1.0);
+    }
+}
@@ -0,0 +1,509 @@
+use crate::{atomic::write_atomic, manifest::digest, template::Record};
+use chardetng::EncodingDetector;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use csv::{ReaderBuilder, Trim};
+use encoding_rs::{Encoding, UTF_8};
+use std::{
+    borrow::Cow,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Delimiters recognized when sniffing a data file, by order of preference.
+const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+
+/// Options controlling the reading of the data files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Field delimiter, detected when not specified.
+    pub delimiter: Option<u8>,
+    /// Character encoding, UTF-8 when not specified.
+    pub encoding: Option<InputEncoding>,
+    /// Lowercase the headers, and replace their spaces with underscores.
+    pub normalize_headers: bool,
+    /// Rename the duplicate and empty headers, instead of failing.
+    pub rename_headers: bool,
+}
+
+/// Character encoding of a data file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEncoding {
+    /// Guess the encoding from the content of the file.
+    Detect,
+    /// Use a known encoding, such as `windows-1252`.
+    Label(&'static Encoding),
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    /// Parse `auto`, or one of the labels of the WHATWG Encoding Standard.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Detect),
+            s => Encoding::for_label(s.as_bytes())
+                .map(Self::Label)
+                .ok_or_else(|| format!("unknown encoding {:?}", s)),
+        }
+    }
+}
+
+impl InputEncoding {
+    /// Transcode the content of a data file to UTF-8.
+    ///
+    /// A byte order mark takes precedence over the encoding, and is stripped.
+    fn decode(self, data: &[u8]) -> Result<Cow<'_, str>, Report> {
+        let encoding = match self {
+            Self::Detect => {
+                let mut detector = EncodingDetector::new();
+                detector.feed(data, true);
+                detector.guess(None, true)
+            }
+            Self::Label(encoding) => encoding,
+        };
+        let (text, used, malformed) = encoding.decode(data);
+        if malformed {
+            return Err(eyre!("The data file is not valid {}", used.name()));
+        }
+
+        Ok(text)
+    }
+}
+
+/// Extensions of the data files, by order of lookup.
+const EXTENSIONS: [&str; 5] = ["csv", "tsv", "tab", "psv", "parquet"];
+
+/// Find the data file located next to a template.
+///
+/// The CSV file is preferred, then the TSV, pipe-separated and Parquet files.
+/// The CSV file is returned when none exists, to report it as missing.
+pub fn data_file(template: &Path) -> PathBuf {
+    EXTENSIONS
+        .iter()
+        .map(|ext| template.with_extension(ext))
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| template.with_extension("csv"))
+}
+
+/// Find the data file located next to a template, failing with an explanation
+/// of the naming convention when none exists.
+pub fn find_data_file(template: &Path) -> Result<PathBuf, Report> {
+    let data = data_file(template);
+    if data.is_file() {
+        return Ok(data);
+    }
+    Err(eyre!(
+        "Cannot find the data file of {}: {} does not exist\n\
+         The records are read from the file named after the template, next to \
+         it, with one of the extensions {}. Create it, or specify the data file \
+         with --data.",
+        template.display(),
+        data.display(),
+        EXTENSIONS.join(", ")
+    ))
+}
+
+/// Whether a data source is a URL rather than a path.
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Path of the cached copy of a remote data file.
+///
+/// The extension of the URL is kept, to detect the delimiter of the file.
+fn cached_path(url: &str, cache_dir: &Path) -> PathBuf {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| EXTENSIONS.contains(ext))
+        .unwrap_or("csv");
+    cache_dir.join(format!("{}.{}", digest(&[url]), ext))
+}
+
+/// Fetch a remote data file into the cache directory, and return the path of
+/// its cached copy.
+///
+/// The ETag of the file is stored along with it, and sent back with the next
+/// requests: the file is only downloaded again when it changed on the server.
+pub fn fetch_data_file(url: &str, cache_dir: &Path) -> Result<PathBuf, Report> {
+    let cached = cached_path(url, cache_dir);
+    let etag_file = cached.with_extension("etag");
+    let mut request = ureq::get(url);
+    if cached.is_file() {
+        if let Ok(etag) = fs::read_to_string(&etag_file) {
+            request = request.set("If-None-Match", etag.trim());
+        }
+    }
+    let response = request
+        .call()
+        .map_err(|e| eyre!("Cannot fetch the data file {}: {}", url, e))?;
+    if response.status() == 304 {
+        return Ok(cached);
+    }
+
+    let etag = response.header("ETag").map(str::to_owned);
+    let mut data = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut data)
+        .map_err(|e| eyre!("Cannot fetch the data file {}: {}", url, e))?;
+    fs::create_dir_all(cache_dir)?;
+    // The ETag is only written once the data file is complete, so that an
+    // interrupted fetch never validates a truncated or outdated file.
+    let _ = fs::remove_file(&etag_file);
+    write_atomic(&cached, |out| Ok(out.write_all(&data)?))?;
+    if let Some(etag) = etag {
+        write_atomic(&etag_file, |out| Ok(out.write_all(etag.as_bytes())?))?;
+    }
+
+    Ok(cached)
+}
+
+/// Detect the field delimiter of a data file.
+///
+/// The `.tsv` and `.tab` files are tab-separated, and the `.psv` files
+/// pipe-separated. Otherwise, the most frequent delimiter of the header line
+/// wins, which handles the semicolons of the CSV files exported by Excel in
+/// some locales.
+fn detect_delimiter(path: &Path, data: &[u8]) -> u8 {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("tsv" | "tab") => return b'\t',
+        Some("psv") => return b'|',
+        _ => (),
+    }
+    let header = data.split(|&b| b == b'\n').next().unwrap_or_default();
+    DELIMITERS
+        .iter()
+        .map(|&delimiter| {
+            let count = header.iter().filter(|&&b| b == delimiter).count();
+            (count, delimiter)
+        })
+        .filter(|&(count, _)| count > 0)
+        // The first delimiter wins the ties.
+        .rev()
+        .max_by_key(|&(count, _)| count)
+        .map_or(b',', |(_, delimiter)| delimiter)
+}
+
+/// Normalize a header: lowercase it, and replace its whitespace with
+/// underscores, so that `Zip Code` becomes `zip_code`.
+fn normalize_header(header: &str) -> String {
+    header
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+        .to_lowercase()
+}
+
+/// Check that the headers are unique and not empty.
+///
+/// The positions in the errors start at 1, like the columns of a spreadsheet.
+/// When renaming, the duplicate headers get a suffix with their occurrence
+/// (`name`, `name_2`, `name_3`), and the empty headers are named after their
+/// position (`column_4`).
+fn check_headers(headers: Vec<String>, rename: bool) -> Result<Vec<String>, Report> {
+    let mut checked: Vec<String> = Vec::with_capacity(headers.len());
+    for (index, header) in headers.into_iter().enumerate() {
+        let position = index + 1;
+        let header = if header.is_empty() {
+            if !rename {
+                return Err(eyre!("Empty header in column {}", position));
+            }
+            format!("column_{}", position)
+        } else if let Some(first) = checked.iter().position(|h| *h == header) {
+            if !rename {
+                return Err(eyre!(
+                    "Duplicate header {:?} in columns {} and {}",
+                    header,
+                    first + 1,
+                    position
+                ));
+            }
+            (2..)
+                .map(|n| format!("{}_{}", header, n))
+                .find(|name| !checked.contains(name))
+                .unwrap()
+        } else {
+            header
+        };
+        checked.push(header);
+    }
+
+    Ok(checked)
+}
+
+/// Normalize the headers when requested, and check them.
+pub(crate) fn prepare_headers<'a>(
+    headers: impl Iterator<Item = &'a str>,
+    options: &ReadOptions,
+) -> Result<Vec<String>, Report> {
+    let headers = headers
+        .map(|header| {
+            if options.normalize_headers {
+                normalize_header(header)
+            } else {
+                header.to_owned()
+            }
+        })
+        .collect();
+    check_headers(headers, options.rename_headers)
+}
+
+/// Read the records of a data file.
+///
+/// The whitespace around the headers is trimmed, and the headers are prepared
+/// with [`prepare_headers`]. The Parquet files are read when the `parquet`
+/// feature is enabled.
+pub fn read_records_with(path: &Path, options: &ReadOptions) -> Result<Vec<Record>, Report> {
+    if path.extension().is_some_and(|ext| ext == "parquet") {
+        return parquet::read_records(path, options);
+    }
+    let data = fs::read(path)?;
+    let data = options
+        .encoding
+        .unwrap_or(InputEncoding::Label(UTF_8))
+        .decode(&data)?;
+    let data = data.as_bytes();
+    let delimiter = options
+        .delimiter
+        .unwrap_or_else(|| detect_delimiter(path, data));
+    let mut reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .trim(Trim::Headers)
+        .from_reader(data);
+    let headers = prepare_headers(reader.headers()?.iter(), options)?;
+    reader.set_headers(headers.into());
+    let records = reader.deserialize().collect::<Result<_, _>>()?;
+
+    Ok(records)
+}
+
+#[cfg(feature = "parquet")]
+mod parquet {
+    use super::prepare_headers;
+    use super::ReadOptions;
+    use crate::template::Record;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use color_eyre::{eyre::Report, Result};
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::Field,
+    };
+    use std::{fs::File, path::Path};
+
+    /// Read the records of a Parquet file.
+    ///
+    /// The values are converted to their exact text representation: the
+    /// numbers are not rounded, the dates and the timestamps are in the ISO
+    /// 8601 format, the binary values are encoded in base64, the nested values
+    /// in JSON, and the nulls are empty.
+    pub fn read_records(path: &Path, options: &ReadOptions) -> Result<Vec<Record>, Report> {
+        let reader = SerializedFileReader::new(File::open(path)?)?;
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let headers = schema
+            .root_schema()
+            .get_fields()
+            .iter()
+            .map(|field| field.name());
+        let headers = prepare_headers(headers, options)?;
+        let mut records = Vec::new();
+        for row in reader.get_row_iter(None)? {
+            let row = row?;
+            let values = row.get_column_iter().map(|(_, field)| text(field));
+            records.push(headers.iter().cloned().zip(values).collect());
+        }
+
+        Ok(records)
+    }
+
+    /// Text representation of a value.
+    fn text(field: &Field) -> String {
+        match field {
+            Field::Null => String::new(),
+            Field::Float(value) => value.to_string(),
+            Field::Double(value) => value.to_string(),
+            Field::Str(value) => value.clone(),
+            Field::Bytes(value) => STANDARD.encode(value.data()),
+            Field::Date(days) => date(i64::from(*days)),
+            Field::TimestampMillis(millis) => timestamp(*millis, 1_000),
+            Field::TimestampMicros(micros) => timestamp(*micros, 1_000_000),
+            Field::Group(_) | Field::ListInternal(_) | Field::MapInternal(_) => {
+                field.to_json_value().to_string()
+            }
+            // The booleans, the integers and the decimals.
+            field => field.to_string(),
+        }
+    }
+
+    /// Format the number of days since the Unix epoch as a `YYYY-MM-DD` date.
+    fn date(days: i64) -> String {
+        // Howard Hinnant's civil_from_days algorithm.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    /// Format a Unix timestamp in `units` per second as a UTC ISO 8601
+    /// timestamp, keeping its fractional seconds.
+    fn timestamp(value: i64, units: i64) -> String {
+        let seconds = value.div_euclid(units);
+        let fraction = value.rem_euclid(units);
+        let time = seconds.rem_euclid(86_400);
+        let mut text = format!(
+            "{}T{:02}:{:02}:{:02}",
+            date(seconds.div_euclid(86_400)),
+            time / 3_600,
+            time % 3_600 / 60,
+            time % 60
+        );
+        if fraction != 0 {
+            let width = units.ilog10() as usize;
+            text.push_str(&format!(".{:0width$}", fraction, width = width));
+        }
+        text.push('Z');
+        text
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_text() {
+            assert_eq!(text(&Field::Null), "");
+            assert_eq!(text(&Field::Float(0.1)), "0.1");
+            assert_eq!(
+                text(&Field::Long(9_007_199_254_740_993)),
+                "9007199254740993"
+            );
+            assert_eq!(text(&Field::Date(19_372)), "2023-01-15");
+            assert_eq!(text(&Field::Date(-1)), "1969-12-31");
+            assert_eq!(
+                text(&Field::TimestampMillis(1_673_776_800_250)),
+                "2023-01-15T10:00:00.250Z"
+            );
+        }
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+mod parquet {
+    use super::ReadOptions;
+    use crate::template::Record;
+    use color_eyre::{
+        eyre::{eyre, Report},
+        Result,
+    };
+    use std::path::Path;
+
+    /// Fail, the Parquet support is not compiled in.
+    pub fn read_records(_path: &Path, _options: &ReadOptions) -> Result<Vec<Record>, Report> {
+        Err(eyre!("svggloo was built without the `parquet` feature"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_delimiter() {
+        let csv = Path::new("data.csv");
+        assert_eq!(detect_delimiter(csv, b"city,state\nAustin,TX\n"), b',');
+        assert_eq!(
+            detect_delimiter(csv, b"city;state;note\nAustin;TX;a,b\n"),
+            b';'
+        );
+        assert_eq!(detect_delimiter(csv, b"city\nAustin\n"), b',');
+        assert_eq!(detect_delimiter(csv, b"a,b;c\n"), b',');
+        assert_eq!(
+            detect_delimiter(Path::new("data.tsv"), b"city,state\n"),
+            b'\t'
+        );
+    }
+
+    #[test]
+    fn test_find_data_file() {
+        let template = std::env::temp_dir().join("svggloo-test-find-data.svg");
+        let csv = template.with_extension("csv");
+        let _ = fs::remove_file(&csv);
+        let error = find_data_file(&template).unwrap_err().to_string();
+        assert!(error.contains(&csv.display().to_string()));
+        assert!(error.contains("--data"));
+        fs::write(&csv, "city\nAustin\n").unwrap();
+        assert_eq!(find_data_file(&template).unwrap(), csv);
+        fs::remove_file(&csv).unwrap();
+    }
+
+    #[test]
+    fn test_cached_path() {
+        let dir = Path::new("cache");
+        let tsv = cached_path("https://example.org/ratings.tsv?v=2", dir);
+        assert_eq!(tsv.extension().unwrap(), "tsv");
+        let csv = cached_path("https://example.org/ratings", dir);
+        assert_eq!(csv.extension().unwrap(), "csv");
+        assert_ne!(tsv, cached_path("https://example.org/ratings.tsv", dir));
+        assert!(is_url("https://example.org/ratings.csv"));
+        assert!(!is_url("ratings.csv"));
+    }
+
+    #[test]
+    fn test_decode() {
+        let data = b"city\nMontr\xe9al\n";
+        let windows_1252: InputEncoding = "windows-1252".parse().unwrap();
+        assert_eq!(windows_1252.decode(data).unwrap(), "city\nMontréal\n");
+        assert_eq!(
+            InputEncoding::Detect.decode(data).unwrap(),
+            "city\nMontréal\n"
+        );
+        assert!(InputEncoding::Label(UTF_8).decode(data).is_err());
+        assert!("klingon".parse::<InputEncoding>().is_err());
+    }
+
+    #[test]
+    fn test_check_headers() {
+        let headers = |names: &[&str]| names.iter().map(|&name| name.to_owned()).collect();
+        let error = check_headers(headers(&["name", "city", "name"]), false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Duplicate header "name" in columns 1 and 3"#
+        );
+        let error = check_headers(headers(&["name", ""]), false).unwrap_err();
+        assert_eq!(error.to_string(), "Empty header in column 2");
+        assert_eq!(
+            check_headers(headers(&["name", "name_2", "name", ""]), true).unwrap(),
+            ["name", "name_2", "name_3", "column_4"]
+        );
+    }
+
+    #[test]
+    fn test_read_records_with() {
+        let path = std::env::temp_dir().join("svggloo-test-reader.csv");
+        fs::write(&path, "\u{feff} City , Zip  Code\nAustin,78701\n").unwrap();
+
+        let records = read_records_with(&path, &ReadOptions::default()).unwrap();
+        assert_eq!(records[0].get("City").unwrap(), "Austin");
+        assert_eq!(records[0].get("Zip  Code").unwrap(), "78701");
+
+        let options = ReadOptions {
+            normalize_headers: true,
+            ..Default::default()
+        };
+        let records = read_records_with(&path, &options).unwrap();
+        assert_eq!(records[0].get("city").unwrap(), "Austin");
+        assert_eq!(records[0].get("zip_code").unwrap(), "78701");
+    }
+}
@@ -0,0 +1,238 @@
+use crate::template::Record;
+use clap::ValueEnum;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use regex::Regex;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt};
+#[cfg(feature = "native")]
+use std::{fs, path::Path};
+
+/// Schema of the data.
+///
+/// The schema declares the constraints applying to the columns of the data,
+/// and is loaded from a YAML file:
+///
+/// ```yaml
+/// columns:
+///   city:
+///     required: true
+///   state:
+///     pattern: "^[A-Z]{2}$"
+///   score:
+///     type: number
+///     min: 0
+///     max: 100
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    /// Constraints of the columns.
+    #[serde(default)]
+    pub columns: BTreeMap<String, Column>,
+}
+
+/// Constraints of a column.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Column {
+    /// Whether the column must be present and its values non-empty.
+    #[serde(default)]
+    pub required: bool,
+    /// Type of the values.
+    #[serde(rename = "type")]
+    pub kind: Option<ColumnType>,
+    /// Minimum value of numeric columns.
+    pub min: Option<f64>,
+    /// Maximum value of numeric columns.
+    pub max: Option<f64>,
+    /// Regular expression the values must match.
+    pub pattern: Option<String>,
+    /// Allowed values.
+    pub values: Option<Vec<String>>,
+    #[serde(skip)]
+    regex: Option<Regex>,
+}
+
+/// Type of the values of a column.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+/// What to do with the records violating the schema.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OnInvalid {
+    /// Report the violations and stop before rendering anything.
+    Fail,
+    /// Report the violations and skip the invalid records.
+    Skip,
+}
+
+/// A violation of the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Row number of the record, starting at 1.
+    pub row: usize,
+    /// Column violating the schema.
+    pub column: String,
+    /// Description of the violation.
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Record {}, column {:?}: {}",
+            self.row, self.column, self.message
+        )
+    }
+}
+
+impl Schema {
+    /// Load a schema from a YAML file.
+    #[cfg(feature = "native")]
+    pub fn from_path(path: &Path) -> Result<Self, Report> {
+        let source = fs::read_to_string(path)?;
+        Self::parse(&source).map_err(|e| eyre!("Invalid schema file {}: {}", path.display(), e))
+    }
+
+    /// Parse a YAML schema, and compile its patterns.
+    pub fn parse(source: &str) -> Result<Self, Report> {
+        let mut schema: Schema = serde_yaml::from_str(source)?;
+        for (name, column) in schema.columns.iter_mut() {
+            if let Some(pattern) = &column.pattern {
+                column.regex = Some(
+                    Regex::new(pattern)
+                        .map_err(|e| eyre!("Invalid pattern of the column {:?}: {}", name, e))?,
+                );
+            }
+        }
+
+        Ok(schema)
+    }
+
+    /// Validate the records, and return all the violations.
+    pub fn validate(&self, records: &[Record]) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (index, record) in records.iter().enumerate() {
+            for (name, column) in &self.columns {
+                let violation = |message: String| Violation {
+                    row: index + 1,
+                    column: name.clone(),
+                    message,
+                };
+                match record.get(name).map(String::as_str) {
+                    None | Some("") if column.required => {
+                        violations.push(violation(String::from("a value is required")))
+                    }
+                    None | Some("") => (),
+                    Some(value) => violations.extend(column.check(value).map(violation)),
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+impl Column {
+    /// Check a non-empty value against the constraints of the column.
+    fn check(&self, value: &str) -> Option<String> {
+        let number = value.trim().parse::<f64>().ok();
+        match self.kind {
+            Some(ColumnType::Integer) if value.trim().parse::<i64>().is_err() => {
+                return Some(format!("{:?} is not an integer", value))
+            }
+            Some(ColumnType::Number) if number.is_none() => {
+                return Some(format!("{:?} is not a number", value))
+            }
+            Some(ColumnType::Boolean)
+                if !matches!(
+                    value.trim().to_lowercase().as_str(),
+                    "true" | "false" | "yes" | "no" | "1" | "0"
+                ) =>
+            {
+                return Some(format!("{:?} is not a boolean", value))
+            }
+            _ => (),
+        }
+        if self.min.is_some() || self.max.is_some() {
+            let n = match number {
+                Some(n) => n,
+                None => return Some(format!("{:?} is not a number", value)),
+            };
+            if let Some(min) = self.min.filter(|min| n < *min) {
+                return Some(format!("{} is less than {}", value, min));
+            }
+            if let Some(max) = self.max.filter(|max| n > *max) {
+                return Some(format!("{} is greater than {}", value, max));
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(value) {
+                return Some(format!("{:?} does not match {:?}", value, regex.as_str()));
+            }
+        }
+        if let Some(values) = &self.values {
+            if !values.iter().any(|v| v == value) {
+                return Some(format!("{:?} is not one of {:?}", value, values));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let schema = Schema::parse(
+            r#"
+columns:
+  city:
+    required: true
+    pattern: "^[A-Z]"
+  score:
+    type: number
+    min: 0
+    max: 100
+"#,
+        )
+        .unwrap();
+        let records = vec![
+            Record::from([
+                (String::from("city"), String::from("Austin")),
+                (String::from("score"), String::from("42")),
+            ]),
+            Record::from([
+                (String::from("city"), String::from("")),
+                (String::from("score"), String::from("142")),
+            ]),
+            Record::from([
+                (String::from("city"), String::from("provo")),
+                (String::from("score"), String::from("high")),
+            ]),
+        ];
+        let violations = schema.validate(&records);
+        let rows: Vec<(usize, &str)> = violations
+            .iter()
+            .map(|v| (v.row, v.column.as_str()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![(2, "city"), (2, "score"), (3, "city"), (3, "score")]
+        );
+        assert!(Schema::parse("columns: {city: {pattern: '('}}").is_err());
+    }
+}
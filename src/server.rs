@@ -0,0 +1,175 @@
+use crate::template::{record_from_json, render_to_strings, Record, RenderOptions};
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use csv::Reader;
+use std::{
+    env, fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    process, slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Format of the rendered document returned by the API.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Svg,
+    Pdf,
+}
+
+/// Serve the rendering of a template over HTTP.
+///
+/// `POST /render` renders the template with the record of the request body,
+/// either a JSON object or a CSV document with a single record, and responds
+/// with the rendered SVG. The exported PDF is returned instead when the request
+/// has a `format=pdf` query parameter or accepts `application/pdf`, which
+/// requires an exporter.
+///
+/// The template is read for every request, so that it can be updated without
+/// restarting the server.
+pub fn serve(address: &str, svg_template: &Path, options: &RenderOptions) -> Result<(), Report> {
+    let server = Server::http(address).map_err(|e| eyre!("Cannot listen on {}: {}", address, e))?;
+    crate::note!("Listening on http://{}", server.server_addr());
+
+    for mut request in server.incoming_requests() {
+        let (status, content_type, body) = match handle(&mut request, svg_template, options) {
+            Ok((content_type, body)) => (200, content_type, body),
+            Err((status, e)) => (
+                status,
+                "text/plain; charset=utf-8",
+                format!("{:#}\n", e).into(),
+            ),
+        };
+        crate::note!("{} {} {}", request.method(), request.url(), status);
+        let header = Header::from_bytes("Content-Type", content_type).expect("valid header");
+        let response = Response::from_data(body)
+            .with_status_code(status)
+            .with_header(header);
+        if let Err(e) = request.respond(response) {
+            eprintln!("Cannot respond: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle a request, and return the content type and the body of the response,
+/// or an error status.
+fn handle(
+    request: &mut Request,
+    svg_template: &Path,
+    options: &RenderOptions,
+) -> Result<(&'static str, Vec<u8>), (u16, Report)> {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    if path != "/render" {
+        return Err((404, eyre!("Not found")));
+    }
+    if *request.method() != Method::Post {
+        return Err((405, eyre!("Only POST is allowed")));
+    }
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.as_str().to_owned())
+            .unwrap_or_default()
+    };
+    let format = if query.split('&').any(|param| param == "format=pdf")
+        || header("Accept").contains("application/pdf")
+    {
+        Format::Pdf
+    } else {
+        Format::Svg
+    };
+    if format == Format::Pdf && options.exporter.is_none() {
+        return Err((400, eyre!("No exporter is configured to render PDF files")));
+    }
+    let content_type = header("Content-Type");
+
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|e| (400, e.into()))?;
+    let record = parse_record(&body, &content_type).map_err(|e| (400, e))?;
+    let mut outputs = render_to_strings(svg_template, &[record], options).map_err(|e| (422, e))?;
+    if outputs.len() != 1 {
+        return Err((
+            422,
+            eyre!("The record produces {} outputs instead of 1", outputs.len()),
+        ));
+    }
+    let (file_name, rendered) = outputs.remove(0);
+
+    match format {
+        Format::Svg => Ok(("image/svg+xml", rendered.into_bytes())),
+        Format::Pdf => export(&file_name, &rendered, options)
+            .map(|pdf| ("application/pdf", pdf))
+            .map_err(|e| (500, e)),
+    }
+}
+
+/// Parse the record of a request body, a JSON object or a CSV document.
+pub fn parse_record(body: &str, content_type: &str) -> Result<Record, Report> {
+    if content_type.starts_with("text/csv") {
+        let mut reader = Reader::from_reader(body.as_bytes());
+        let mut records = reader.deserialize().collect::<Result<Vec<Record>, _>>()?;
+        if records.len() != 1 {
+            return Err(eyre!("Expected 1 CSV record, got {}", records.len()));
+        }
+        return Ok(records.remove(0));
+    }
+
+    record_from_json(body)
+}
+
+/// Export a rendered SVG to PDF in a temporary directory, and return the PDF.
+fn export(file_name: &str, rendered: &str, options: &RenderOptions) -> Result<Vec<u8>, Report> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let exporter = options
+        .exporter
+        .expect("the exporter is checked beforehand");
+    let dir = env::temp_dir().join(format!(
+        "svggloo-{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir)?;
+    let svg = dir.join(file_name);
+    fs::write(&svg, rendered)?;
+
+    // The exporters panic when they cannot run, which must not stop the server.
+    let exported = panic::catch_unwind(AssertUnwindSafe(|| {
+        exporter.export(
+            slice::from_ref(&svg),
+            options.export_area,
+            &options.export_limits,
+        )
+    }));
+    let pdf = fs::read(svg.with_extension("pdf"));
+    fs::remove_dir_all(&dir)?;
+    match (exported, pdf) {
+        (Ok(()), Ok(pdf)) => Ok(pdf),
+        _ => Err(eyre!("{:?} failed to export {}", exporter, file_name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_record() {
+        let record = parse_record(r#"{"city": "Austin"}"#, "application/json").unwrap();
+        assert_eq!(record["city"], "Austin");
+
+        let record = parse_record("city,score\nAustin,4.5\n", "text/csv; charset=utf-8").unwrap();
+        assert_eq!(record["score"], "4.5");
+        assert!(parse_record("city\nAustin\nDallas\n", "text/csv").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{
+    reader::{prepare_headers, ReadOptions},
+    template::Record,
+};
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Scope granting a read-only access to the spreadsheets.
+const SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets.readonly";
+
+/// Authentication to the Google Sheets API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SheetsAuth {
+    /// API key, which can only read the publicly shared spreadsheets.
+    ApiKey(String),
+    /// JSON key file of a service account the spreadsheet is shared with.
+    ServiceAccount(PathBuf),
+}
+
+/// Fields of the JSON key file of a service account.
+#[derive(Debug, Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+/// Claims of the token requesting an access token.
+#[derive(Debug, Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+/// Response of the token endpoint.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Response of the `values` endpoint.
+#[derive(Debug, Deserialize)]
+struct ValueRange {
+    #[serde(default)]
+    values: Vec<Vec<String>>,
+}
+
+/// Extract the ID of a spreadsheet from its URL, e.g.
+/// `https://docs.google.com/spreadsheets/d/ID/edit#gid=0`.
+///
+/// Anything else is considered to be an ID already.
+pub fn sheet_id(sheet: &str) -> &str {
+    sheet
+        .split_once("/spreadsheets/d/")
+        .and_then(|(_, rest)| rest.split(['/', '?', '#']).next())
+        .unwrap_or(sheet)
+}
+
+/// Exchange the key of a service account for an access token.
+fn access_token(key_file: &Path) -> Result<String, Report> {
+    let key: ServiceAccountKey = serde_json::from_str(&fs::read_to_string(key_file)?)
+        .map_err(|e| eyre!("Invalid service account key {}: {}", key_file.display(), e))?;
+    let iat = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let claims = Claims {
+        iss: &key.client_email,
+        scope: SCOPE,
+        aud: &key.token_uri,
+        iat,
+        exp: iat + 3600,
+    };
+    let assertion = encode(
+        &Header::new(Algorithm::RS256),
+        &claims,
+        &EncodingKey::from_rsa_pem(key.private_key.as_bytes())?,
+    )?;
+    let response = ureq::post(&key.token_uri)
+        .send_form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", &assertion),
+        ])
+        .map_err(|e| eyre!("Cannot authenticate to Google: {}", e))?;
+    let token: TokenResponse = serde_json::from_str(&response.into_string()?)?;
+
+    Ok(token.access_token)
+}
+
+/// Convert the rows of a sheet into records, the first row being the headers.
+///
+/// The API omits the trailing empty cells of the rows, they are filled with
+/// empty values.
+fn records(rows: Vec<Vec<String>>, options: &ReadOptions) -> Result<Vec<Record>, Report> {
+    let mut rows = rows.into_iter();
+    let Some(headers) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let headers = prepare_headers(headers.iter().map(|h| h.trim()), options)?;
+
+    Ok(rows
+        .map(|row| {
+            let mut values = row.into_iter();
+            headers
+                .iter()
+                .map(|header| (header.clone(), values.next().unwrap_or_default()))
+                .collect()
+        })
+        .collect())
+}
+
+/// Read the records of a Google Sheets spreadsheet.
+///
+/// The `range` is in the A1 notation, e.g. `Cities!A1:F`, and defaults to the
+/// first sheet. The headers are checked like the ones of the data files.
+pub fn read_sheet(
+    sheet: &str,
+    range: Option<&str>,
+    auth: &SheetsAuth,
+    options: &ReadOptions,
+) -> Result<Vec<Record>, Report> {
+    let url = format!(
+        "https://sheets.googleapis.com/v4/spreadsheets/{}/values/{}",
+        sheet_id(sheet),
+        urlencoding(range.unwrap_or("A:ZZZ"))
+    );
+    let request = match auth {
+        SheetsAuth::ApiKey(key) => ureq::get(&url).query("key", key),
+        SheetsAuth::ServiceAccount(key_file) => ureq::get(&url).set(
+            "Authorization",
+            &format!("Bearer {}", access_token(key_file)?),
+        ),
+    };
+    let response = request
+        .call()
+        .map_err(|e| eyre!("Cannot read the spreadsheet {}: {}", sheet, e))?;
+    let range: ValueRange = serde_json::from_str(&response.into_string()?)?;
+
+    records(range.values, options)
+}
+
+/// Percent-encode a path segment.
+fn urlencoding(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                char::from(b).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sheet_id() {
+        assert_eq!(
+            sheet_id("https://docs.google.com/spreadsheets/d/1AbC-d_E/edit#gid=0"),
+            "1AbC-d_E"
+        );
+        assert_eq!(sheet_id("1AbC-d_E"), "1AbC-d_E");
+        assert_eq!(urlencoding("City ratings!A1:F"), "City%20ratings%21A1%3AF");
+    }
+
+    #[test]
+    fn test_records() {
+        let rows = vec![
+            vec![String::from("City "), String::from("Score")],
+            vec![String::from("Austin"), String::from("3.2")],
+            vec![String::from("Provo")],
+        ];
+        let options = ReadOptions {
+            normalize_headers: true,
+            ..Default::default()
+        };
+        let records = records(rows, &options).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["score"], "3.2");
+        assert_eq!(records[1]["city"], "Provo");
+        assert_eq!(records[1]["score"], "");
+    }
+}
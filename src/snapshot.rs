@@ -0,0 +1,152 @@
+use color_eyre::{eyre::Report, Result};
+use similar::TextDiff;
+use std::{collections::BTreeSet, ffi::OsStr, fs, path::Path};
+
+/// Result of the comparison of a rendered file with its golden file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The rendered file matches its golden file.
+    Unchanged,
+    /// The rendered file differs from its golden file, with the unified diff.
+    Changed(String),
+    /// The rendered file has no golden file.
+    New,
+    /// The golden file has no rendered file anymore.
+    Removed,
+}
+
+/// Normalize an SVG document before comparing it.
+///
+/// The line endings, the trailing whitespace and the blank lines are not
+/// significant, and would make the snapshots depend on the platform or on the
+/// editor used to write the template.
+pub fn normalize(svg: &str) -> String {
+    let mut normalized = String::with_capacity(svg.len());
+    for line in svg.lines().map(str::trim_end).filter(|l| !l.is_empty()) {
+        normalized.push_str(line);
+        normalized.push('\n');
+    }
+
+    normalized
+}
+
+/// Compare the rendered files with the golden files of a directory.
+///
+/// The `outputs` are the rendered files, as `(file name, content)` pairs. The
+/// outcomes are returned by file name. With `update`, the golden files are
+/// replaced by the normalized rendered files, and the obsolete ones removed.
+pub fn compare(
+    outputs: &[(String, String)],
+    golden_dir: &Path,
+    update: bool,
+) -> Result<Vec<(String, Outcome)>, Report> {
+    let mut outcomes = Vec::new();
+    let mut rendered = BTreeSet::new();
+    for (file_name, content) in outputs {
+        rendered.insert(file_name.as_str());
+        let golden_file = golden_dir.join(file_name);
+        let actual = normalize(content);
+        let outcome = match fs::read_to_string(&golden_file) {
+            Ok(expected) => {
+                let expected = normalize(&expected);
+                if expected == actual {
+                    Outcome::Unchanged
+                } else {
+                    let diff = TextDiff::from_lines(&expected, &actual)
+                        .unified_diff()
+                        .header(&format!("a/{}", file_name), &format!("b/{}", file_name))
+                        .to_string();
+                    Outcome::Changed(diff)
+                }
+            }
+            Err(_) => Outcome::New,
+        };
+        if update && outcome != Outcome::Unchanged {
+            fs::create_dir_all(golden_dir)?;
+            fs::write(&golden_file, actual)?;
+        }
+        outcomes.push((file_name.clone(), outcome));
+    }
+
+    // Look for the golden files which are not rendered anymore.
+    if golden_dir.is_dir() {
+        let mut removed = Vec::new();
+        for entry in fs::read_dir(golden_dir)? {
+            let path = entry?.path();
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if path.extension() == Some(OsStr::new("svg")) && !rendered.contains(file_name.as_ref())
+            {
+                removed.push(file_name.into_owned());
+                if update {
+                    fs::remove_file(&path)?;
+                }
+            }
+        }
+        removed.sort();
+        outcomes.extend(removed.into_iter().map(|name| (name, Outcome::Removed)));
+    }
+
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize("<svg>  \r\n\r\n  <text/>\r\n</svg>"),
+            "<svg>\n  <text/>\n</svg>\n"
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let golden_dir = std::env::temp_dir().join("svggloo-test-snapshots");
+        let _ = fs::remove_dir_all(&golden_dir);
+        fs::create_dir_all(&golden_dir).unwrap();
+        fs::write(
+            golden_dir.join("austin.svg"),
+            "<svg>\n<text>Austin</text>\n</svg>\n",
+        )
+        .unwrap();
+        fs::write(
+            golden_dir.join("provo.svg"),
+            "<svg>\n<text>Provo</text>\n</svg>\n",
+        )
+        .unwrap();
+        fs::write(golden_dir.join("orem.svg"), "<svg/>\n").unwrap();
+        let outputs = vec![
+            (
+                String::from("austin.svg"),
+                String::from("<svg>\r\n<text>Austin</text>\r\n</svg>"),
+            ),
+            (
+                String::from("provo.svg"),
+                String::from("<svg>\n<text>Provo, UT</text>\n</svg>\n"),
+            ),
+            (String::from("boise.svg"), String::from("<svg/>")),
+        ];
+
+        let outcomes = compare(&outputs, &golden_dir, false).unwrap();
+        assert_eq!(
+            outcomes[0],
+            (String::from("austin.svg"), Outcome::Unchanged)
+        );
+        match &outcomes[1].1 {
+            Outcome::Changed(diff) => {
+                assert!(diff.contains("-<text>Provo</text>\n+<text>Provo, UT</text>\n"))
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        assert_eq!(outcomes[2], (String::from("boise.svg"), Outcome::New));
+        assert_eq!(outcomes[3], (String::from("orem.svg"), Outcome::Removed));
+
+        let outcomes = compare(&outputs, &golden_dir, true).unwrap();
+        assert_eq!(outcomes.len(), 4);
+        let outcomes = compare(&outputs, &golden_dir, false).unwrap();
+        assert!(outcomes.iter().all(|(_, o)| *o == Outcome::Unchanged));
+        fs::remove_dir_all(&golden_dir).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+#[cfg(feature = "native")]
+use crate::template::read_template;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+#[cfg(feature = "native")]
+use std::path::Path;
+use std::{collections::HashMap, fmt};
+
+/// Source the templates are loaded from.
+///
+/// The templates are read from the filesystem by default. A source lets the
+/// applications embedding the library, such as servers deployed without
+/// template files, provide them from elsewhere.
+pub trait TemplateSource: fmt::Debug + Send + Sync {
+    /// Load the template with this name, e.g. the path given to the renderer.
+    fn load(&self, name: &str) -> Result<String, Report>;
+}
+
+/// Templates read from the filesystem, or from the standard input for `-`.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSource;
+
+#[cfg(feature = "native")]
+impl TemplateSource for FileSource {
+    fn load(&self, name: &str) -> Result<String, Report> {
+        read_template(Path::new(name))
+    }
+}
+
+/// Templates held in memory by name, e.g. embedded in the binary with
+/// `include_str!`.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    templates: HashMap<String, String>,
+}
+
+impl MemorySource {
+    /// Create an empty source.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a template, replacing the one with the same name.
+    pub fn add(mut self, name: impl Into<String>, source: impl Into<String>) -> Self {
+        self.templates.insert(name.into(), source.into());
+        self
+    }
+}
+
+impl TemplateSource for MemorySource {
+    fn load(&self, name: &str) -> Result<String, Report> {
+        self.templates
+            .get(name)
+            .cloned()
+            .ok_or_else(|| eyre!("Unknown template {:?}", name))
+    }
+}
+
+/// Templates downloaded over http(s), their names being resolved against a
+/// base URL.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone)]
+pub struct UrlSource {
+    base: String,
+}
+
+#[cfg(feature = "native")]
+impl UrlSource {
+    /// Create a source resolving the template names against `base`, e.g.
+    /// `https://example.org/templates/`.
+    pub fn new(base: impl Into<String>) -> Self {
+        Self { base: base.into() }
+    }
+
+    /// URL of a template.
+    fn url(&self, name: &str) -> String {
+        match name.starts_with("http://") || name.starts_with("https://") {
+            true => name.to_owned(),
+            false => format!(
+                "{}/{}",
+                self.base.trim_end_matches('/'),
+                name.trim_start_matches('/')
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl TemplateSource for UrlSource {
+    fn load(&self, name: &str) -> Result<String, Report> {
+        let url = self.url(name);
+        let response = ureq::get(&url)
+            .call()
+            .map_err(|e| eyre!("Cannot download the template {}: {}", url, e))?;
+        Ok(response.into_string()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_source() {
+        let source = MemorySource::new().add("scorecard.svg", "<svg>{{ city }}</svg>");
+        assert_eq!(
+            source.load("scorecard.svg").unwrap(),
+            "<svg>{{ city }}</svg>"
+        );
+        assert!(source.load("missing.svg").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_url() {
+        let source = UrlSource::new("https://example.org/templates/");
+        assert_eq!(
+            source.url("/scorecard.svg"),
+            "https://example.org/templates/scorecard.svg"
+        );
+        assert_eq!(
+            source.url("http://example.com/a.svg"),
+            "http://example.com/a.svg"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::notify::human_size;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use std::{io, path::Path, process::Command};
+
+/// Number of files written before the space needed by a run is estimated.
+pub const SAMPLE_SIZE: usize = 5;
+
+/// Margin of the estimates, as the files vary in size.
+const MARGIN: f64 = 1.2;
+
+/// Free space of the volume of a directory, in bytes, as reported by `df`.
+///
+/// The directory may not exist yet, in which case its closest existing
+/// ancestor is used. `None` is returned when the space cannot be read, e.g.
+/// when `df` is not available.
+pub fn available_space(dir: &Path) -> Option<u64> {
+    let dir = dir.ancestors().find(|dir| dir.is_dir()).unwrap_or(dir);
+    let dir = match dir.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => dir,
+    };
+    let output = Command::new("df").arg("-Pk").arg(dir).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_df(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Read the available space from the POSIX output of `df -Pk`.
+fn parse_df(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let kib: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Estimate the space needed by the files left, from the sizes of the files
+/// already written.
+///
+/// The exported files are assumed to be as large as the rendered ones.
+pub fn estimate(sample: &[u64], left: usize, exported: bool) -> u64 {
+    if sample.is_empty() {
+        return 0;
+    }
+    let mean = sample.iter().sum::<u64>() as f64 / sample.len() as f64;
+    let factor = if exported { 2.0 } else { 1.0 };
+    (mean * left as f64 * factor * MARGIN) as u64
+}
+
+/// Check that the volume of a directory has enough space for the files left.
+pub fn check_space(dir: &Path, sample: &[u64], left: usize, exported: bool) -> Result<(), Report> {
+    let Some(available) = available_space(dir) else {
+        return Ok(());
+    };
+    let needed = estimate(sample, left, exported);
+    if needed > available {
+        return Err(eyre!(
+            "The {} file(s) left need about {}, but only {} are available in {}",
+            left,
+            human_size(needed),
+            human_size(available),
+            dir.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check whether an error comes from a full volume.
+pub fn is_storage_full(error: &Report) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::StorageFull)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df() {
+        let output = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                      /dev/sda1        102400000  51200000  40960000      56% /\n";
+        assert_eq!(parse_df(output), Some(40960000 * 1024));
+        assert_eq!(parse_df(""), None);
+    }
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(estimate(&[], 10, false), 0);
+        assert_eq!(estimate(&[1000, 3000], 10, false), 24000);
+        assert_eq!(estimate(&[1000, 3000], 10, true), 48000);
+        let error = Report::from(io::Error::from(io::ErrorKind::StorageFull));
+        assert!(is_storage_full(&error.wrap_err("Cannot write austin.svg")));
+    }
+}
@@ -0,0 +1,1249 @@
+#[cfg(feature = "native")]
+use crate::map::mime_type;
+#[cfg(feature = "native")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::ValueEnum;
+#[cfg(feature = "native")]
+use color_eyre::eyre::WrapErr;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use minijinja::Value;
+use quick_xml::{
+    events::{BytesCData, BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+use std::{collections::BTreeMap, str::FromStr};
+#[cfg(feature = "native")]
+use std::{fs, io::BufRead, path::Path};
+
+/// Number of millimeters per CSS pixel.
+const MM_PER_PX: f64 = 25.4 / 96.0;
+
+/// A page size, in millimeters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl FromStr for PageSize {
+    type Err = String;
+
+    /// Parse a page size.
+    ///
+    /// The page size is either a known paper format (`letter`, `legal`,
+    /// `tabloid`, `a3`, `a4` or `a5`) or a `WIDTHxHEIGHT` size with an
+    /// optional unit, like `8.5x11in` or `800x600`. The unit defaults to
+    /// pixels.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = match s.to_lowercase().as_str() {
+            "letter" => (215.9, 279.4),
+            "legal" => (215.9, 355.6),
+            "tabloid" => (279.4, 431.8),
+            "a3" => (297.0, 420.0),
+            "a4" => (210.0, 297.0),
+            "a5" => (148.0, 210.0),
+            size => {
+                let invalid = || format!("invalid page size {:?}", s);
+                let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+                let unit = height.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                let width = parse_length(&format!("{}{}", width, unit)).ok_or_else(invalid)?;
+                let height = parse_length(height).ok_or_else(invalid)?;
+                (width, height)
+            }
+        };
+
+        Ok(Self { width, height })
+    }
+}
+
+/// How the content is fitted into a new page size.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum PageFit {
+    /// Scale the content to fit the page.
+    Scale,
+    /// Keep the size of the content and pad it with margins.
+    Pad,
+}
+
+/// Parse an SVG length, and convert it to millimeters.
+///
+/// Lengths without unit are in pixels. Relative lengths are not supported.
+fn parse_length(length: &str) -> Option<f64> {
+    let length = length.trim();
+    let number_end = length
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(length.len());
+    let value = length[..number_end].parse::<f64>().ok()?;
+    let factor = match &length[number_end..] {
+        "" | "px" => MM_PER_PX,
+        "mm" => 1.0,
+        "cm" => 10.0,
+        "in" => 25.4,
+        "pt" => 25.4 / 72.0,
+        "pc" => 25.4 / 6.0,
+        _ => return None,
+    };
+
+    Some(value * factor)
+}
+
+/// Format a number for an SVG attribute.
+fn format_number(value: f64) -> String {
+    ((value * 10000.0).round() / 10000.0).to_string()
+}
+
+/// Rewrite an SVG document event by event.
+///
+/// The `rewrite` closure receives each event and returns the events to write in
+/// its place. Returning an empty vector drops the event.
+fn rewrite<F>(svg: &str, mut rewrite: F) -> Result<String, Report>
+where
+    F: FnMut(Event) -> Result<Vec<Event>, Report>,
+{
+    let mut reader = Reader::from_str(svg);
+    reader.check_end_names(false);
+    let mut writer = Writer::new(Vec::with_capacity(svg.len()));
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            event => {
+                for event in rewrite(event)? {
+                    writer.write_event(event)?;
+                }
+            }
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+/// Show or hide the layers of an SVG document.
+///
+/// A group (`<g>`) whose `id` or `inkscape:label` matches the name of a boolean
+/// value of the context is shown if the value is true, and hidden otherwise.
+/// Recognized boolean values are `true`/`false`, `yes`/`no` and `1`/`0`, case
+/// insensitive. The other groups are left untouched.
+pub fn toggle_layers(svg: &str, context: &BTreeMap<String, Value>) -> Result<String, Report> {
+    rewrite(svg, |event| {
+        let event = match event {
+            Event::Start(e) if e.local_name().as_ref() == b"g" => {
+                Event::Start(toggle_group(e, context)?)
+            }
+            Event::Empty(e) if e.local_name().as_ref() == b"g" => {
+                Event::Empty(toggle_group(e, context)?)
+            }
+            event => event,
+        };
+        Ok(vec![event])
+    })
+}
+
+/// Show or hide a group based on the context.
+fn toggle_group<'a>(
+    group: BytesStart<'a>,
+    context: &BTreeMap<String, Value>,
+) -> Result<BytesStart<'a>, Report> {
+    // Look for a boolean context value matching the group id or label.
+    let mut visible = None;
+    for attr in group.attributes() {
+        let attr = attr?;
+        if matches!(attr.key.as_ref(), b"id" | b"inkscape:label") {
+            let name = attr.unescape_value()?;
+            visible = visible.or_else(|| context.get(name.as_ref()).and_then(as_bool));
+        }
+    }
+    let visible = match visible {
+        Some(visible) => visible,
+        None => return Ok(group),
+    };
+
+    // Rewrite the style attribute with the new display value.
+    let mut style = String::new();
+    let mut toggled = group.to_owned();
+    toggled.clear_attributes();
+    for attr in group.attributes() {
+        let attr = attr?;
+        if attr.key.as_ref() == b"style" {
+            style = attr.unescape_value()?.into_owned();
+        } else {
+            toggled.push_attribute(attr);
+        }
+    }
+    let display = if visible { "inline" } else { "none" };
+    toggled.push_attribute((
+        "style",
+        set_style_property(&style, "display", display).as_str(),
+    ));
+
+    Ok(toggled)
+}
+
+/// Check that an SVG document is well-formed.
+///
+/// The error reports the line and column of the first problem found.
+pub fn validate(svg: &str) -> Result<(), Report> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    match roxmltree::Document::parse_with_options(svg, options) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(eyre!(
+            "malformed SVG (line {}, column {}): {}",
+            e.pos().row,
+            e.pos().col,
+            e
+        )),
+    }
+}
+
+/// Check that an SVG document is well-formed while reading it, without
+/// holding it in memory, e.g. once streamed to a file.
+///
+/// The error reports the byte offset of the first problem found.
+#[cfg(feature = "native")]
+pub fn validate_reader(reader: impl BufRead) -> Result<(), Report> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    let mut depth: usize = 0;
+    let mut roots = 0;
+    // The entities defined by a DTD are not known.
+    let mut entities = false;
+    loop {
+        let event = reader.read_event_into(&mut buf);
+        if let Ok(Event::Start(_) | Event::Empty(_)) = &event {
+            if depth == 0 {
+                roots += 1;
+            }
+        }
+        let problem = match event {
+            Err(e) => Some(e.to_string()),
+            Ok(Event::Start(_) | Event::Empty(_)) if roots > 1 => {
+                Some(String::from("several root elements"))
+            }
+            Ok(Event::Start(e)) => {
+                depth += 1;
+                attributes_problem(&e, entities)
+            }
+            Ok(Event::Empty(e)) => attributes_problem(&e, entities),
+            Ok(Event::End(_)) => {
+                depth = depth.saturating_sub(1);
+                None
+            }
+            Ok(Event::Text(e)) if depth == 0 => match e.iter().all(u8::is_ascii_whitespace) {
+                true => None,
+                false => Some(String::from("text outside of the root element")),
+            },
+            Ok(Event::Text(e)) if !entities => e.unescape().err().map(|e| e.to_string()),
+            Ok(Event::DocType(_)) => {
+                entities = true;
+                None
+            }
+            Ok(Event::Eof) if depth > 0 => Some(String::from("unclosed element")),
+            Ok(Event::Eof) if roots == 0 => Some(String::from("no root element")),
+            Ok(Event::Eof) => return Ok(()),
+            Ok(_) => None,
+        };
+        if let Some(problem) = problem {
+            return Err(eyre!(
+                "malformed SVG (byte {}): {}",
+                reader.buffer_position(),
+                problem
+            ));
+        }
+        buf.clear();
+    }
+}
+
+/// Find the first malformed or duplicated attribute of an element, or the
+/// first unknown entity of their values.
+#[cfg(feature = "native")]
+fn attributes_problem(element: &BytesStart, entities: bool) -> Option<String> {
+    element.attributes().find_map(|attr| match attr {
+        Ok(attr) if !entities => attr.unescape_value().err().map(|e| e.to_string()),
+        Ok(_) => None,
+        Err(e) => Some(e.to_string()),
+    })
+}
+
+/// Inject a stylesheet into an SVG document.
+///
+/// The stylesheet is inserted as a `<style>` element at the end of the root
+/// element, so that it takes precedence over the stylesheets of the template.
+/// A self-closing root element is expanded to hold it.
+pub fn inject_css(svg: &str, css: &str) -> Result<String, Report> {
+    // A CDATA section ends at the first `]]>`, which is split across two
+    // sections.
+    let style = |end: Event<'static>| {
+        vec![
+            Event::Start(BytesStart::new("style")),
+            Event::CData(BytesCData::new(css.replace("]]>", "]]]]><![CDATA[>"))),
+            Event::End(BytesEnd::new("style")),
+            end,
+        ]
+    };
+    let mut depth = 0;
+    rewrite(svg, |event| {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::Empty(e) if depth == 0 => {
+                let name = String::from_utf8(e.name().as_ref().to_vec())?;
+                let mut events = vec![Event::Start(e)];
+                events.extend(style(Event::End(BytesEnd::new(name))));
+                return Ok(events);
+            }
+            Event::End(_) if depth == 1 => {
+                depth -= 1;
+                return Ok(style(event.into_owned()));
+            }
+            Event::End(_) => depth -= 1,
+            _ => (),
+        };
+        Ok(vec![event])
+    })
+}
+
+/// Append elements at the end of the root element of an SVG document.
+///
+/// The `elements` closure receives the `viewBox` of the root element and
+/// returns the events of the elements to append. A self-closing root element
+/// is expanded to hold them.
+fn append<F>(svg: &str, elements: F) -> Result<String, Report>
+where
+    F: Fn([f64; 4]) -> Result<Vec<Event<'static>>, Report>,
+{
+    let mut depth = 0;
+    let mut view_box = None;
+    rewrite(svg, |event| {
+        match &event {
+            Event::Start(e) => {
+                if depth == 0 {
+                    view_box = Some(dimensions(e)?.2);
+                }
+                depth += 1;
+            }
+            Event::Empty(e) if depth == 0 => {
+                let name = String::from_utf8(e.name().as_ref().to_vec())?;
+                let mut events = elements(dimensions(e)?.2)?;
+                events.insert(0, Event::Start(e.clone()));
+                events.push(Event::End(BytesEnd::new(name)));
+                return Ok(events);
+            }
+            Event::End(_) if depth == 1 => {
+                depth -= 1;
+                let mut events = match view_box {
+                    Some(view_box) => elements(view_box)?,
+                    None => Vec::new(),
+                };
+                events.push(event);
+                return Ok(events);
+            }
+            Event::End(_) => depth -= 1,
+            _ => (),
+        };
+        Ok(vec![event])
+    })
+}
+
+/// Stamp a watermark text diagonally across an SVG document.
+///
+/// The text is drawn in translucent gray over the content, sized to span most
+/// of the diagonal of the `viewBox`.
+pub fn watermark(svg: &str, text: &str) -> Result<String, Report> {
+    append(svg, |[x, y, width, height]| {
+        let (cx, cy) = (x + width / 2.0, y + height / 2.0);
+        let angle = -(height / width).atan().to_degrees();
+        let length = text.chars().count().max(1) as f64;
+        let font_size = (width.hypot(height) * 0.8 / (0.6 * length)).min(width.min(height) / 3.0);
+        let transform = format!(
+            "rotate({} {} {})",
+            format_number(angle),
+            format_number(cx),
+            format_number(cy)
+        );
+        let element = BytesStart::new("text").with_attributes([
+            ("x", format_number(cx).as_str()),
+            ("y", format_number(cy).as_str()),
+            ("dy", "0.35em"),
+            ("transform", transform.as_str()),
+            ("text-anchor", "middle"),
+            ("font-family", "sans-serif"),
+            ("font-weight", "bold"),
+            ("font-size", format_number(font_size).as_str()),
+            ("fill", "#808080"),
+            ("fill-opacity", "0.3"),
+        ]);
+        Ok(vec![
+            Event::Start(element),
+            Event::Text(BytesText::new(text).into_owned()),
+            Event::End(BytesEnd::new("text")),
+        ])
+    })
+}
+
+/// Convert the lines of a text into `<tspan>` elements, so that the multiline
+/// cells render on several lines.
+///
+/// Each line starts at `x` when specified, and `dy` below the previous one. The
+/// first line stays on the baseline of the parent `<text>` element, and the
+/// blank lines hold a non-breaking space, which carries their offset.
+pub fn lines(text: &str, x: Option<&str>, dy: &str) -> String {
+    let mut writer = Writer::new(Vec::new());
+    for (i, line) in text.lines().enumerate() {
+        let mut tspan = BytesStart::new("tspan");
+        if let Some(x) = x {
+            tspan.push_attribute(("x", x));
+        }
+        tspan.push_attribute(("dy", if i == 0 { "0" } else { dy }));
+        let line = if line.trim().is_empty() {
+            "\u{a0}"
+        } else {
+            line
+        };
+        // Writing into a vector cannot fail.
+        let _ = writer.write_event(Event::Start(tspan));
+        let _ = writer.write_event(Event::Text(BytesText::new(line)));
+        let _ = writer.write_event(Event::End(BytesEnd::new("tspan")));
+    }
+
+    String::from_utf8(writer.into_inner()).expect("the input is valid UTF-8")
+}
+
+/// Lay out a table as an SVG fragment: a header row, then a row per record,
+/// separated by rule lines, starting at the origin of the parent element.
+///
+/// Each cell is a `<text>` element at the baseline of its row, aligned to the
+/// left of its column, or to the right in the columns of numbers. The elements
+/// have the `svggloo-table-header`, `svggloo-table-cell` and
+/// `svggloo-table-rule` classes, to be styled by the template.
+pub fn table(header: &[String], rows: &[Vec<String>], widths: &[f64], row_height: f64) -> String {
+    const PADDING: f64 = 4.0;
+    let width: f64 = widths.iter().sum();
+    let numeric: Vec<bool> = (0..header.len())
+        .map(|column| {
+            let mut values = rows
+                .iter()
+                .filter_map(|row| row.get(column))
+                .filter(|value| !value.trim().is_empty())
+                .peekable();
+            values.peek().is_some() && values.all(|value| value.trim().parse::<f64>().is_ok())
+        })
+        .collect();
+
+    let mut writer = Writer::new(Vec::new());
+    let mut text = |class: &str, y: f64, column: usize, value: &str| {
+        let left: f64 = widths[..column].iter().sum();
+        let (x, anchor) = match numeric[column] {
+            true => (left + widths[column] - PADDING, "end"),
+            false => (left + PADDING, "start"),
+        };
+        let mut element = BytesStart::new("text");
+        element.push_attribute(("class", class));
+        element.push_attribute(("x", format_number(x).as_str()));
+        element.push_attribute(("y", format_number(y).as_str()));
+        element.push_attribute(("text-anchor", anchor));
+        if class == "svggloo-table-header" {
+            element.push_attribute(("font-weight", "bold"));
+        }
+        // Writing into a vector cannot fail.
+        let _ = writer.write_event(Event::Start(element));
+        let _ = writer.write_event(Event::Text(BytesText::new(value)));
+        let _ = writer.write_event(Event::End(BytesEnd::new("text")));
+    };
+    // The baselines leave room for the descenders above the rules.
+    let baseline = |row: usize| (row + 1) as f64 * row_height - row_height * 0.3;
+    for (column, label) in header.iter().enumerate() {
+        text("svggloo-table-header", baseline(0), column, label);
+    }
+    for (i, row) in rows.iter().enumerate() {
+        for (column, value) in row.iter().enumerate().take(header.len()) {
+            text("svggloo-table-cell", baseline(i + 1), column, value);
+        }
+    }
+
+    for row in 1..=rows.len() {
+        let y = format_number(row as f64 * row_height);
+        let mut rule = BytesStart::new("line");
+        rule.push_attribute(("class", "svggloo-table-rule"));
+        rule.push_attribute(("x1", "0"));
+        rule.push_attribute(("y1", y.as_str()));
+        rule.push_attribute(("x2", format_number(width).as_str()));
+        rule.push_attribute(("y2", y.as_str()));
+        rule.push_attribute(("stroke", "currentColor"));
+        // The rule under the header is bolder than the ones between the rows.
+        rule.push_attribute(("stroke-width", if row == 1 { "1" } else { "0.5" }));
+        let _ = writer.write_event(Event::Empty(rule));
+    }
+
+    String::from_utf8(writer.into_inner()).expect("the input is valid UTF-8")
+}
+
+/// Lay out a color legend as an SVG fragment: a swatch of each color, of a
+/// size of `swatch`, followed by its label, one below the other.
+pub fn legend(entries: &[(String, String)], swatch: f64) -> String {
+    let mut writer = Writer::new(Vec::new());
+    let step = swatch * 1.5;
+    for (i, (color, label)) in entries.iter().enumerate() {
+        let top = i as f64 * step;
+        let mut rect = BytesStart::new("rect");
+        rect.push_attribute(("class", "svggloo-legend-swatch"));
+        rect.push_attribute(("x", "0"));
+        rect.push_attribute(("y", format_number(top).as_str()));
+        rect.push_attribute(("width", format_number(swatch).as_str()));
+        rect.push_attribute(("height", format_number(swatch).as_str()));
+        rect.push_attribute(("fill", color.as_str()));
+        // Writing into a vector cannot fail.
+        let _ = writer.write_event(Event::Empty(rect));
+
+        let mut text = BytesStart::new("text");
+        text.push_attribute(("class", "svggloo-legend-label"));
+        text.push_attribute(("x", format_number(step).as_str()));
+        text.push_attribute(("y", format_number(top + swatch * 0.8).as_str()));
+        let _ = writer.write_event(Event::Start(text));
+        let _ = writer.write_event(Event::Text(BytesText::new(label)));
+        let _ = writer.write_event(Event::End(BytesEnd::new("text")));
+    }
+
+    String::from_utf8(writer.into_inner()).expect("the input is valid UTF-8")
+}
+
+/// Draw the modules of a barcode as an SVG fragment of bars, `module` wide per
+/// module and `height` high.
+pub fn barcode(modules: &[bool], module: f64, height: f64) -> String {
+    let mut writer = Writer::new(Vec::new());
+    let mut start = 0;
+    while start < modules.len() {
+        let end = modules[start..]
+            .iter()
+            .position(|dark| *dark != modules[start])
+            .map_or(modules.len(), |length| start + length);
+        if modules[start] {
+            let mut rect = BytesStart::new("rect");
+            rect.push_attribute(("class", "svggloo-barcode"));
+            rect.push_attribute(("x", format_number(start as f64 * module).as_str()));
+            rect.push_attribute(("y", "0"));
+            rect.push_attribute((
+                "width",
+                format_number((end - start) as f64 * module).as_str(),
+            ));
+            rect.push_attribute(("height", format_number(height).as_str()));
+            rect.push_attribute(("fill", "currentColor"));
+            // Writing into a vector cannot fail.
+            let _ = writer.write_event(Event::Empty(rect));
+        }
+        start = end;
+    }
+
+    String::from_utf8(writer.into_inner()).expect("the input is valid UTF-8")
+}
+
+/// Composite an overlay SVG document over an SVG document.
+///
+/// The overlay is nested at the end of the document, stretched over its
+/// `viewBox`. The overlay keeps its own `viewBox` and aspect ratio.
+pub fn overlay(svg: &str, overlay: &str) -> Result<String, Report> {
+    // Read the overlay, without its prolog.
+    let mut reader = Reader::from_str(overlay);
+    reader.check_end_names(false);
+    let mut events = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Eof => break,
+            Event::Decl(_) | Event::DocType(_) | Event::PI(_) => (),
+            Event::Comment(_) | Event::Text(_) if events.is_empty() => (),
+            event => events.push(event.into_owned()),
+        }
+    }
+    if !matches!(events.first(), Some(Event::Start(e) | Event::Empty(e)) if e.local_name().as_ref() == b"svg")
+    {
+        return Err(eyre!("the overlay is not an SVG document"));
+    }
+
+    append(svg, |[x, y, width, height]| {
+        let mut events = events.clone();
+        let root = match &events[0] {
+            Event::Start(e) | Event::Empty(e) => e,
+            _ => unreachable!("the overlay root is checked beforehand"),
+        };
+        let mut nested = root.to_owned();
+        nested.clear_attributes();
+        for attr in root.attributes() {
+            let attr = attr?;
+            if !matches!(attr.key.as_ref(), b"x" | b"y" | b"width" | b"height") {
+                nested.push_attribute(attr);
+            }
+        }
+        nested.push_attribute(("x", format_number(x).as_str()));
+        nested.push_attribute(("y", format_number(y).as_str()));
+        nested.push_attribute(("width", format_number(width).as_str()));
+        nested.push_attribute(("height", format_number(height).as_str()));
+        events[0] = match events[0] {
+            Event::Empty(_) => Event::Empty(nested),
+            _ => Event::Start(nested),
+        };
+        Ok(events)
+    })
+}
+
+/// Annotate an SVG document for the assistive technologies.
+///
+/// The `title` replaces the `<title>` of the root element, which is then
+/// exposed as an image. The `lang` sets the language of the document. The
+/// images whose `id` is a key of `alts` get its value as their alternative
+/// text.
+pub fn annotate(
+    svg: &str,
+    title: Option<&str>,
+    lang: Option<&str>,
+    alts: &BTreeMap<String, String>,
+) -> Result<String, Report> {
+    let title_events = |text: &str| {
+        vec![
+            Event::Start(BytesStart::new("title")),
+            Event::Text(BytesText::new(text).into_owned()),
+            Event::End(BytesEnd::new("title")),
+        ]
+    };
+    let mut depth = 0;
+    let mut skipped_depth = None;
+    rewrite(svg, |event| {
+        // Drop the previous title of the root element.
+        if let Some(skipped) = skipped_depth {
+            match &event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => {
+                    depth -= 1;
+                    if depth == skipped {
+                        skipped_depth = None;
+                    }
+                }
+                _ => (),
+            }
+            return Ok(Vec::new());
+        }
+
+        let events = match event {
+            Event::Start(e) if depth == 0 => {
+                depth += 1;
+                let mut events = vec![Event::Start(annotate_root(e, title, lang)?)];
+                events.extend(title.map(title_events).unwrap_or_default());
+                events
+            }
+            Event::Start(e) if depth == 1 && title.is_some() && e.name().as_ref() == b"title" => {
+                skipped_depth = Some(depth);
+                depth += 1;
+                Vec::new()
+            }
+            Event::Start(e) if e.local_name().as_ref() == b"image" => {
+                depth += 1;
+                match alt(&e, alts)? {
+                    Some(alt) => {
+                        let mut events = vec![Event::Start(annotate_image(e, alt)?)];
+                        events.extend(title_events(alt));
+                        events
+                    }
+                    None => vec![Event::Start(e)],
+                }
+            }
+            Event::Empty(e) if e.local_name().as_ref() == b"image" => match alt(&e, alts)? {
+                Some(alt) => {
+                    let image = annotate_image(e, alt)?;
+                    let end = BytesEnd::new(String::from_utf8(image.name().as_ref().to_vec())?);
+                    let mut events = vec![Event::Start(image)];
+                    events.extend(title_events(alt));
+                    events.push(Event::End(end));
+                    events
+                }
+                None => vec![Event::Empty(e)],
+            },
+            Event::Start(e) => {
+                depth += 1;
+                vec![Event::Start(e)]
+            }
+            Event::End(e) => {
+                depth -= 1;
+                vec![Event::End(e)]
+            }
+            event => vec![event],
+        };
+        Ok(events)
+    })
+}
+
+/// Set the language and the role of the root element of an SVG document.
+fn annotate_root<'a>(
+    root: BytesStart<'a>,
+    title: Option<&str>,
+    lang: Option<&str>,
+) -> Result<BytesStart<'a>, Report> {
+    let mut annotated = root.to_owned();
+    annotated.clear_attributes();
+    for attr in root.attributes() {
+        let attr = attr?;
+        let replaced = match attr.key.as_ref() {
+            b"lang" | b"xml:lang" => lang.is_some(),
+            b"role" => title.is_some(),
+            _ => false,
+        };
+        if !replaced {
+            annotated.push_attribute(attr);
+        }
+    }
+    if let Some(lang) = lang {
+        annotated.push_attribute(("xml:lang", lang));
+        annotated.push_attribute(("lang", lang));
+    }
+    if title.is_some() {
+        annotated.push_attribute(("role", "img"));
+    }
+
+    Ok(annotated)
+}
+
+/// Find the alternative text of an image, by its `id`.
+fn alt<'a>(
+    image: &BytesStart,
+    alts: &'a BTreeMap<String, String>,
+) -> Result<Option<&'a str>, Report> {
+    for attr in image.attributes() {
+        let attr = attr?;
+        if attr.key.as_ref() == b"id" {
+            return Ok(alts
+                .get(attr.unescape_value()?.as_ref())
+                .map(String::as_str));
+        }
+    }
+    Ok(None)
+}
+
+/// Expose an image with an alternative text.
+fn annotate_image<'a>(image: BytesStart<'a>, alt: &str) -> Result<BytesStart<'a>, Report> {
+    let mut annotated = image.to_owned();
+    annotated.clear_attributes();
+    for attr in image.attributes() {
+        let attr = attr?;
+        if !matches!(attr.key.as_ref(), b"role" | b"aria-label") {
+            annotated.push_attribute(attr);
+        }
+    }
+    annotated.push_attribute(("role", "img"));
+    annotated.push_attribute(("aria-label", alt));
+
+    Ok(annotated)
+}
+
+/// Embed the linked images of an SVG document.
+///
+/// The images referenced by a relative or `file://` link are resolved against
+/// `base_dir`, and replaced by data URIs, so that the document is
+/// self-contained. Remote images and data URIs are left untouched.
+#[cfg(feature = "native")]
+pub fn embed_images(svg: &str, base_dir: &Path) -> Result<String, Report> {
+    rewrite(svg, |event| {
+        let event = match event {
+            Event::Start(e) if e.local_name().as_ref() == b"image" => {
+                Event::Start(embed_image(e, base_dir)?)
+            }
+            Event::Empty(e) if e.local_name().as_ref() == b"image" => {
+                Event::Empty(embed_image(e, base_dir)?)
+            }
+            event => event,
+        };
+        Ok(vec![event])
+    })
+}
+
+/// Replace the link of an `<image>` element by a data URI.
+#[cfg(feature = "native")]
+fn embed_image<'a>(image: BytesStart<'a>, base_dir: &Path) -> Result<BytesStart<'a>, Report> {
+    let mut embedded = image.to_owned();
+    embedded.clear_attributes();
+    for attr in image.attributes() {
+        let attr = attr?;
+        if !matches!(attr.key.as_ref(), b"href" | b"xlink:href") {
+            embedded.push_attribute(attr);
+            continue;
+        }
+        let href = attr.unescape_value()?;
+        if href.starts_with("data:") || href.contains("://") && !href.starts_with("file://") {
+            embedded.push_attribute(attr);
+            continue;
+        }
+        let path = base_dir.join(href.trim_start_matches("file://"));
+        let bytes = fs::read(&path)
+            .wrap_err_with(|| format!("Cannot embed the linked image {}", path.display()))?;
+        let mime = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("svg") => "image/svg+xml",
+            _ => mime_type(&bytes),
+        };
+        let uri = format!("data:{};base64,{}", mime, STANDARD.encode(bytes));
+        embedded.push_attribute((
+            String::from_utf8_lossy(attr.key.as_ref()).as_ref(),
+            uri.as_str(),
+        ));
+    }
+
+    Ok(embedded)
+}
+
+/// Resize the canvas of an SVG document.
+///
+/// The `width` and `height` of the root element are set to the `page` size,
+/// and multiplied by the `scale` factor. With [`PageFit::Scale`], the content
+/// is scaled to fit the new canvas, centered. With [`PageFit::Pad`], the
+/// content keeps its size (multiplied by the `scale` factor) and is centered on
+/// the page by enlarging the `viewBox`.
+pub fn resize(
+    svg: &str,
+    page: Option<PageSize>,
+    fit: PageFit,
+    scale: Option<f64>,
+) -> Result<String, Report> {
+    rewrite_root(svg, |root| {
+        let (width, height, [x, y, vb_width, vb_height]) = dimensions(&root)?;
+
+        // Compute the new dimensions.
+        let scale = scale.unwrap_or(1.0);
+        let page = page.unwrap_or(PageSize { width, height });
+        match fit {
+            PageFit::Scale => set_dimensions(
+                root,
+                page.width * scale,
+                page.height * scale,
+                [x, y, vb_width, vb_height],
+            ),
+            PageFit::Pad => {
+                // Keep the ratio of user units per millimeter of the scaled content.
+                let padded_width = page.width * vb_width / (width * scale);
+                let padded_height = page.height * vb_height / (height * scale);
+                let x = x - (padded_width - vb_width) / 2.0;
+                let y = y - (padded_height - vb_height) / 2.0;
+                set_dimensions(
+                    root,
+                    page.width,
+                    page.height,
+                    [x, y, padded_width, padded_height],
+                )
+            }
+        }
+    })
+}
+
+/// Crop the canvas of an SVG document to an area.
+///
+/// The area is expressed in user units. The physical size of the content is
+/// preserved.
+pub fn crop(svg: &str, x: f64, y: f64, width: f64, height: f64) -> Result<String, Report> {
+    rewrite_root(svg, |root| {
+        let (root_width, root_height, [_, _, vb_width, vb_height]) = dimensions(&root)?;
+        set_dimensions(
+            root,
+            width * root_width / vb_width,
+            height * root_height / vb_height,
+            [x, y, width, height],
+        )
+    })
+}
+
+/// Rewrite the root element of an SVG document.
+fn rewrite_root<F>(svg: &str, mut rewrite_root: F) -> Result<String, Report>
+where
+    F: FnMut(BytesStart) -> Result<BytesStart, Report>,
+{
+    let mut done = false;
+    rewrite(svg, |event| {
+        let event = match event {
+            Event::Start(e) if !done && e.local_name().as_ref() == b"svg" => {
+                done = true;
+                Event::Start(rewrite_root(e)?)
+            }
+            Event::Empty(e) if !done && e.local_name().as_ref() == b"svg" => {
+                done = true;
+                Event::Empty(rewrite_root(e)?)
+            }
+            event => event,
+        };
+        Ok(vec![event])
+    })
+}
+
+/// Get the dimensions of the root element of an SVG document.
+///
+/// Returns the width and height in millimeters, and the `viewBox`. Missing
+/// values are derived from each other, assuming the user units are pixels.
+fn dimensions(root: &BytesStart) -> Result<(f64, f64, [f64; 4]), Report> {
+    let mut width = None;
+    let mut height = None;
+    let mut view_box = None;
+    for attr in root.attributes() {
+        let attr = attr?;
+        let value = attr.unescape_value()?;
+        match attr.key.as_ref() {
+            b"width" => width = parse_length(&value),
+            b"height" => height = parse_length(&value),
+            b"viewBox" => {
+                view_box = value
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .ok()
+                    .and_then(|v| <[f64; 4]>::try_from(v).ok())
+            }
+            _ => (),
+        }
+    }
+    let view_box = match (view_box, width, height) {
+        (Some(view_box), _, _) => view_box,
+        (None, Some(width), Some(height)) => [0.0, 0.0, width / MM_PER_PX, height / MM_PER_PX],
+        _ => return Err(eyre!("the SVG root has no usable dimensions")),
+    };
+    let width = width.unwrap_or(view_box[2] * MM_PER_PX);
+    let height = height.unwrap_or(view_box[3] * MM_PER_PX);
+
+    Ok((width, height, view_box))
+}
+
+/// Set the dimensions of the root element of an SVG document.
+fn set_dimensions<'a>(
+    root: BytesStart<'a>,
+    width: f64,
+    height: f64,
+    view_box: [f64; 4],
+) -> Result<BytesStart<'a>, Report> {
+    let mut resized = root.to_owned();
+    resized.clear_attributes();
+    for attr in root.attributes() {
+        let attr = attr?;
+        if !matches!(
+            attr.key.as_ref(),
+            b"width" | b"height" | b"viewBox" | b"preserveAspectRatio"
+        ) {
+            resized.push_attribute(attr);
+        }
+    }
+    let view_box = view_box.map(format_number).join(" ");
+    resized.push_attribute(("width", format!("{}mm", format_number(width)).as_str()));
+    resized.push_attribute(("height", format!("{}mm", format_number(height)).as_str()));
+    resized.push_attribute(("viewBox", view_box.as_str()));
+    resized.push_attribute(("preserveAspectRatio", "xMidYMid meet"));
+
+    Ok(resized)
+}
+
+/// Namespace prefixes of the SVG editors.
+const EDITOR_PREFIXES: [&[u8]; 2] = [b"inkscape", b"sodipodi"];
+
+/// Strip the editor specific metadata from an SVG document.
+///
+/// Removes the elements and attributes of the Inkscape and Sodipodi namespaces
+/// (e.g. `sodipodi:namedview` and its grid settings), their namespace
+/// declarations, and the `<metadata>` element of the document.
+pub fn strip_metadata(svg: &str) -> Result<String, Report> {
+    let mut skipped_depth = 0;
+    rewrite(svg, |event| {
+        let event = match event {
+            _ if skipped_depth > 0 => {
+                match event {
+                    Event::Start(_) => skipped_depth += 1,
+                    Event::End(_) => skipped_depth -= 1,
+                    _ => (),
+                }
+                return Ok(vec![]);
+            }
+            Event::Start(e) if is_editor_element(&e) => {
+                skipped_depth = 1;
+                return Ok(vec![]);
+            }
+            Event::Empty(e) if is_editor_element(&e) => return Ok(vec![]),
+            Event::Start(e) => Event::Start(strip_editor_attributes(e)?),
+            Event::Empty(e) => Event::Empty(strip_editor_attributes(e)?),
+            event => event,
+        };
+        Ok(vec![event])
+    })
+}
+
+/// Check whether a name belongs to an editor namespace.
+fn is_editor_name(name: &[u8]) -> bool {
+    let prefix = match name.strip_prefix(b"xmlns:") {
+        Some(prefix) => prefix,
+        None => name.split(|&b| b == b':').next().unwrap_or_default(),
+    };
+    name.contains(&b':') && EDITOR_PREFIXES.contains(&prefix)
+}
+
+/// Check whether an element is editor metadata.
+fn is_editor_element(element: &BytesStart) -> bool {
+    element.name().as_ref() == b"metadata" || is_editor_name(element.name().as_ref())
+}
+
+/// Strip the editor specific attributes of an element.
+fn strip_editor_attributes(element: BytesStart) -> Result<BytesStart, Report> {
+    let mut stripped = element.to_owned();
+    stripped.clear_attributes();
+    for attr in element.attributes() {
+        let attr = attr?;
+        if !is_editor_name(attr.key.as_ref()) {
+            stripped.push_attribute(attr);
+        }
+    }
+
+    Ok(stripped)
+}
+
+/// Interpret a context value as a boolean.
+fn as_bool(value: &Value) -> Option<bool> {
+    match value.to_string().trim().to_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Set a property in an inline CSS style declaration.
+fn set_style_property(style: &str, property: &str, value: &str) -> String {
+    let mut declarations = style
+        .split(';')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .filter(|d| d.split(':').next().map(str::trim) != Some(property))
+        .map(String::from)
+        .collect::<Vec<String>>();
+    declarations.push(format!("{}:{}", property, value));
+
+    declarations.join(";")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_layers() {
+        let svg = r#"<svg><g id="badge" style="display:inline;opacity:1"><text>Top 10</text></g><g inkscape:label="new"/><g id="city"/></svg>"#;
+        let context = BTreeMap::from([
+            (String::from("badge"), Value::from("false")),
+            (String::from("new"), Value::from("Yes")),
+            (String::from("city"), Value::from("Austin")),
+        ]);
+        assert_eq!(
+            toggle_layers(svg, &context).unwrap(),
+            r#"<svg><g id="badge" style="opacity:1;display:none"><text>Top 10</text></g><g inkscape:label="new" style="display:inline"/><g id="city"/></svg>"#
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        assert_eq!(
+            lines("Main St & 5th\r\n\nAustin", Some("10"), "1.2em"),
+            "<tspan x=\"10\" dy=\"0\">Main St &amp; 5th</tspan>\
+             <tspan x=\"10\" dy=\"1.2em\">\u{a0}</tspan>\
+             <tspan x=\"10\" dy=\"1.2em\">Austin</tspan>"
+        );
+        assert_eq!(
+            lines("Austin", None, "1em"),
+            "<tspan dy=\"0\">Austin</tspan>"
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let header = [String::from("Project"), String::from("Miles")];
+        let rows = [
+            vec![String::from("Lanes & tracks"), String::from("12.5")],
+            vec![String::from("Bridge"), String::new()],
+        ];
+        let table = table(&header, &rows, &[100.0, 50.0], 20.0);
+        assert!(table.starts_with(
+            "<text class=\"svggloo-table-header\" x=\"4\" y=\"14\" text-anchor=\"start\" \
+             font-weight=\"bold\">Project</text>"
+        ));
+        assert!(table.contains(
+            "<text class=\"svggloo-table-cell\" x=\"146\" y=\"34\" text-anchor=\"end\">12.5</text>"
+        ));
+        assert!(table.contains(">Lanes &amp; tracks</text>"));
+        assert_eq!(table.matches("<line ").count(), 2);
+        assert!(table.ends_with(
+            "<line class=\"svggloo-table-rule\" x1=\"0\" y1=\"40\" x2=\"150\" y2=\"40\" \
+             stroke=\"currentColor\" stroke-width=\"0.5\"/>"
+        ));
+    }
+
+    #[test]
+    fn test_legend() {
+        let entries = [
+            (String::from("#d73027"), String::from("Low")),
+            (String::from("#1a9850"), String::from("High & safe")),
+        ];
+        assert_eq!(
+            legend(&entries, 10.0),
+            "<rect class=\"svggloo-legend-swatch\" x=\"0\" y=\"0\" width=\"10\" height=\"10\" \
+             fill=\"#d73027\"/><text class=\"svggloo-legend-label\" x=\"15\" y=\"8\">Low</text>\
+             <rect class=\"svggloo-legend-swatch\" x=\"0\" y=\"15\" width=\"10\" height=\"10\" \
+             fill=\"#1a9850\"/><text class=\"svggloo-legend-label\" x=\"15\" y=\"23\">\
+             High &amp; safe</text>"
+        );
+    }
+
+    #[test]
+    fn test_barcode() {
+        assert_eq!(
+            barcode(&[true, true, false, true], 0.5, 30.0),
+            "<rect class=\"svggloo-barcode\" x=\"0\" y=\"0\" width=\"1\" height=\"30\" \
+             fill=\"currentColor\"/><rect class=\"svggloo-barcode\" x=\"1.5\" y=\"0\" \
+             width=\"0.5\" height=\"30\" fill=\"currentColor\"/>"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(r#"<svg><text>A &amp; B</text></svg>"#).is_ok());
+        let e = validate("<svg>\n<text>A & B</text></svg>").unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("malformed SVG (line 2, column 9)"));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_validate_reader() {
+        let valid = r#"<?xml version="1.0"?><svg><text>A &amp; B</text><g/></svg>"#;
+        assert!(validate_reader(valid.as_bytes()).is_ok());
+        for svg in [
+            "<svg><text>A & B</text></svg>",
+            "<svg><text></svg>",
+            "<svg><g>",
+            "<svg/><svg/>",
+            "<svg a=\"1\" a=\"2\"/>",
+            "",
+        ] {
+            let e = validate_reader(svg.as_bytes()).unwrap_err();
+            assert!(e.to_string().starts_with("malformed SVG (byte "), "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_inject_css() {
+        let svg = r#"<svg><g><text class="title">Austin</text></g></svg>"#;
+        assert_eq!(
+            inject_css(svg, ".title { fill: #e03; }").unwrap(),
+            r#"<svg><g><text class="title">Austin</text></g><style><![CDATA[.title { fill: #e03; }]]></style></svg>"#
+        );
+        assert_eq!(
+            inject_css(r#"<svg viewBox="0 0 10 10"/>"#, "rect { fill: red; }").unwrap(),
+            r#"<svg viewBox="0 0 10 10"><style><![CDATA[rect { fill: red; }]]></style></svg>"#
+        );
+        let css = r#"text::after { content: "]]>"; }"#;
+        let injected = inject_css(svg, css).unwrap();
+        assert!(injected.contains(r#"content: "]]]]><![CDATA[>"; }]]></style></svg>"#));
+        validate(&injected).unwrap();
+    }
+
+    #[test]
+    fn test_watermark() {
+        let svg = r#"<svg viewBox="0 0 200 100"><rect/></svg>"#;
+        assert_eq!(
+            watermark(svg, "DRAFT & co").unwrap(),
+            r##"<svg viewBox="0 0 200 100"><rect/><text x="100" y="50" dy="0.35em" transform="rotate(-26.5651 100 50)" text-anchor="middle" font-family="sans-serif" font-weight="bold" font-size="29.8142" fill="#808080" fill-opacity="0.3">DRAFT &amp; co</text></svg>"##
+        );
+        assert_eq!(
+            watermark(r#"<svg viewBox="0 0 200 100"/>"#, "DRAFT").unwrap(),
+            r##"<svg viewBox="0 0 200 100"><text x="100" y="50" dy="0.35em" transform="rotate(-26.5651 100 50)" text-anchor="middle" font-family="sans-serif" font-weight="bold" font-size="33.3333" fill="#808080" fill-opacity="0.3">DRAFT</text></svg>"##
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        let svg = r#"<svg lang="fr"><title>Template</title><image id="logo" href="a.png"/><image href="b.png"/></svg>"#;
+        let alts = BTreeMap::from([(String::from("logo"), String::from("PeopleForBikes logo"))]);
+        assert_eq!(
+            annotate(svg, Some("Austin"), Some("en"), &alts).unwrap(),
+            r#"<svg xml:lang="en" lang="en" role="img"><title>Austin</title><image id="logo" href="a.png" role="img" aria-label="PeopleForBikes logo"><title>PeopleForBikes logo</title></image><image href="b.png"/></svg>"#
+        );
+    }
+
+    #[test]
+    fn test_overlay() {
+        let svg = r#"<svg viewBox="10 10 200 100"><rect/></svg>"#;
+        let stamp = r#"<?xml version="1.0"?>
+<svg width="20mm" viewBox="0 0 20 10"><circle r="5"/></svg>"#;
+        assert_eq!(
+            overlay(svg, stamp).unwrap(),
+            r#"<svg viewBox="10 10 200 100"><rect/><svg viewBox="0 0 20 10" x="10" y="10" width="200" height="100"><circle r="5"/></svg></svg>"#
+        );
+        assert_eq!(
+            overlay(r#"<svg viewBox="10 10 200 100"/>"#, stamp).unwrap(),
+            r#"<svg viewBox="10 10 200 100"><svg viewBox="0 0 20 10" x="10" y="10" width="200" height="100"><circle r="5"/></svg></svg>"#
+        );
+        assert!(overlay(svg, "<g/>").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_embed_images() {
+        let dir = std::env::temp_dir().join("svggloo-test-embed-images");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("logo.png"), b"\x89PNG").unwrap();
+        let svg = r#"<svg><image xlink:href="logo.png" width="10"/><image href="https://example.org/a.png"/></svg>"#;
+        assert_eq!(
+            embed_images(svg, &dir).unwrap(),
+            r#"<svg><image xlink:href="data:image/png;base64,iVBORw==" width="10"/><image href="https://example.org/a.png"/></svg>"#
+        );
+        assert!(embed_images(r#"<svg><image href="missing.png"/></svg>"#, &dir).is_err());
+    }
+
+    #[test]
+    fn test_page_size() {
+        assert_eq!(
+            "A4".parse::<PageSize>().unwrap(),
+            PageSize {
+                width: 210.0,
+                height: 297.0
+            }
+        );
+        let letter = "8.5x11in".parse::<PageSize>().unwrap();
+        assert!((letter.width - 215.9).abs() < 1e-9);
+        assert!((letter.height - 279.4).abs() < 1e-9);
+        assert!("big".parse::<PageSize>().is_err());
+    }
+
+    #[test]
+    fn test_resize() {
+        let svg = r#"<svg width="100mm" height="100mm" viewBox="0 0 100 100"/>"#;
+        let a5 = Some("a5".parse::<PageSize>().unwrap());
+        assert_eq!(
+            resize(svg, a5, PageFit::Scale, None).unwrap(),
+            r#"<svg width="148mm" height="210mm" viewBox="0 0 100 100" preserveAspectRatio="xMidYMid meet"/>"#
+        );
+        assert_eq!(
+            resize(svg, a5, PageFit::Pad, None).unwrap(),
+            r#"<svg width="148mm" height="210mm" viewBox="-24 -55 148 210" preserveAspectRatio="xMidYMid meet"/>"#
+        );
+        assert_eq!(
+            resize(svg, None, PageFit::Scale, Some(0.5)).unwrap(),
+            r#"<svg width="50mm" height="50mm" viewBox="0 0 100 100" preserveAspectRatio="xMidYMid meet"/>"#
+        );
+    }
+
+    #[test]
+    fn test_crop() {
+        let svg = r#"<svg width="200mm" height="100mm" viewBox="0 0 100 50"><rect/></svg>"#;
+        assert_eq!(
+            crop(svg, 10.0, 10.0, 20.0, 30.0).unwrap(),
+            r#"<svg width="40mm" height="60mm" viewBox="10 10 20 30" preserveAspectRatio="xMidYMid meet"><rect/></svg>"#
+        );
+    }
+
+    #[test]
+    fn test_strip_metadata() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" inkscape:version="1.2"><sodipodi:namedview id="view"><inkscape:grid type="xygrid"/></sodipodi:namedview><metadata><rdf:RDF/></metadata><g inkscape:label="Layer 1" id="layer1"/></svg>"#;
+        assert_eq!(
+            strip_metadata(svg).unwrap(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><g id="layer1"/></svg>"#
+        );
+    }
+}
@@ -1,22 +1,689 @@
+pub use crate::export::Exporter;
+#[cfg(feature = "native")]
+pub use crate::export::{export_with_cairosvg, export_with_inkscape, export_with_svg2pdf};
+#[cfg(feature = "native")]
+use crate::{
+    atomic::{clean, write_atomic_checked},
+    blob::Blobs,
+    cache::{discard, Cache},
+    data::{compute, cross, dedupe, pivot, running, sample, select, slice, sort},
+    email::{describe, send, Email, EmailOptions},
+    export::{ExportPool, ProcessLimits},
+    gallery::{gallery, Entry},
+    hook::PostCommand,
+    icon::icon,
+    journal::Journal,
+    manifest::{digest, sha256, Manifest, ManifestFile},
+    map::map_image,
+    notify::{notify, RunSummary},
+    pdf::{
+        append, encrypt, finish, impose, optimize, page_count, read_ledger, record_ledger, sign,
+        verify, Booklet, LedgerEntry, Metadata, Navigation, Optimization, PdfStandard, SignOptions,
+    },
+    print::{print, PrintOptions},
+    profile::{Profiler, Stage},
+    raster::rasterize,
+    reader::{find_data_file, read_records_with, ReadOptions},
+    space::{check_space, is_storage_full, SAMPLE_SIZE},
+    svg::{
+        annotate, crop, embed_images, inject_css, overlay, resize, strip_metadata, toggle_layers,
+        validate, validate_reader, watermark,
+    },
+    upload::{upload, UploadOptions},
+};
+use crate::{
+    data::{Keep, Pivot, Running, SortKey},
+    export::ExportArea,
+    filters,
+    map::MapOptions,
+    schema::{OnInvalid, Schema},
+    source::TemplateSource,
+    svg::{lines, PageFit, PageSize},
+    theme::Theme,
+};
+#[cfg(feature = "native")]
 use clap::ValueEnum;
-use color_eyre::{eyre::Report, Result};
-use csv::Reader;
-use minijinja::Environment;
+#[cfg(feature = "native")]
+use color_eyre::eyre::WrapErr;
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+#[cfg(feature = "native")]
+use deunicode::deunicode;
+use indexmap::IndexMap;
+#[cfg(feature = "native")]
+use minijinja::Template;
+use minijinja::{AutoEscape, Environment, Value};
 use serde::Serialize;
+#[cfg(feature = "native")]
 use std::{
+    borrow::Cow,
     collections::HashMap,
     fs,
-    path::{Path, PathBuf},
-    process::Command,
+    io::{self, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
 };
+use std::{collections::BTreeMap, fmt, path::PathBuf, sync::Arc};
+#[cfg(feature = "native")]
+use unicode_normalization::UnicodeNormalization;
+
+/// A data record, keeping the order of the columns of the data file.
+pub type Record = IndexMap<String, String>;
+
+/// Subdirectory of the output directory where the thumbnails are generated.
+#[cfg(feature = "native")]
+pub const THUMBNAIL_DIR: &str = "thumbs";
+
+/// Default maximum number of outputs of a run, guarding against the joins and
+/// the cross products producing far more files than intended.
+#[cfg(feature = "native")]
+pub const DEFAULT_MAX_OUTPUTS: usize = 100_000;
+
+type Context = BTreeMap<String, Value>;
+
+/// Transformation of each record into the context of the template, e.g. to
+/// look up or convert values.
+///
+/// The returned value must be a map, its entries replacing the columns of the
+/// record.
+#[derive(Clone)]
+pub struct RecordMapper(pub Arc<dyn Fn(Record) -> Result<Value, Report> + Send + Sync>);
+
+impl RecordMapper {
+    /// Wrap a transformation of the records.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Record) -> Result<Value, Report> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for RecordMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RecordMapper")
+    }
+}
+
+/// Customization of the template environment, e.g. to register filters,
+/// functions or globals, run once the ones of svggloo are registered.
+#[derive(Clone)]
+pub struct EnvHook(pub Arc<dyn Fn(&mut Environment<'_>) + Send + Sync>);
+
+impl fmt::Debug for EnvHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EnvHook")
+    }
+}
+
+/// Options controlling the rendering of a template.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Source the template is loaded from, by the path given to the renderer.
+    ///
+    /// Defaults to the filesystem. The linked files, such as the images, are
+    /// still resolved relatively to the directory of the path.
+    pub template_source: Option<Arc<dyn TemplateSource>>,
+    /// Exporter used to convert the rendered SVG files.
+    pub exporter: Option<Exporter>,
+    /// Column naming the exporter of each record.
+    ///
+    /// When a record has a non-empty value in this column, it takes precedence
+    /// over `exporter`.
+    pub exporter_column: Option<String>,
+    /// Data fields used to name the rendered files.
+    pub field_based_name: Option<Vec<String>>,
+    /// Separator used to join the naming fields.
+    pub separator: Option<String>,
+    /// Transliterate the names of the rendered files to ASCII.
+    pub ascii_names: bool,
+    /// Suffix the colliding names with a number, instead of failing.
+    pub disambiguate_names: bool,
+    /// What to do with the records whose naming fields are empty.
+    #[cfg(feature = "native")]
+    pub on_empty_name: OnEmptyName,
+    /// Placeholder of the empty naming fields, with
+    /// [`OnEmptyName::Placeholder`].
+    #[cfg(feature = "native")]
+    pub name_placeholder: String,
+    /// Configuration of the `map_image` template function.
+    pub map: MapOptions,
+    /// Directory containing the icons used by the `icon` template function.
+    pub icon_dir: PathBuf,
+    /// Themes to render each record with.
+    ///
+    /// When empty, each record is rendered only once.
+    pub themes: Vec<Theme>,
+    /// Locales to render each record in.
+    pub locales: Vec<String>,
+    /// Column listing the locales of each record, as a comma-separated list.
+    ///
+    /// When a record has a non-empty value in this column, it takes precedence
+    /// over `locales`.
+    pub locale_column: Option<String>,
+    /// Show or hide the layers matching boolean values of the context.
+    pub toggle_layers: bool,
+    /// Strip the editor specific metadata from the rendered SVG files.
+    pub strip_metadata: bool,
+    /// Escape the values interpolated in the template for XML.
+    ///
+    /// Use the `safe` filter to interpolate a value verbatim.
+    pub autoescape: bool,
+    /// Remove the first newline after a block tag.
+    pub trim_blocks: bool,
+    /// Strip the spaces and the tabs before a block tag on its line.
+    pub lstrip_blocks: bool,
+    /// Check that the rendered SVG files are well-formed.
+    pub validate: bool,
+    /// Long to wide reshaping of the records, applied before the other
+    /// operations.
+    pub pivot: Option<Pivot>,
+    /// Variants combined with each record, to render every combination.
+    pub cross: Option<Vec<Record>>,
+    /// Columns computed from the other columns of the records, as
+    /// `(name, expression)` pairs.
+    pub computed: Vec<(String, String)>,
+    /// Columns holding JSON texts, parsed into structured values.
+    pub json_columns: Vec<String>,
+    /// Nest the values of the dotted columns, e.g. `scores.network`, into
+    /// objects.
+    pub nest_columns: bool,
+    /// Transformation of each record, applied before the other changes of the
+    /// context.
+    pub record_mapper: Option<RecordMapper>,
+    /// Customization of the template environment, see
+    /// [`RenderOptions::configure_env`].
+    pub env_hook: Option<EnvHook>,
+    /// Expose all the records to the templates as `_all`.
+    pub expose_all: bool,
+    /// Column grouping the records, exposing the other records of the group to
+    /// the templates as `_siblings`.
+    pub siblings_by: Option<String>,
+    /// Expose the previous and the next records of the data file to the
+    /// templates as `_prev` and `_next`, none at the edges.
+    pub expose_neighbors: bool,
+    /// Expose the position of the records in the selection to the templates as
+    /// `_rank`, starting at 1.
+    pub expose_rank: bool,
+    /// Running aggregates of the selected records exposed to the templates as
+    /// `_running`, e.g. `_running.sum.population`.
+    pub running: Vec<Running>,
+    /// Stylesheet injected into the rendered SVG files.
+    pub stylesheet: Option<String>,
+    /// Embed the linked images into the rendered SVG files.
+    pub embed_images: bool,
+    /// Page size of the rendered SVG files.
+    pub page_size: Option<PageSize>,
+    /// How the content is fitted into the page size.
+    pub page_fit: PageFit,
+    /// Scale factor applied to the canvas of the rendered SVG files.
+    pub scale: Option<f64>,
+    /// Column holding the title of each document.
+    pub title_column: Option<String>,
+    /// Language of the documents, as a language tag.
+    pub lang: Option<String>,
+    /// Column holding the language of each document.
+    ///
+    /// When a record has a non-empty value in this column, it takes precedence
+    /// over `lang`.
+    pub lang_column: Option<String>,
+    /// Columns holding the alternative texts of the images, by image `id`.
+    pub image_alts: Vec<(String, String)>,
+    /// Watermark text stamped diagonally across the rendered SVG files.
+    pub watermark_text: Option<String>,
+    /// SVG document composited over the rendered SVG files.
+    pub overlay: Option<String>,
+    /// Area of the rendered SVG files to export.
+    ///
+    /// Defaults to the exporter's own default: the drawing for Inkscape, the
+    /// page for the others.
+    pub export_area: Option<ExportArea>,
+    /// Destination of the rendered SVG when a single output is expected.
+    ///
+    /// When set, the rendering fails if the data produce more than one output.
+    pub single_output: Option<SingleOutput>,
+    /// Schema the records are validated against before rendering.
+    pub schema: Option<Schema>,
+    /// What to do with the records violating the schema.
+    pub on_invalid: OnInvalid,
+    /// Filters selecting the records to render, as `(column, value)` pairs.
+    pub only: Vec<(String, String)>,
+    /// Columns identifying the duplicated records.
+    pub dedupe_by: Vec<String>,
+    /// Which occurrence of the duplicated records is kept.
+    pub dedupe_keep: Keep,
+    /// Keys sorting the records before rendering.
+    pub sort_by: Vec<SortKey>,
+    /// Number of records to skip.
+    pub offset: usize,
+    /// Maximum number of records to render.
+    pub limit: Option<usize>,
+    /// Number of records to randomly sample.
+    pub sample: Option<usize>,
+    /// Seed of the random sampling.
+    pub seed: Option<u64>,
+    /// Indices of the records to render among the selected ones, all of them
+    /// by default.
+    ///
+    /// The other selected records are still planned, so that the outputs are
+    /// named and numbered as in a run of the whole selection.
+    pub pick: Option<Vec<usize>>,
+    /// Whether an HTML gallery of the rendered files is generated.
+    pub gallery: bool,
+    /// Width of the PNG thumbnails generated for the rendered files.
+    pub thumbnails: Option<u32>,
+    /// Upload of the generated files.
+    #[cfg(feature = "native")]
+    pub upload: Option<UploadOptions>,
+    /// URL notified with the summary of the run.
+    #[cfg(feature = "native")]
+    pub notify_url: Option<String>,
+    /// Delivery of the generated files by email.
+    #[cfg(feature = "native")]
+    pub email: Option<EmailOptions>,
+    /// Printing of the exported files.
+    #[cfg(feature = "native")]
+    pub print: Option<PrintOptions>,
+    /// Directory of the cache of the generated files, shared across runs.
+    #[cfg(feature = "native")]
+    pub cache_dir: Option<PathBuf>,
+    /// Skip the files already produced by an interrupted run.
+    #[cfg(feature = "native")]
+    pub resume: bool,
+    /// Warn, instead of failing, when there are no records to render.
+    #[cfg(feature = "native")]
+    pub allow_empty: bool,
+    /// Command run for each produced file.
+    #[cfg(feature = "native")]
+    pub post_cmd: Option<PostCommand>,
+    /// Ghostscript preset the exported files are optimized with.
+    #[cfg(feature = "native")]
+    pub optimize_pdf: Option<Optimization>,
+    /// PDF standard the exported files conform to.
+    #[cfg(feature = "native")]
+    pub pdf_standard: Option<PdfStandard>,
+    /// ICC profile of the output intent of the PDF standard.
+    #[cfg(feature = "native")]
+    pub icc_profile: Option<PathBuf>,
+    /// Password required to open the exported files.
+    #[cfg(feature = "native")]
+    pub pdf_password: Option<String>,
+    /// Column holding the password required to open the exported file of each
+    /// record.
+    ///
+    /// When a record has a non-empty value in this column, it takes precedence
+    /// over `pdf_password`.
+    #[cfg(feature = "native")]
+    pub pdf_password_column: Option<String>,
+    /// Digital signature of the exported files.
+    #[cfg(feature = "native")]
+    pub sign: Option<SignOptions>,
+    /// Timing of the stages of the run.
+    #[cfg(feature = "native")]
+    pub profiler: Option<Arc<Profiler>>,
+    /// Keep the large embedded data, such as the images, out of the compiled
+    /// template and of the rendered files until they are written, to bound
+    /// the memory used by the large templates.
+    #[cfg(feature = "native")]
+    pub large_template: bool,
+    /// Number of records rendered concurrently.
+    #[cfg(feature = "native")]
+    pub render_jobs: usize,
+    /// Maximum number of outputs of the run, checked before writing anything.
+    #[cfg(feature = "native")]
+    pub max_outputs: Option<usize>,
+    /// Number of exporter processes run concurrently, while the records are
+    /// still being rendered.
+    #[cfg(feature = "native")]
+    pub export_jobs: usize,
+    /// Limits of the resources used by the exporter processes.
+    #[cfg(feature = "native")]
+    pub export_limits: ProcessLimits,
+    /// Check that the output volume has enough space for the run, once a few
+    /// files are written.
+    #[cfg(feature = "native")]
+    pub space_check: bool,
+    /// Record the SHA-256 checksums of the final artifacts in the manifest.
+    #[cfg(feature = "native")]
+    pub checksums: bool,
+    /// Report the pages of the exported files are appended to, skipping the
+    /// files appended by the previous runs.
+    #[cfg(feature = "native")]
+    pub append_to: Option<PathBuf>,
+    /// Navigation aids of the report the pages are appended to.
+    #[cfg(feature = "native")]
+    pub navigation: Navigation,
+    /// Imposition of the report the pages are appended to, written next to it.
+    #[cfg(feature = "native")]
+    pub booklet: Option<Booklet>,
+}
+
+/// What to do with the records whose naming fields are empty, which would
+/// produce names like `-.svg` or colliding ones.
+#[cfg(feature = "native")]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum OnEmptyName {
+    /// Stop before rendering anything, reporting the record.
+    #[default]
+    Fail,
+    /// Replace the empty fields with the placeholder.
+    Placeholder,
+    /// Replace the empty fields with the row number of the record.
+    Index,
+}
+
+/// Destination of a single rendered SVG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SingleOutput {
+    /// Write the rendered SVG to the standard output.
+    Stdout,
+    /// Write the rendered SVG to a file.
+    File(PathBuf),
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            template_source: None,
+            exporter: None,
+            exporter_column: None,
+            field_based_name: None,
+            separator: None,
+            ascii_names: false,
+            disambiguate_names: false,
+            #[cfg(feature = "native")]
+            on_empty_name: OnEmptyName::Fail,
+            #[cfg(feature = "native")]
+            name_placeholder: String::from("unnamed"),
+            map: MapOptions::default(),
+            icon_dir: PathBuf::from("icons"),
+            themes: Vec::new(),
+            locales: Vec::new(),
+            locale_column: None,
+            toggle_layers: false,
+            strip_metadata: false,
+            autoescape: true,
+            trim_blocks: true,
+            lstrip_blocks: true,
+            validate: true,
+            pivot: None,
+            cross: None,
+            computed: Vec::new(),
+            json_columns: Vec::new(),
+            nest_columns: false,
+            record_mapper: None,
+            env_hook: None,
+            expose_all: false,
+            siblings_by: None,
+            expose_neighbors: false,
+            expose_rank: false,
+            running: Vec::new(),
+            stylesheet: None,
+            embed_images: false,
+            page_size: None,
+            page_fit: PageFit::Scale,
+            scale: None,
+            title_column: None,
+            lang: None,
+            lang_column: None,
+            image_alts: Vec::new(),
+            watermark_text: None,
+            overlay: None,
+            export_area: None,
+            single_output: None,
+            schema: None,
+            on_invalid: OnInvalid::Fail,
+            only: Vec::new(),
+            dedupe_by: Vec::new(),
+            dedupe_keep: Keep::First,
+            sort_by: Vec::new(),
+            offset: 0,
+            limit: None,
+            sample: None,
+            seed: None,
+            pick: None,
+            gallery: false,
+            thumbnails: None,
+            #[cfg(feature = "native")]
+            upload: None,
+            #[cfg(feature = "native")]
+            notify_url: None,
+            #[cfg(feature = "native")]
+            email: None,
+            #[cfg(feature = "native")]
+            print: None,
+            #[cfg(feature = "native")]
+            cache_dir: None,
+            #[cfg(feature = "native")]
+            resume: false,
+            #[cfg(feature = "native")]
+            allow_empty: false,
+            #[cfg(feature = "native")]
+            post_cmd: None,
+            #[cfg(feature = "native")]
+            optimize_pdf: None,
+            #[cfg(feature = "native")]
+            pdf_standard: None,
+            #[cfg(feature = "native")]
+            icc_profile: None,
+            #[cfg(feature = "native")]
+            pdf_password: None,
+            #[cfg(feature = "native")]
+            pdf_password_column: None,
+            #[cfg(feature = "native")]
+            sign: None,
+            #[cfg(feature = "native")]
+            profiler: None,
+            #[cfg(feature = "native")]
+            large_template: false,
+            #[cfg(feature = "native")]
+            render_jobs: 1,
+            #[cfg(feature = "native")]
+            max_outputs: Some(DEFAULT_MAX_OUTPUTS),
+            #[cfg(feature = "native")]
+            export_jobs: 1,
+            #[cfg(feature = "native")]
+            export_limits: ProcessLimits::default(),
+            #[cfg(feature = "native")]
+            space_check: true,
+            #[cfg(feature = "native")]
+            checksums: false,
+            #[cfg(feature = "native")]
+            append_to: None,
+            #[cfg(feature = "native")]
+            navigation: Navigation::default(),
+            #[cfg(feature = "native")]
+            booklet: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Customize the template environment before rendering, e.g. to register
+    /// filters, functions or globals, or to change the syntax settings.
+    ///
+    /// The closure runs after svggloo registers its own filters and functions,
+    /// which it can override. It replaces the previous one, if any.
+    pub fn configure_env<F>(&mut self, f: F)
+    where
+        F: Fn(&mut Environment<'_>) + Send + Sync + 'static,
+    {
+        self.env_hook = Some(EnvHook(Arc::new(f)));
+    }
+}
+
+/// A variant of a record.
+///
+/// Each record is rendered once per variant.
+#[derive(Debug, Clone, Default)]
+struct Variant<'a> {
+    theme: Option<&'a Theme>,
+    locale: Option<String>,
+}
+
+/// Rendering deferred until the SVG is written, to stream it to its destination.
+#[cfg(feature = "native")]
+type Deferred<'a> = Box<dyn Fn(&mut dyn Write) -> Result<(), Report> + Send + 'a>;
+
+/// The output of a rendering job.
+#[cfg(feature = "native")]
+struct Rendered<'a> {
+    /// Rendered SVG, empty if the rendering is deferred.
+    svg: String,
+    /// Rendering streaming the SVG, run as it is written.
+    deferred: Option<Deferred<'a>>,
+    /// Key of the output, when the cache or the journal is enabled.
+    key: Option<String>,
+    /// Where the output comes from.
+    origin: Origin,
+    /// Time spent rendering the SVG, zero if it was not rendered again or if
+    /// the rendering is deferred.
+    elapsed: Duration,
+    /// Blobs kept out of a large template, to splice back in the SVG.
+    blobs: Option<Arc<Blobs>>,
+    /// Number of jobs left after this one.
+    left: usize,
+    /// Check that the SVG is well-formed once written, when the rendering is
+    /// deferred.
+    validate: bool,
+}
+
+#[cfg(feature = "native")]
+impl Rendered<'_> {
+    /// Write the SVG, along with the blobs of a large template.
+    fn write(&self, out: &mut dyn Write) -> Result<(), Report> {
+        match (&self.deferred, &self.blobs) {
+            (Some(render), _) => render(out)?,
+            (None, Some(blobs)) => blobs.write(&self.svg, &mut { out })?,
+            (None, None) => out.write_all(self.svg.as_bytes())?,
+        }
+        Ok(())
+    }
+
+    /// Write the SVG to a file, atomically.
+    ///
+    /// A streamed SVG is checked as it is read back from the temporary file,
+    /// so that a malformed SVG is never written.
+    fn write_file(&self, path: &Path) -> Result<(), Report> {
+        write_atomic_checked(
+            path,
+            |out| self.write(out),
+            |temp| match self.deferred.is_some() && self.validate {
+                true => validate_reader(io::BufReader::new(fs::File::open(temp)?)),
+                false => Ok(()),
+            },
+        )
+    }
+
+    /// The whole SVG, along with the blobs of a large template.
+    fn full_svg(&self) -> Result<Cow<'_, str>, Report> {
+        if self.deferred.is_some() {
+            let mut svg = Vec::new();
+            self.write(&mut svg)?;
+            let svg = String::from_utf8(svg)?;
+            if self.validate {
+                validate(&svg)?;
+            }
+            return Ok(Cow::Owned(svg));
+        }
+        Ok(match &self.blobs {
+            Some(blobs) => blobs.splice(&self.svg),
+            None => Cow::Borrowed(&self.svg),
+        })
+    }
+}
+
+/// Where the output of a rendering job comes from.
+#[cfg(feature = "native")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Origin {
+    /// The template was rendered.
+    Rendered,
+    /// The output was found in the cache.
+    Cached,
+    /// The output was produced by the interrupted run being resumed.
+    Resumed,
+}
+
+/// A rendering job: one variant of a record.
+#[cfg(feature = "native")]
+struct Job<'a> {
+    /// Position of the record in the data, starting at 1.
+    number: usize,
+    record: &'a Record,
+    variant: Variant<'a>,
+    /// Name of the output file.
+    file_name: String,
+    /// Exporter of the output file.
+    exporter: Option<Exporter>,
+}
+
+#[cfg(feature = "native")]
+impl Variant<'_> {
+    /// Suffixes to append to the output file name.
+    fn suffixes(&self) -> impl Iterator<Item = &str> {
+        self.theme
+            .map(|theme| theme.name.as_str())
+            .into_iter()
+            .chain(self.locale.as_deref())
+    }
+}
+
+/// Create a template environment.
+///
+/// The environment comes with the svggloo specific functions and filters
+/// already registered.
+pub fn environment<'source>(options: &RenderOptions) -> Environment<'source> {
+    let mut env = Environment::new();
+
+    // SVG files are not recognized by the default callback, which would leave
+    // the values unescaped and the output malformed.
+    let autoescape = options.autoescape;
+    env.set_auto_escape_callback(move |_| match autoescape {
+        true => AutoEscape::Html,
+        false => AutoEscape::None,
+    });
+
+    // The lines of the block tags would otherwise leave blank lines and spaces
+    // in the text elements, shifting their layout.
+    env.set_trim_blocks(options.trim_blocks);
+    env.set_lstrip_blocks(options.lstrip_blocks);
+
+    // The multiline cells would otherwise render on a single line.
+    env.add_filter(
+        "lines",
+        |value: String, x: Option<Value>, dy: Option<String>| {
+            let x = x.map(|x| x.to_string());
+            let dy = dy.as_deref().unwrap_or("1.2em");
+            Value::from_safe_string(lines(&value, x.as_deref(), dy))
+        },
+    );
+    filters::register(&mut env);
+
+    // The functions reaching out to the filesystem or the network are not
+    // available in the browser.
+    #[cfg(feature = "native")]
+    {
+        let map = options.map.clone();
+        env.add_function("map_image", move |lat, lon, zoom, size| {
+            map_image(&map, lat, lon, zoom, size)
+        });
+        let icon_dir = options.icon_dir.clone();
+        env.add_function("icon", move |name: String| icon(&icon_dir, &name));
+    }
 
-type Record = HashMap<String, String>;
+    if let Some(hook) = &options.env_hook {
+        (hook.0)(&mut env);
+    }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-pub enum Exporter {
-    Inkscape,
-    CairoSVG,
-    SVG2PDF,
+    env
 }
 
 /// Render an SVG template.
@@ -24,7 +691,7 @@ pub enum Exporter {
 /// Merges the data from the CSV file into the SVG template to create a new SVG
 /// file and render it to PDF.
 ///
-/// The `field_based_name` argument can be used to specify one or several fields
+/// The `field_based_name` option can be used to specify one or several fields
 /// from the CSV file that must be used to name the output files. If the fields
 /// don't exist, this function will panic. Once all the fields are being
 /// collected, they are transformed to lowercase and concatenated together using
@@ -35,10 +702,153 @@ pub enum Exporter {
 ///
 /// If `separator` is not specified, it defaults to dash (`-`).
 ///
+/// If `themes` are specified, each record is rendered once per theme, with the
+/// theme variables added to its context. The theme name is appended to the name
+/// of the output file, and is exposed to the template as `_theme`.
+///
+/// Similarly, if `locales` are specified, each record is rendered once per
+/// locale (and per theme). The locale is appended to the name of the output
+/// file, and is exposed to the template as `locale`.
+///
+/// If `toggle_layers` is set, the groups of the rendered SVG whose `id` or
+/// `inkscape:label` match a boolean value of the context are shown or hidden
+/// accordingly.
+///
+/// If `strip_metadata` is set, the Inkscape and Sodipodi editor metadata are
+/// removed from the rendered SVG.
+///
+/// If `validate` is set, the rendering stops at the first record producing a
+/// malformed SVG, reporting the line and column of the error.
+///
+/// If a `stylesheet` is specified, it is injected into a `<style>` element at
+/// the end of every rendered SVG.
+///
+/// If `embed_images` is set, the images linked from the rendered SVG are
+/// resolved relatively to the template and embedded as data URIs, so that the
+/// output files can be exported from any directory.
+///
+/// If a `page_size` or a `scale` factor is specified, the canvas of the
+/// rendered SVG is resized accordingly, either scaling the content or padding
+/// it depending on the `page_fit`.
+///
+/// If an `exporter_column` is specified, the exporter named in this column
+/// (case-insensitive) overrides the `exporter` for the record. The records
+/// without a value in this column use the `exporter`.
+///
+/// The `export_area` selects the area exported by the exporter. A custom area
+/// crops the canvas of the rendered SVG, so that all the exporters produce the
+/// same result. The drawing area is only supported by Inkscape.
+///
+/// The rendered SVG can be annotated for the assistive technologies: with the
+/// title of the `title_column`, with the language of the locale of the variant,
+/// of the `lang_column` or `lang`, and with the alternative texts of the
+/// `image_alts` columns for the images with the matching `id`. The title and the
+/// language are also set in the metadata of the exported files.
+///
+/// If a `watermark_text` is specified, it is stamped diagonally across the
+/// canvas of the rendered SVG. If an `overlay` SVG document is specified, it is
+/// stretched over the canvas, on top of the content.
+///
+/// If `optimize_pdf` is specified, the exported files are rewritten with
+/// Ghostscript using this preset, which downsamples their images and compresses
+/// their streams.
+///
+/// If a `pdf_standard` is specified, the exported files are then converted to
+/// it with Ghostscript, embedding the `icc_profile` as their output intent, and
+/// verified with veraPDF when it is installed.
+///
+/// If a `pdf_password` or a `pdf_password_column` is specified, the exported
+/// files are finally encrypted with qpdf, and require the password of their
+/// record to be opened.
+///
+/// If `sign` is configured, the exported files are finally signed with
+/// pyHanko, using the certificate and the private key of a PKCS#12 file. Signed
+/// files cannot be encrypted, since the encryption would break the signature.
+///
+/// If a `thumbnails` width is specified, each rendered SVG is also rasterized
+/// to a PNG thumbnail of this width, in the `thumbs` subdirectory of the output
+/// directory.
+///
+/// If `gallery` is set, an `index.html` gallery of the rendered files, showing
+/// the key fields of their records and linking to the exported files, is
+/// generated in the output directory.
+///
+/// If `upload` is configured, the final artifacts (the exported files, or the
+/// rendered files without exporter, along with the thumbnails and the gallery)
+/// are uploaded once generated. A failed upload does not stop the others, the
+/// rendering fails once every file has been tried. The URLs of the uploaded
+/// files are recorded in the `manifest.json` file of the output directory.
+///
+/// If `append_to` is specified, the pages of the exported files are appended to
+/// this report with qpdf, the original being kept as a backup. The files
+/// appended are recorded in a ledger next to the report, and are not rendered
+/// again by the next runs. The `navigation` aids, i.e. the page numbers and the
+/// bookmarks of the records, are added to the whole report after every run, and
+/// the report is imposed into a `booklet` for saddle-stitch printing.
+///
+/// If `checksums` is set, the SHA-256 checksums of the final artifacts are
+/// recorded in the manifest, see [`Manifest::verify`].
+///
+/// If a `notify_url` is specified, a JSON summary of the run (counts,
+/// failures, manifest location) is POSTed to it when the run finishes, whether
+/// it succeeded or not.
+///
+/// If a `cache_dir` is specified, the generated files are stored in this
+/// directory, keyed by the template, the record and the options they depend on.
+/// The outputs found in the cache are neither rendered nor exported again,
+/// their files are hard-linked or copied from the cache instead.
+///
+/// A journal of the files produced in the output directory is kept, recording
+/// each file once written or, when exported, once its PDF is exported and
+/// processed. If `resume` is set, the files recorded by the interrupted
+/// previous run are not rendered again, and only exported if their export is
+/// missing.
+///
+/// If a `post_cmd` is specified, it is run for each produced file once
+/// exported, with the rendered file, the final file and the values of the
+/// record as placeholders, see [`PostCommand`]. A failed command does not stop
+/// the others, the rendering fails once every command has been run. The files
+/// restored from the cache are copied rather than hard-linked, so that the
+/// command can modify them in place.
+///
+/// If `print` is configured, the exported files are sent to a printer queue
+/// with the CUPS `lp` command, one job per file.
+///
+/// If `email` is configured, the exported file of each record (or its rendered
+/// file without exporter) is emailed to the address of its email column, with
+/// a subject and a body rendered from the record. The emails are sent one at a
+/// time, at most `rate` per minute, and are only reported in a dry run. A
+/// failed email does not stop the others, the rendering fails once every email
+/// has been tried.
+///
+/// If a `single_output` is specified, the data must produce exactly one
+/// output, which is written to the specified destination instead of the
+/// output directory.
+///
+/// If a `schema` is specified, every record is validated against it before
+/// rendering, and all the violations are reported on the standard error. Then,
+/// depending on `on_invalid`, the rendering either fails or skips the invalid
+/// records.
+///
+/// The `only` filters restrict the rendering to the matching records. The
+/// filters of a same column are combined with OR, the filters of different
+/// columns with AND.
+///
+/// The records sharing the same values in the `dedupe_by` columns are
+/// rendered only once, keeping the occurrence designated by `dedupe_keep`. The
+/// number of dropped duplicates is reported on the standard error.
+///
+/// The selected records are sorted by the `sort_by` keys, comparing the values
+/// numerically when possible. They can then be sliced with `offset` and `limit`, and
+/// finally randomly sampled with `sample`, using an optional `seed` to make the
+/// sampling reproducible. The position of the records in this selection is
+/// exposed as `_rank` with `expose_rank`, and their `running` aggregates as
+/// `_running`.
+///
 /// ```no_run
 /// # use color_eyre::{eyre::Report, Result};
 /// use std::path::Path;
-/// use svggloo::template::{render, Exporter};
+/// use svggloo::template::{render, Exporter, RenderOptions};
 ///
 /// # fn main() -> Result<(), Report> {
 /// let svg_template = Path::new("SVG_TEMPLATE_FILENAME");
@@ -48,220 +858,2396 @@ pub enum Exporter {
 ///     String::from("state"),
 ///     String::from("city"),
 /// ];
-/// let _ = render(
-///     &svg_template.canonicalize()?,
-///     output_dir,
-///     Some(Exporter::CairoSVG),
-///     Some(fields),
-///     None,
-/// )?;
+/// let options = RenderOptions {
+///     exporter: Some(Exporter::CairoSVG),
+///     field_based_name: Some(fields),
+///     ..Default::default()
+/// };
+/// render(&svg_template.canonicalize()?, output_dir, &options)?;
 /// # Ok(())
 /// # }
 /// ```
+#[cfg(feature = "native")]
 pub fn render(
     svg_template: &Path,
     output_dir: &Path,
-    exporter: Option<Exporter>,
-    field_based_name: Option<Vec<String>>,
-    separator: Option<&str>,
+    options: &RenderOptions,
 ) -> Result<(), Report> {
-    // Locate the template file data and the prepare the output directory.
-    let template_data = svg_template.with_extension("csv");
-    fs::create_dir_all(output_dir)?;
+    // Read the data file located next to the template.
+    let records = read_records(&find_data_file(svg_template)?)?;
 
-    // Load the template.
-    let source = fs::read_to_string(svg_template)?;
-    let name = svg_template
-        .file_name()
-        .expect("Invalid template name.")
-        .to_str()
-        .unwrap();
-    let mut env = Environment::new();
-    env.add_template(name, &source)?;
-    let tmpl = env.get_template(name).unwrap();
+    render_records(svg_template, &records, output_dir, options)
+}
 
-    // Set the separator.
-    let sep = separator.unwrap_or("-");
+/// Read the records of a data file, detecting its delimiter.
+///
+/// See [`read_records_with`] to specify the reading options.
+#[cfg(feature = "native")]
+pub fn read_records(path: &Path) -> Result<Vec<Record>, Report> {
+    read_records_with(path, &ReadOptions::default())
+}
 
-    // Read the CSV.
-    let mut csv_reader = Reader::from_path(template_data)?;
-    let mut files: Vec<PathBuf> = Vec::new();
-    for result in csv_reader.deserialize() {
-        let record: Record = result?;
+/// Render an SVG template with the provided records.
+///
+/// See [`render`] for the details.
+#[cfg(feature = "native")]
+pub fn render_records(
+    svg_template: &Path,
+    records: &[Record],
+    output_dir: &Path,
+    options: &RenderOptions,
+) -> Result<(), Report> {
+    render_records_with_summary(svg_template, records, output_dir, options).1
+}
 
-        // Construct the name of the output file.
-        let item_name = match field_based_name.clone() {
-            Some(fields) => {
-                let v = fields
-                    .clone()
-                    .iter()
-                    .map(|f| record[f].clone())
-                    .map(|f| f.replace(' ', "_"))
-                    .collect::<Vec<String>>();
-                v.join(sep).to_lowercase()
-            }
-            None => record.values().next().unwrap().to_owned().to_lowercase(),
-        };
-        let mut item = item_name.clone();
-        item.push_str(".svg");
+/// Name the output files of the records, as a rendering would, along with the
+/// index of their record.
+#[cfg(feature = "native")]
+pub fn output_names(
+    records: &[Record],
+    options: &RenderOptions,
+) -> Result<Vec<(usize, String)>, Report> {
+    let indices: Vec<usize> = (0..records.len()).collect();
+    Ok(plan(records, &indices, options)?
+        .into_iter()
+        .map(|job| (job.number - 1, job.file_name))
+        .collect())
+}
 
-        // Render the template to file for this specific record.
-        let rendered = tmpl.render(&record)?;
-        let output_file = output_dir.join(&item);
-        fs::write(&output_file, rendered)?;
-        files.push(output_file);
+/// Render an SVG template with the provided records, and summarize the run.
+///
+/// The summary is returned whatever the outcome of the run, along with it.
+#[cfg(feature = "native")]
+pub fn render_records_with_summary(
+    svg_template: &Path,
+    records: &[Record],
+    output_dir: &Path,
+    options: &RenderOptions,
+) -> (RunSummary, Result<(), Report>) {
+    let start = Instant::now();
+    let mut summary = RunSummary {
+        template: svg_template.display().to_string(),
+        records: records.len(),
+        ..Default::default()
+    };
+    let result = run(svg_template, records, output_dir, options, &mut summary);
+    summary.success = result.is_ok();
+    summary.error = result.as_ref().err().map(|e| format!("{:#}", e));
+    summary.duration = start.elapsed().as_secs_f64();
+    if options.single_output.is_none() {
+        summary.output_size = dir_size(output_dir);
     }
 
-    // Convert it to pdf.
-    if let Some(exporter) = exporter {
-        match exporter {
-            Exporter::Inkscape => export_with_inkscape(&files),
-            Exporter::CairoSVG => export_with_cairosvg(&files),
-            Exporter::SVG2PDF => export_with_svg2pdf(&files),
+    // Notify the end of the run, whatever its outcome.
+    if let Some(url) = &options.notify_url {
+        if let Err(e) = notify(url, &summary) {
+            crate::report(e.to_string());
         }
     }
-    Ok(())
-}
-
-/// Render the template using a record from the CSV file.
-///
-/// ```no_run
-/// # use color_eyre::{eyre::Report, Result};
-/// use svggloo::template::render_record;
-/// use std::collections::HashMap;
-///
-/// # fn main() -> Result<(), Report> {
-/// let template = "This is {{city}}.";
-/// let record = HashMap::from([("city", "Austin")]);
-/// let rendered = render_record(template, record)?;
-/// assert_eq!(rendered, String::from("This is Austin"));
-/// # Ok(())
-/// # }
-/// ```
-pub fn render_record<S: Serialize>(template: &str, record: S) -> Result<String, Report> {
-    let name = "template";
-    let mut env = Environment::new();
-    env.add_template(name, template)?;
-    let tmpl = env.get_template(name).unwrap();
 
-    // Render the template to file for this specific record.
-    Ok(tmpl.render(&record)?)
+    (summary, result)
 }
 
-/// Render a template file using a record from the CSV file.
-pub fn render_record_from_file<S: Serialize>(
+/// Run the rendering, and fill in its summary.
+#[cfg(feature = "native")]
+fn run(
     svg_template: &Path,
-    record: S,
-) -> Result<String, Report> {
-    // Load the template.
-    let template = fs::read_to_string(svg_template)?;
+    records: &[Record],
+    output_dir: &Path,
+    options: &RenderOptions,
+    summary: &mut RunSummary,
+) -> Result<(), Report> {
+    // An empty data file usually reveals a failure of the pipeline producing
+    // it, rather than an absence of records.
+    if records.is_empty() {
+        if !options.allow_empty {
+            return Err(eyre!(
+                "No records to render in the data of {}, use --allow-empty to accept it",
+                svg_template.display()
+            ));
+        }
+        crate::note!(
+            "Warning: no records to render in the data of {}",
+            svg_template.display()
+        );
+        return Ok(());
+    }
 
-    // Render it.
-    render_record(&template, record)
-}
+    // Check the export options before rendering anything.
+    if let (Some(exporter), Some(area)) = (options.exporter, options.export_area) {
+        if !exporter.supports(area) {
+            return Err(eyre!("{:?} cannot export the {:?} area", exporter, area));
+        }
+    }
+    if options.exporter.is_some() && options.single_output == Some(SingleOutput::Stdout) {
+        return Err(eyre!("The standard output cannot be exported"));
+    }
+    if let Some(standard) = options
+        .pdf_standard
+        .filter(|_| options.icc_profile.is_none())
+    {
+        return Err(eyre!(
+            "{} requires an ICC profile, specify it with --icc-profile",
+            standard
+        ));
+    }
+    if options.print.is_some() && options.exporter.is_none() && options.exporter_column.is_none() {
+        return Err(eyre!("Only the exported files can be printed"));
+    }
+    if options.append_to.is_some() {
+        if options.exporter.is_none() && options.exporter_column.is_none() {
+            return Err(eyre!("Only the exported files can be appended"));
+        }
+        if options.single_output.is_some() {
+            return Err(eyre!("A single output cannot be appended"));
+        }
+    } else if !options.navigation.is_empty() || options.booklet.is_some() {
+        return Err(eyre!(
+            "Page numbers, bookmarks and booklets require a report to append to"
+        ));
+    }
 
-/// Exports an SVG file to a PDF with Inkscape.
-///
-/// Exports an SVG `src` file as a PDF with the same name.
-///
-/// The export is done using Inkspace. If Inkscape is not found, this function
-/// will panic.
-pub fn export_with_inkscape(srcs: &[PathBuf]) {
-    // Set the name of the Inkscape binary.
-    let program = "inkscape";
+    let encrypted = options.pdf_password.is_some() || options.pdf_password_column.is_some();
+    if encrypted && options.sign.is_some() {
+        return Err(eyre!("Signed files cannot be encrypted"));
+    }
+    if options.append_to.is_some() && (encrypted || options.sign.is_some()) {
+        return Err(eyre!("Encrypted or signed files cannot be appended"));
+    }
+    if let Some(column) = &options.pdf_password_column {
+        if records
+            .first()
+            .filter(|r| !r.contains_key(column))
+            .is_some()
+        {
+            return Err(eyre!("Unknown PDF password column {:?}", column));
+        }
+    }
 
-    // Prepare the Inkscape arguments.
-    let export_filenames = srcs
-        .iter()
-        .map(|s| s.clone().into_os_string())
-        .filter_map(|src| src.into_string().ok())
-        .collect::<Vec<String>>();
-    let mut args = vec![
-        "--export-area-drawing".to_owned(),
-        "--batch-process".to_owned(),
-        "--export-type=pdf".to_owned(),
-    ];
-    args.extend(export_filenames);
-
-    export_with(program, &args);
-}
-
-/// Export with a specific program and arguments.
-fn export_with(program: &str, args: &[String]) {
-    // Prepare the error message.
-    let error_msg = format!(
-        "Failed to execute command `{} {}`",
-        program,
-        &args.join(" ")
+    // Prepare the emails templates.
+    let mut email_env = Environment::new();
+    if let Some(email) = &options.email {
+        if options.single_output.is_some() {
+            return Err(eyre!("A single output cannot be emailed"));
+        }
+        if records
+            .first()
+            .filter(|r| !r.contains_key(&email.column))
+            .is_some()
+        {
+            return Err(eyre!("Unknown email column {:?}", email.column));
+        }
+        email_env.add_template("subject", &email.subject)?;
+        email_env.add_template("body", &email.body)?;
+    }
+
+    // Render the template for each job.
+    let template_dir = svg_template.parent().unwrap_or_else(|| Path::new(""));
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut thumbnails: Vec<PathBuf> = Vec::new();
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut emails: Vec<Email> = Vec::new();
+    let cache = options.cache_dir.as_deref().map(Cache::new);
+    let mut exports: Vec<PathBuf> = Vec::new();
+    let pool = ExportPool::new(
+        options.export_jobs,
+        options.export_area,
+        options.export_limits.clone(),
+        options.profiler.clone(),
+    );
+    let mut pdfs: Vec<PathBuf> = Vec::new();
+    let mut artifacts: Vec<PathBuf> = Vec::new();
+    let mut fresh: Vec<(PathBuf, String, bool)> = Vec::new();
+    let mut produced: Vec<(PathBuf, PathBuf, Record)> = Vec::new();
+    let mut metadata: HashMap<PathBuf, Metadata> = HashMap::new();
+    let mut reused = 0;
+    let mut resumed = 0;
+    // The exported files, journaled once their processing is done.
+    let mut journaled: Vec<(String, String, PathBuf)> = Vec::new();
+    // The files written by the run, removed if the volume gets full, and the
+    // sizes of the first ones.
+    let mut created: Vec<PathBuf> = Vec::new();
+    let mut sample: Vec<u64> = Vec::new();
+    let mut out_of_space = false;
+    // The output directory is only changed once the outputs are planned, so
+    // that a rejected plan leaves it as it was.
+    let open_journal = || match options.single_output {
+        Some(_) => Ok(None),
+        None => {
+            let cleaned = clean(output_dir);
+            if cleaned > 0 {
+                crate::note!("Removed {} partial file(s) of an interrupted run", cleaned);
+            }
+            Journal::open(output_dir, options.resume).map(Some)
+        }
+    };
+    let rendered = render_jobs(
+        svg_template,
+        records,
+        options,
+        open_journal,
+        |job, rendered, journal| {
+            summary.rendered += 1;
+            // The deferred renderings run as their files are written.
+            let mut written = Duration::ZERO;
+            let mut export = |file: &Path| {
+                if let Some(exporter) = job.exporter {
+                    pool.push(exporter, file.to_path_buf());
+                    exports.push(file.to_path_buf());
+                }
+            };
+            match &options.single_output {
+                Some(SingleOutput::Stdout) => {
+                    if job.exporter.is_some() {
+                        return Err(eyre!("The standard output cannot be exported"));
+                    }
+                    let mut stdout = io::stdout().lock();
+                    let result = rendered
+                        .write(&mut stdout)
+                        .and_then(|_| Ok(stdout.flush()?));
+                    match result {
+                        // The reader of a pipeline may stop early, e.g. `head`.
+                        Err(e)
+                            if e.downcast_ref::<io::Error>()
+                                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) => {}
+                        result => result?,
+                    }
+                }
+                Some(SingleOutput::File(output_file)) => {
+                    let start = Instant::now();
+                    rendered.write_file(output_file)?;
+                    written = start.elapsed();
+                    if let Some(profiler) = &options.profiler {
+                        profiler.record(Stage::Write, &job.file_name, start);
+                    }
+                    export(output_file);
+                    let final_file = match job.exporter {
+                        Some(_) => output_file.with_extension("pdf"),
+                        None => output_file.clone(),
+                    };
+                    produced.push((output_file.clone(), final_file, job.record.clone()));
+                    files.push(output_file.clone());
+                }
+                None => {
+                    fs::create_dir_all(output_dir)?;
+                    let output_file = output_dir.join(&job.file_name);
+                    let start = Instant::now();
+                    if rendered.origin != Origin::Resumed {
+                        created.push(output_file.clone());
+                    }
+                    rendered.write_file(&output_file)?;
+                    written = start.elapsed();
+                    if let Some(profiler) = &options.profiler {
+                        profiler.record(Stage::Write, &job.file_name, start);
+                    }
+                    if options.space_check && sample.len() < SAMPLE_SIZE {
+                        sample.push(fs::metadata(&output_file)?.len());
+                        if sample.len() == SAMPLE_SIZE && rendered.left > 0 {
+                            let exported = job.exporter.is_some();
+                            check_space(output_dir, &sample, rendered.left, exported)
+                                .inspect_err(|_| out_of_space = true)?;
+                        }
+                    }
+                    let pdf = output_file.with_extension("pdf");
+                    let exported = job.exporter.is_some();
+                    match (rendered.origin, &cache, &rendered.key) {
+                        (Origin::Resumed, _, _) => {
+                            // The export may not have run before the interruption.
+                            if !pdf.is_file() {
+                                export(&output_file);
+                            }
+                            resumed += 1;
+                        }
+                        (Origin::Cached, Some(cache), Some(key)) => {
+                            if exported {
+                                cache.restore(key, &pdf, options.post_cmd.is_none())?;
+                            }
+                            reused += 1;
+                        }
+                        (_, Some(_), Some(key)) => {
+                            discard(&pdf)?;
+                            export(&output_file);
+                            fresh.push((output_file.clone(), key.clone(), exported));
+                        }
+                        _ => export(&output_file),
+                    }
+                    let final_file = match exported {
+                        true => pdf.clone(),
+                        false => output_file.clone(),
+                    };
+                    if exported {
+                        let context = context(job.record, &job.variant);
+                        metadata.insert(pdf.clone(), accessibility(&context, options).0);
+                        pdfs.push(pdf.clone());
+                    }
+                    artifacts.push(final_file.clone());
+                    produced.push((output_file.clone(), final_file, job.record.clone()));
+                    if let (Some(journal), Some(key)) = (journal, &rendered.key) {
+                        match (rendered.origin, exported) {
+                            (Origin::Resumed, _) => (),
+                            (_, true) => {
+                                journaled.push((key.clone(), job.file_name.clone(), pdf.clone()))
+                            }
+                            (_, false) => journal.record(key, &job.file_name)?,
+                        }
+                    }
+                    if let Some(email) = &options.email {
+                        match job.record[&email.column].trim() {
+                            "" => crate::note!("Record {} has no email address", job.number),
+                            to => {
+                                let context = job_context(job, options)?;
+                                emails.push(Email {
+                                    to: to.to_owned(),
+                                    subject: email_env.get_template("subject")?.render(&context)?,
+                                    body: email_env.get_template("body")?.render(&context)?,
+                                    attachment: match exported {
+                                        true => pdf.clone(),
+                                        false => output_file.clone(),
+                                    },
+                                });
+                            }
+                        }
+                    }
+                    files.push(output_file);
+                    let thumbnail = match options.thumbnails {
+                        Some(width) => {
+                            let thumbnail = Path::new(THUMBNAIL_DIR)
+                                .join(&job.file_name)
+                                .with_extension("png");
+                            let png = rasterize(&rendered.full_svg()?, template_dir, Some(width))?
+                                .encode_png()
+                                .map_err(|e| eyre!("Cannot encode the thumbnail: {}", e))?;
+                            fs::create_dir_all(output_dir.join(THUMBNAIL_DIR))?;
+                            fs::write(output_dir.join(&thumbnail), png)?;
+                            thumbnails.push(output_dir.join(&thumbnail));
+                            Some(thumbnail)
+                        }
+                        None => None,
+                    };
+                    if options.gallery {
+                        entries.push(gallery_entry(job, thumbnail.as_deref(), options));
+                    }
+                }
+            }
+            let elapsed = match rendered.deferred {
+                Some(_) => written.as_secs_f64(),
+                None => rendered.elapsed.as_secs_f64(),
+            };
+            if summary.slowest.is_none() || elapsed > summary.slowest_duration {
+                summary.slowest = Some(job.file_name.clone());
+                summary.slowest_duration = elapsed;
+            }
+            Ok(())
+        },
     );
-    // Execute the export command.
-    let _output = Command::new(program).args(args).output().expect(&error_msg);
-}
 
-/// Exports an SVG file to a PDF with CairoSVG.
-///
-/// Exports an SVG `src` file as a PDF with the same name.
-///
-/// The export is done using CairoSVG. If CairoSVG is not found, this function
-/// will panic.
-pub fn export_with_cairosvg(srcs: &[PathBuf]) {
-    for src in srcs {
-        // Prepare the input/output values from the src argument.
-        let (in_svg, out_pdf) = get_in_out_file(src);
+    let (skipped, journal) = match rendered {
+        Ok(rendered) => rendered,
+        Err(e) if out_of_space || is_storage_full(&e) => {
+            // Leave no truncated file behind.
+            drop(pool);
+            for file in &created {
+                let _ = fs::remove_file(file);
+                let _ = fs::remove_file(file.with_extension("pdf"));
+            }
+            crate::note!("Removed the {} file(s) written by the run", created.len());
+            return Err(e);
+        }
+        Err(e) => return Err(e),
+    };
+    summary.skipped = skipped;
 
-        // Prepare the command.
-        let program = "cairosvg";
-        let args = vec![
-            "-f".to_owned(),
-            "pdf".to_owned(),
-            "-o".to_owned(),
-            out_pdf,
-            in_svg,
-        ];
+    // Wait for the conversions to pdf.
+    pool.finish();
+    summary.exported = pdfs.len();
+
+    // Optimize the newly exported files, and make them conform to a standard.
+    let exported: Vec<PathBuf> = exports
+        .iter()
+        .map(|src| src.with_extension("pdf"))
+        .filter(|pdf| pdf.is_file())
+        .collect();
+    if let Some(optimization) = options.optimize_pdf {
+        if !exported.is_empty() {
+            let (before, after) = optimize(&exported, optimization)?;
+            crate::note!(
+                "Optimized {} file(s) from {} KiB to {} KiB",
+                exported.len(),
+                before / 1024,
+                after / 1024
+            );
+        }
+    }
+    let finished: Vec<(PathBuf, Metadata)> = exported
+        .iter()
+        .map(|pdf| (pdf.clone(), metadata.remove(pdf).unwrap_or_default()))
+        .collect();
+    finish(
+        &finished,
+        options.pdf_standard,
+        options.icc_profile.as_deref(),
+    )?;
+    if let Some(standard) = options.pdf_standard {
+        if !exported.is_empty() {
+            match verify(&exported, standard)? {
+                Some(failed) if !failed.is_empty() => {
+                    return Err(eyre!(
+                        "{} file(s) do not conform to {}: {}",
+                        failed.len(),
+                        standard,
+                        failed
+                            .iter()
+                            .map(|pdf| pdf.display().to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ));
+                }
+                Some(_) => crate::note!("Verified {} file(s) with veraPDF", exported.len()),
+                None => crate::note!("The {} conformance was not verified", standard),
+            }
+        }
+    }
+    if encrypted && !exported.is_empty() {
+        // Check all the passwords first, so that the files are either all
+        // encrypted or not at all.
+        let passwords = produced
+            .iter()
+            .filter(|(_, output, _)| exported.contains(output))
+            .map(|(_, pdf, record)| {
+                options
+                    .pdf_password_column
+                    .as_ref()
+                    .and_then(|column| record.get(column))
+                    .map(|value| value.trim())
+                    .filter(|value| !value.is_empty())
+                    .or(options.pdf_password.as_deref())
+                    .map(|password| (pdf, password))
+                    .ok_or_else(|| eyre!("No password to encrypt {}", pdf.display()))
+            })
+            .collect::<Result<Vec<_>, Report>>()?;
+        for (pdf, password) in &passwords {
+            encrypt(pdf, password)?;
+        }
+        crate::note!("Encrypted {} file(s)", passwords.len());
+    }
+    if let Some(sign_options) = &options.sign {
+        for pdf in &exported {
+            sign(pdf, sign_options)?;
+        }
+        if !exported.is_empty() {
+            crate::note!("Signed {} file(s)", exported.len());
+        }
+    }
 
-        export_with(program, &args);
+    // Journal the exported files, now that they are processed. A failed export
+    // must not be journaled.
+    if let Some(journal) = &journal {
+        for (key, file_name, pdf) in &journaled {
+            if pdf.is_file() {
+                journal.record(key, file_name)?;
+            }
+        }
     }
-}
 
-pub fn export_with_svg2pdf(srcs: &[PathBuf]) {
-    for src in srcs {
-        // Prepare the input/output values from the src argument.
-        let (in_svg, _out_pdf) = get_in_out_file(src);
+    // Append the pages of the new files to the report, in the order of the
+    // records.
+    if let Some(report) = &options.append_to {
+        let (pdfs, entries): (Vec<PathBuf>, Vec<LedgerEntry>) = produced
+            .iter()
+            .filter(|(_, pdf, _)| pdf.extension().is_some_and(|e| e == "pdf") && pdf.is_file())
+            .map(|(svg, pdf, record)| {
+                let entry = LedgerEntry {
+                    name: relative_path(svg, output_dir),
+                    pages: page_count(pdf)?,
+                    title: bookmark_title(svg, record, options),
+                };
+                Ok((pdf.clone(), entry))
+            })
+            .collect::<Result<Vec<_>, Report>>()?
+            .into_iter()
+            .unzip();
+        if pdfs.is_empty() {
+            crate::note!("No new file to append to {}", report.display());
+        } else {
+            let backup = append(report, &pdfs, &entries, options.navigation)?;
+            record_ledger(report, &entries)?;
+            match backup {
+                Some(backup) => crate::note!(
+                    "Appended {} file(s) to {}, the original is kept in {}",
+                    pdfs.len(),
+                    report.display(),
+                    backup.display()
+                ),
+                None => crate::note!("Created {} from {} file(s)", report.display(), pdfs.len()),
+            }
+        }
+        if let Some(booklet) = options.booklet.filter(|_| report.is_file()) {
+            let booklet = impose(report, booklet)?;
+            crate::note!("Imposed the booklet {}", booklet.display());
+        }
+    }
 
-        // Prepare the command.
-        let program = "svg2pdf";
-        let args = vec![in_svg];
+    if resumed > 0 {
+        crate::note!("Resumed {} file(s) from the interrupted run", resumed);
+    }
 
-        export_with(program, &args);
+    // Cache the newly generated files.
+    if let Some(cache) = &cache {
+        if reused > 0 {
+            crate::note!("Reused {} cached file(s)", reused);
+        }
+        for (file, key, exported) in &fresh {
+            // A failed export must not be cached.
+            if !exported || file.with_extension("pdf").is_file() {
+                cache.store(key, file, *exported)?;
+            }
+        }
     }
-}
 
-/// Get the input and output string representations of the provided file.
-fn get_in_out_file<P>(src: P) -> (String, String)
-where
-    P: AsRef<Path>,
-{
-    let in_svg = src
-        .as_ref()
-        .to_str()
-        .expect("The src file path is not valid UTF-8.");
-    let dest = src.as_ref().with_extension("pdf");
-    let out_pdf = dest
-        .to_str()
-        .expect("The dest file path is not valid UTF-8");
+    // Run the post command for each produced file.
+    let mut post_cmd_failures = 0;
+    if let Some(post_cmd) = &options.post_cmd {
+        for (input, output, record) in &produced {
+            if let Err(e) = post_cmd.run(input, output, record) {
+                let failure = format!("Post command failed for {}: {}", output.display(), e);
+                crate::note!("{}", failure);
+                summary.failures.push(failure);
+                post_cmd_failures += 1;
+            }
+        }
+    }
 
-    (in_svg.into(), out_pdf.into())
+    // Print the exported files.
+    if let Some(print_options) = &options.print {
+        print(&pdfs, print_options)?;
+        summary.printed = pdfs.len();
+        crate::note!("Printed {} file(s)", pdfs.len());
+    }
+
+    // Generate the gallery of the rendered files.
+    if options.gallery && options.single_output.is_none() {
+        let title = svg_template
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        fs::write(output_dir.join("index.html"), gallery(&title, &entries)?)?;
+    }
+
+    // Email the final artifacts.
+    let mut email_failures = 0;
+    if let Some(email_options) = &options.email {
+        for (i, email) in emails.iter().enumerate() {
+            if email_options.dry_run {
+                crate::note!("Would send {}", describe(email));
+                continue;
+            }
+            if i > 0 {
+                thread::sleep(email_options.delay());
+            }
+            match send(email, email_options) {
+                Ok(()) => summary.emailed += 1,
+                Err(e) => {
+                    let failure = format!("Cannot email {}: {}", email.to, e);
+                    crate::note!("{}", failure);
+                    summary.failures.push(failure);
+                    email_failures += 1;
+                }
+            }
+        }
+        if !email_options.dry_run {
+            crate::note!(
+                "Emailed {} record(s), {} failure(s)",
+                summary.emailed,
+                email_failures
+            );
+        }
+    }
+
+    // Record the checksums of the final artifacts in the manifest.
+    artifacts.extend(thumbnails);
+    if options.gallery && options.single_output.is_none() {
+        artifacts.push(output_dir.join("index.html"));
+    }
+    let mut manifest = Manifest::default();
+    if options.checksums {
+        for artifact in &artifacts {
+            manifest.files.push(ManifestFile {
+                path: relative_path(artifact, output_dir),
+                url: None,
+                sha256: Some(sha256(artifact)?),
+            });
+        }
+    }
+
+    // Upload the final artifacts, and record their URLs in the manifest.
+    let mut upload_failures = 0;
+    if let Some(upload_options) = &options.upload {
+        let uploads = upload(&artifacts, output_dir, upload_options);
+        crate::note!(
+            "Uploaded {} file(s), {} failure(s)",
+            uploads.uploaded.len(),
+            uploads.failed.len()
+        );
+        for (path, reason) in &uploads.failed {
+            let failure = format!("Cannot upload {}: {}", path.display(), reason);
+            crate::note!("{}", failure);
+            summary.failures.push(failure);
+        }
+        summary.uploaded = uploads.uploaded.len();
+        for uploaded in uploads.uploaded {
+            let path = relative_path(&uploaded.path, output_dir);
+            match manifest.files.iter_mut().find(|file| file.path == path) {
+                Some(file) => file.url = Some(uploaded.url),
+                None => manifest.files.push(ManifestFile {
+                    path,
+                    url: Some(uploaded.url),
+                    sha256: None,
+                }),
+            }
+        }
+        upload_failures = uploads.failed.len();
+    }
+    if (options.checksums || options.upload.is_some()) && options.single_output.is_none() {
+        let path = manifest.write(output_dir)?;
+        summary.manifest = Some(path.display().to_string());
+    }
+    if upload_failures > 0 {
+        return Err(eyre!("{} file(s) failed to upload", upload_failures));
+    }
+    if email_failures > 0 {
+        return Err(eyre!("{} email(s) failed to send", email_failures));
+    }
+    if post_cmd_failures > 0 {
+        return Err(eyre!("{} post command(s) failed", post_cmd_failures));
+    }
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Format a path relatively to a directory, with forward slashes.
+#[cfg(feature = "native")]
+fn relative_path(path: &Path, dir: &Path) -> String {
+    path.strip_prefix(dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
 
-    #[test]
-    fn test_get_in_out_file() {
-        let src = PathBuf::from("brochure.svg");
-        let (in_svg, out_pdf) = get_in_out_file(src);
+/// Title of the bookmark of an appended file, made of the values of its naming
+/// fields, or of its name when the files are not named after specific fields.
+#[cfg(feature = "native")]
+fn bookmark_title(svg: &Path, record: &Record, options: &RenderOptions) -> String {
+    match &options.field_based_name {
+        Some(names) => names
+            .iter()
+            .map(|name| record.get(name).map_or("", String::as_str))
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => svg
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
+
+/// Describe the output of a job in the gallery.
+///
+/// The key fields of the record are its naming fields, or all its fields when
+/// the files are not named after specific fields.
+#[cfg(feature = "native")]
+fn gallery_entry(job: &Job, thumbnail: Option<&Path>, options: &RenderOptions) -> Entry {
+    let mut fields: Vec<(String, String)> = match &options.field_based_name {
+        Some(names) => names
+            .iter()
+            .map(|name| (name.clone(), job.record[name].clone()))
+            .collect(),
+        None => {
+            let mut fields: Vec<_> = job
+                .record
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            fields.sort();
+            fields
+        }
+    };
+    if let Some(theme) = job.variant.theme {
+        fields.push((String::from("theme"), theme.name.clone()));
+    }
+    if let Some(locale) = &job.variant.locale {
+        fields.push((String::from("locale"), locale.clone()));
+    }
+
+    Entry {
+        file_name: job.file_name.clone(),
+        thumbnail: thumbnail.map(|path| path.to_string_lossy().into_owned()),
+        pdf: job
+            .exporter
+            .map(|_| Path::new(&job.file_name).with_extension("pdf"))
+            .map(|pdf| pdf.to_string_lossy().into_owned()),
+        fields,
+    }
+}
+
+/// Render an SVG template with the provided records, in memory.
+///
+/// The rendered SVG documents are returned along with their file names, in
+/// rendering order. Nothing is written, and the exporter is not run.
+#[cfg(feature = "native")]
+pub fn render_to_strings(
+    svg_template: &Path,
+    records: &[Record],
+    options: &RenderOptions,
+) -> Result<Vec<(String, String)>, Report> {
+    let mut outputs = Vec::new();
+    render_jobs(
+        svg_template,
+        records,
+        options,
+        || Ok(None),
+        |job, rendered, _| {
+            outputs.push((job.file_name.clone(), rendered.full_svg()?.into_owned()));
+            Ok(())
+        },
+    )?;
+
+    Ok(outputs)
+}
+
+/// Size of the files of a directory and of its subdirectories, in bytes.
+///
+/// The files which cannot be read are not counted.
+#[cfg(feature = "native")]
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Render the template for each job, and pass the results to the `output`
+/// callback, along with their jobs and the journal.
+///
+/// The journal is opened with `open_journal` once the outputs are planned and
+/// checked, nothing being changed on disk before. The outputs recorded in the
+/// journal of a resumed run, or found in the cache, are not rendered again.
+/// The number of records left out by the selection is returned, along with the
+/// journal, for the outputs processed after their rendering.
+#[cfg(feature = "native")]
+fn render_jobs<J, F>(
+    svg_template: &Path,
+    records: &[Record],
+    options: &RenderOptions,
+    open_journal: J,
+    mut output: F,
+) -> Result<(usize, Option<Journal>), Report>
+where
+    J: FnOnce() -> Result<Option<Journal>, Report>,
+    F: FnMut(&Job, Rendered, Option<&Journal>) -> Result<(), Report>,
+{
+    let template_dir = svg_template.parent().unwrap_or_else(|| Path::new(""));
+
+    // Load the template.
+    let source = match &options.template_source {
+        Some(template_source) => template_source.load(&svg_template.to_string_lossy())?,
+        None => read_template(svg_template)?,
+    };
+
+    // Without post-processing, the rendered files are streamed to their
+    // destination instead of being held in memory.
+    let stream = streams(options);
+
+    // Keep the large static blocks out of the compiled template and of the
+    // rendered files, until they are written.
+    let (source, blobs) = match options.large_template {
+        true => {
+            let (source, blobs) = Blobs::extract(source.into_bytes())?;
+            (source, Some(Arc::new(blobs)))
+        }
+        false => (source, None),
+    };
+    let name = svg_template
+        .file_name()
+        .expect("Invalid template name.")
+        .to_str()
+        .unwrap();
+    let mut env = environment(options);
+    env.add_template(name, &source)
+        .map_err(template_error(&source))?;
+    let tmpl = env.get_template(name).unwrap();
+
+    // Reshape the records, and combine them with the variants.
+    let pivoted;
+    let records = match &options.pivot {
+        Some(options) => {
+            pivoted = pivot(records, options)?;
+            &pivoted
+        }
+        None => records,
+    };
+    let crossed;
+    let records = match &options.cross {
+        Some(variants) => {
+            crossed = cross(records, variants)?;
+            &crossed
+        }
+        None => records,
+    };
+
+    // Add the computed columns, which can be selected, sorted and validated
+    // like the other ones.
+    let computed;
+    let records = if options.computed.is_empty() {
+        records
+    } else {
+        computed = compute(&env, records, &options.computed)?;
+        &computed
+    };
+
+    // Select the records and plan the outputs.
+    let mut indices: Vec<usize> = (0..records.len()).collect();
+    if let Some(schema) = &options.schema {
+        let violations = schema.validate(records);
+        for violation in &violations {
+            crate::report(violation.to_string());
+        }
+        if !violations.is_empty() {
+            if options.on_invalid == OnInvalid::Fail {
+                return Err(eyre!(
+                    "The data violate the schema ({} violation(s))",
+                    violations.len()
+                ));
+            }
+            indices.retain(|index| violations.iter().all(|v| v.row != index + 1));
+        }
+    }
+    let indices = select(records, indices, &options.only)?;
+    let (indices, dropped) = dedupe(records, indices, &options.dedupe_by, options.dedupe_keep)?;
+    if dropped > 0 {
+        crate::note!("Dropped {} duplicate record(s)", dropped);
+    }
+    let indices = sort(records, indices, &options.sort_by)?;
+    let indices = slice(indices, options.offset, options.limit);
+    let indices = match options.sample {
+        Some(size) => sample(indices, size, options.seed),
+        None => indices,
+    };
+    let skipped = match &options.pick {
+        Some(pick) => records.len() - indices.iter().filter(|i| pick.contains(i)).count(),
+        None => records.len() - indices.len(),
+    };
+
+    // The ranks and the running aggregates follow the order of the selection,
+    // e.g. the records sorted by score.
+    let aggregates = match options.running.is_empty() {
+        true => Vec::new(),
+        false => running(records, &indices, &options.running)?,
+    };
+    let positions: HashMap<usize, usize> = match options.expose_rank || !aggregates.is_empty() {
+        true => indices
+            .iter()
+            .enumerate()
+            .map(|(i, &index)| (index, i))
+            .collect(),
+        false => HashMap::new(),
+    };
+    let mut jobs = plan(records, &indices, options)?;
+    if let Some(pick) = &options.pick {
+        jobs.retain(|job| pick.contains(&(job.number - 1)));
+    }
+    if let Some(max) = options.max_outputs.filter(|max| jobs.len() > *max) {
+        return Err(eyre!(
+            "The run would produce {} outputs, more than the maximum of {}, raise it with --max-outputs if this is intended",
+            jobs.len(),
+            max
+        ));
+    }
+    if options.single_output.is_some() && jobs.len() != 1 {
+        return Err(eyre!(
+            "A single output was expected, but the selection produces {} outputs, narrow it down with --only",
+            jobs.len()
+        ));
+    }
+    if let Some(report) = &options.append_to {
+        let appended = read_ledger(report)?;
+        let planned = jobs.len();
+        jobs.retain(|job| !appended.contains(&job.file_name));
+        if jobs.len() < planned {
+            crate::note!(
+                "Skipped {} file(s) already appended to {}",
+                planned - jobs.len(),
+                report.display()
+            );
+        }
+    }
+
+    if let Some(column) = options
+        .json_columns
+        .iter()
+        .find(|column| records.iter().any(|record| !record.contains_key(*column)))
+    {
+        return Err(eyre!(
+            "Unknown column {:?} for --parse-json-columns",
+            column
+        ));
+    }
+
+    // The records are exposed to the templates only on demand, as they make
+    // every context as large as the whole data file.
+    if let Some(column) = &options.siblings_by {
+        if records.iter().any(|record| !record.contains_key(column)) {
+            return Err(eyre!("Unknown column {:?} for --siblings-by", column));
+        }
+    }
+    let values: Vec<Value> =
+        if options.expose_all || options.siblings_by.is_some() || options.expose_neighbors {
+            records.iter().map(Value::from_serializable).collect()
+        } else {
+            Vec::new()
+        };
+
+    // Every check passed, the outputs can be produced.
+    let opened = open_journal()?;
+    let journal = opened.as_ref();
+
+    let cache = options.cache_dir.as_deref().map(Cache::new);
+    let render_job = |i: usize, stream: bool| {
+        let job = &jobs[i];
+        let mut context = job_context(job, options)?;
+        add_related(&mut context, records, &values, job.number - 1, options);
+        let position = positions.get(&(job.number - 1)).copied();
+        add_ranking(&mut context, position, &aggregates, options);
+        let key = match (&cache, journal) {
+            (None, None) => None,
+            _ => Some(job_key(&source, &context, job.exporter, options)?),
+        };
+        let found = journal
+            .zip(key.as_deref())
+            .and_then(|(journal, key)| journal.get(key, &job.file_name))
+            .map(|svg| (svg, Origin::Resumed))
+            .or_else(|| {
+                cache
+                    .as_ref()
+                    .zip(key.as_deref())
+                    .and_then(|(cache, key)| cache.get(key, job.exporter.is_some()))
+                    .map(|svg| (svg, Origin::Cached))
+            });
+        let describe = || {
+            let values = job
+                .record
+                .iter()
+                .map(|(column, value)| format!("{}={:?}", column, value))
+                .collect::<Vec<_>>();
+            format!(
+                "Record {} ({}) rendered as {}",
+                job.number,
+                values.join(", "),
+                job.file_name
+            )
+        };
+        let start = Instant::now();
+        let mut deferred: Option<Deferred> = None;
+        let (svg, origin) = match found {
+            Some(found) => found,
+            None if stream && !has_accessibility(&context, options) => {
+                let tmpl = &tmpl;
+                deferred = Some(Box::new(move |out: &mut dyn Write| {
+                    tmpl.render_to_write(&context, out)
+                        .map(|_| ())
+                        .map_err(template_error(tmpl.source()))
+                        .wrap_err_with(describe)
+                }));
+                (String::new(), Origin::Rendered)
+            }
+            None => (
+                render_svg(&tmpl, &context, template_dir, options).wrap_err_with(describe)?,
+                Origin::Rendered,
+            ),
+        };
+        let elapsed = match (origin, &deferred) {
+            (Origin::Rendered, None) => start.elapsed(),
+            _ => Duration::ZERO,
+        };
+        if let (Some(profiler), Origin::Rendered, None) = (&options.profiler, origin, &deferred) {
+            profiler.record(Stage::Render, &job.file_name, start);
+        }
+        Ok::<_, Report>(Rendered {
+            svg,
+            deferred,
+            key,
+            origin,
+            elapsed,
+            blobs: blobs.clone(),
+            left: jobs.len() - i - 1,
+            validate: options.validate,
+        })
+    };
+
+    let workers = options.render_jobs.min(jobs.len());
+    if workers <= 1 {
+        for (i, job) in jobs.iter().enumerate() {
+            output(job, render_job(i, stream)?, journal)?;
+        }
+        return Ok((skipped, opened));
+    }
+
+    // Render on several threads, each taking the next job when it is done,
+    // and pass the results to the callback in order. The rendered files are
+    // held in memory until their turn comes.
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..workers {
+            let (sender, next, jobs, render_job) = (sender.clone(), &next, &jobs, &render_job);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                // Stop when the jobs are done, or when the run failed.
+                if i >= jobs.len() || sender.send((i, render_job(i, false))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        let mut done = BTreeMap::new();
+        let mut expected = 0;
+        for (i, rendered) in receiver {
+            done.insert(i, rendered);
+            while let Some(rendered) = done.remove(&expected) {
+                output(&jobs[expected], rendered?, journal)?;
+                expected += 1;
+            }
+        }
+        Ok::<_, Report>(())
+    })?;
+
+    Ok((skipped, opened))
+}
+
+/// Check whether the rendered files can be streamed to their destination, i.e.
+/// whether no step of the rendering needs the whole document.
+///
+/// The streamed files are validated once written, except on the standard
+/// output. The accessibility metadata, which depend on the records, are
+/// checked per record with [`has_accessibility`].
+#[cfg(feature = "native")]
+fn streams(options: &RenderOptions) -> bool {
+    (!options.validate || options.single_output != Some(SingleOutput::Stdout))
+        && !options.toggle_layers
+        && !options.strip_metadata
+        && options.stylesheet.is_none()
+        && !options.embed_images
+        && options.page_size.is_none()
+        && options.scale.is_none()
+        && !matches!(options.export_area, Some(ExportArea::Custom { .. }))
+        && options.watermark_text.is_none()
+        && options.overlay.is_none()
+        && options.thumbnails.is_none()
+        && !options.large_template
+}
+
+/// Check whether a document gets accessibility metadata or alternative texts.
+#[cfg(feature = "native")]
+fn has_accessibility(context: &Context, options: &RenderOptions) -> bool {
+    let (metadata, alts) = accessibility(context, options);
+    !metadata.is_empty() || !alts.is_empty()
+}
+
+/// Compute the key identifying the output of a job, in the cache and in the
+/// journal.
+///
+/// The key covers the template, the context of the job and the options
+/// affecting the rendered and exported files. The files linked from the
+/// template, such as the icons and the images, are not covered.
+#[cfg(feature = "native")]
+fn job_key(
+    source: &str,
+    context: &Context,
+    exporter: Option<Exporter>,
+    options: &RenderOptions,
+) -> Result<String, Report> {
+    // The options are written with their `Debug` representations, derived
+    // from their definitions.
+    let rendering = format!(
+        "{:?}",
+        (
+            options.autoescape,
+            options.trim_blocks,
+            options.lstrip_blocks,
+            options.toggle_layers,
+            options.strip_metadata,
+            &options.stylesheet,
+            options.embed_images,
+            options.page_size,
+            options.page_fit,
+            options.scale,
+            &options.watermark_text,
+            &options.overlay,
+        )
+    );
+    let accessibility = format!(
+        "{:?}",
+        (
+            &options.title_column,
+            &options.lang,
+            &options.lang_column,
+            &options.image_alts,
+        )
+    );
+    let export = format!(
+        "{:?}",
+        (
+            exporter,
+            options.export_area,
+            options.optimize_pdf,
+            options.pdf_standard,
+            &options.icc_profile,
+            &options.pdf_password,
+            &options.pdf_password_column,
+            options.sign.as_ref().map(|sign| &sign.cert),
+        )
+    );
+
+    Ok(digest(&[
+        env!("CARGO_PKG_VERSION"),
+        source,
+        // The context is a sorted map, serialized in a stable order.
+        &serde_json::to_string(context)?,
+        &rendering,
+        &accessibility,
+        &export,
+    ]))
+}
+
+/// Add the related records to the context of the record at `index`: all the
+/// records as `_all`, the other records of its group as `_siblings`, and the
+/// records around it as `_prev` and `_next`.
+///
+/// The `values` are the records converted for the templates.
+#[cfg(feature = "native")]
+fn add_related(
+    context: &mut Context,
+    records: &[Record],
+    values: &[Value],
+    index: usize,
+    options: &RenderOptions,
+) {
+    if options.expose_all {
+        context.insert("_all".to_owned(), Value::from(values.to_vec()));
+    }
+    if let Some(column) = &options.siblings_by {
+        let group = &records[index][column];
+        let siblings = records
+            .iter()
+            .zip(values)
+            .enumerate()
+            .filter(|(i, (record, _))| *i != index && record[column] == *group)
+            .map(|(_, (_, value))| value.clone())
+            .collect::<Vec<_>>();
+        context.insert("_siblings".to_owned(), Value::from(siblings));
+    }
+    if options.expose_neighbors {
+        let neighbor = |i: Option<usize>| {
+            i.and_then(|i| values.get(i))
+                .cloned()
+                .unwrap_or_else(|| Value::from(()))
+        };
+        context.insert("_prev".to_owned(), neighbor(index.checked_sub(1)));
+        context.insert("_next".to_owned(), neighbor(Some(index + 1)));
+    }
+}
+
+/// Add the rank of the record at `position` in the selection to its context as
+/// `_rank`, and its running aggregates as `_running`.
+#[cfg(feature = "native")]
+fn add_ranking(
+    context: &mut Context,
+    position: Option<usize>,
+    aggregates: &[Value],
+    options: &RenderOptions,
+) {
+    let Some(position) = position else {
+        return;
+    };
+    if options.expose_rank {
+        context.insert("_rank".to_owned(), Value::from(position + 1));
+    }
+    if let Some(aggregates) = aggregates.get(position) {
+        context.insert("_running".to_owned(), aggregates.clone());
+    }
+}
+
+/// Plan the rendering jobs.
+///
+/// Each variant of each selected record becomes a job, named after the naming
+/// fields of the record and the suffixes of the variant.
+#[cfg(feature = "native")]
+fn plan<'a>(
+    records: &'a [Record],
+    indices: &[usize],
+    options: &'a RenderOptions,
+) -> Result<Vec<Job<'a>>, Report> {
+    // Set the separator.
+    let sep = options.separator.as_deref().unwrap_or("-");
+    check_separator(sep)?;
+
+    let mut jobs = Vec::new();
+    for &index in indices {
+        let record = &records[index];
+        let exporter =
+            record_exporter(options, record).wrap_err_with(|| format!("Record {}", index + 1))?;
+
+        // Construct the name of the output file.
+        let item_name = item_name(record, index + 1, options, sep)
+            .wrap_err_with(|| format!("Record {}", index + 1))?;
+
+        // Render each record once per variant.
+        for variant in variants(options, record) {
+            let mut file_name = item_name.clone();
+            for suffix in variant.suffixes() {
+                file_name.push_str(sep);
+                file_name.push_str(&suffix.to_lowercase());
+            }
+            let mut file_name = normalize_name(&file_name, options.ascii_names);
+            file_name.push_str(".svg");
+            jobs.push(Job {
+                number: index + 1,
+                record,
+                variant,
+                file_name,
+                exporter,
+            });
+        }
+    }
+
+    // Report the collisions before producing any file, including the ones
+    // which only happen on the case-insensitive file systems.
+    let mut names: HashMap<String, (usize, String)> = HashMap::new();
+    for job in &mut jobs {
+        if let Some((number, name)) = names.get(&fold_case(&job.file_name)) {
+            if options.disambiguate_names {
+                let stem = job.file_name.trim_end_matches(".svg").to_owned();
+                job.file_name = (2..)
+                    .map(|n| format!("{}{}{}.svg", stem, sep, n))
+                    .find(|name| !names.contains_key(&fold_case(name)))
+                    .unwrap();
+            } else if *name == job.file_name {
+                return Err(eyre!(
+                    "Records {} and {} are both named {:?}, use --field to name them differently",
+                    number,
+                    job.number,
+                    name
+                ));
+            } else {
+                crate::note!(
+                    "Warning: records {} and {} are named {:?} and {:?}, which collide on case-insensitive file systems",
+                    number, job.number, name, job.file_name
+                );
+            }
+        }
+        names.insert(
+            fold_case(&job.file_name),
+            (job.number, job.file_name.clone()),
+        );
+    }
+
+    Ok(jobs)
+}
+
+/// Fold the case of a file name, the way the case-insensitive file systems
+/// compare the names: `Straße` and `STRASSE` are the same name.
+#[cfg(feature = "native")]
+fn fold_case(name: &str) -> String {
+    name.to_uppercase().to_lowercase().nfkc().collect()
+}
+
+/// Characters which are not allowed in file names, on Windows at least.
+#[cfg(feature = "native")]
+const ILLEGAL_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Check that a separator can be used in file names.
+#[cfg(feature = "native")]
+fn check_separator(sep: &str) -> Result<(), Report> {
+    if sep.is_empty() {
+        return Err(eyre!("The separator cannot be empty"));
+    }
+    if sep.contains(ILLEGAL_CHARS) || sep.contains(char::is_control) {
+        return Err(eyre!(
+            "The separator {:?} contains characters which are not allowed in file names",
+            sep
+        ));
+    }
+
+    Ok(())
+}
+
+/// Normalize a file name to the NFC form, so that the accented letters are
+/// encoded the same way on every platform, or transliterate it to ASCII.
+///
+/// The transliteration may produce characters which are not allowed in file
+/// names, e.g. `½` becomes `1/2`: they are replaced with underscores.
+#[cfg(feature = "native")]
+fn normalize_name(name: &str, ascii: bool) -> String {
+    if ascii {
+        deunicode(name)
+            .replace(
+                |c: char| c == ' ' || ILLEGAL_CHARS.contains(&c) || c.is_control(),
+                "_",
+            )
+            .to_lowercase()
+    } else {
+        name.nfc().collect()
+    }
+}
+
+/// Build the name of a record, from the values of its naming fields joined
+/// with the separator, or from its first column.
+///
+/// The empty fields are handled according to `options.on_empty_name`, `row`
+/// being the row number of the record.
+#[cfg(feature = "native")]
+fn item_name(
+    record: &Record,
+    row: usize,
+    options: &RenderOptions,
+    sep: &str,
+) -> Result<String, Report> {
+    let fields = match &options.field_based_name {
+        Some(fields) => fields
+            .iter()
+            .map(|field| match record.get(field) {
+                Some(value) => Ok((field.as_str(), value.as_str())),
+                None => Err(eyre!("Unknown naming field {:?}", field)),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        // Use the first column of the data file.
+        None => record
+            .get_index(0)
+            .map(|(field, value)| (field.as_str(), value.as_str()))
+            .into_iter()
+            .collect(),
+    };
+    let parts = fields
+        .into_iter()
+        .map(|(field, value)| match value.trim().is_empty() {
+            false => Ok(value.replace(' ', "_")),
+            true => match options.on_empty_name {
+                OnEmptyName::Fail => Err(eyre!(
+                    "Empty naming field {:?}, use --on-empty-name to name the record anyway",
+                    field
+                )),
+                OnEmptyName::Placeholder => Ok(options.name_placeholder.replace(' ', "_")),
+                OnEmptyName::Index => Ok(row.to_string()),
+            },
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let name = parts.join(sep).to_lowercase();
+    if name.contains(['/', '\\', '\0']) {
+        return Err(eyre!("The name {:?} is not a valid file name", name));
+    }
+
+    Ok(name)
+}
+
+/// Render the template with a context, and apply the post-rendering passes.
+#[cfg(feature = "native")]
+fn render_svg(
+    tmpl: &Template,
+    context: &Context,
+    template_dir: &Path,
+    options: &RenderOptions,
+) -> Result<String, Report> {
+    let mut rendered = tmpl
+        .render(context)
+        .map_err(template_error(tmpl.source()))?;
+    if options.validate {
+        validate(&rendered)?;
+    }
+    if options.toggle_layers {
+        rendered = toggle_layers(&rendered, context)?;
+    }
+    if options.strip_metadata {
+        rendered = strip_metadata(&rendered)?;
+    }
+    if let Some(css) = &options.stylesheet {
+        rendered = inject_css(&rendered, css)?;
+    }
+    if options.embed_images {
+        rendered = embed_images(&rendered, template_dir)?;
+    }
+    if options.page_size.is_some() || options.scale.is_some() {
+        rendered = resize(
+            &rendered,
+            options.page_size,
+            options.page_fit,
+            options.scale,
+        )?;
+    }
+    if let Some(ExportArea::Custom {
+        x,
+        y,
+        width,
+        height,
+    }) = options.export_area
+    {
+        rendered = crop(&rendered, x, y, width, height)?;
+    }
+    let (metadata, alts) = accessibility(context, options);
+    if !metadata.is_empty() || !alts.is_empty() {
+        rendered = annotate(
+            &rendered,
+            metadata.title.as_deref(),
+            metadata.lang.as_deref(),
+            &alts,
+        )?;
+    }
+    if let Some(text) = &options.watermark_text {
+        rendered = watermark(&rendered, text)?;
+    }
+    if let Some(svg) = &options.overlay {
+        rendered = overlay(&rendered, svg)?;
+    }
+
+    Ok(rendered)
+}
+
+/// Resolve the accessibility metadata of a document from its context, along
+/// with the alternative texts of its images by `id`.
+///
+/// The language is the locale of the variant, or the language of the record.
+#[cfg(feature = "native")]
+fn accessibility(
+    context: &Context,
+    options: &RenderOptions,
+) -> (Metadata, BTreeMap<String, String>) {
+    let value = |column: &str| {
+        context
+            .get(column)
+            .map(|value| value.to_string())
+            .filter(|value| !value.trim().is_empty())
+    };
+    let metadata = Metadata {
+        title: options.title_column.as_deref().and_then(value),
+        lang: value("locale")
+            .or_else(|| options.lang_column.as_deref().and_then(value))
+            .or_else(|| options.lang.clone()),
+    };
+    let alts = options
+        .image_alts
+        .iter()
+        .filter_map(|(id, column)| value(column).map(|alt| (id.clone(), alt)))
+        .collect();
+
+    (metadata, alts)
+}
+
+/// List the variants to render a record with.
+///
+/// A variant is created for each combination of theme and locale. Without any
+/// theme or locale, a single default variant is returned so that each record
+/// is still rendered once.
+#[cfg(feature = "native")]
+fn variants<'a>(options: &'a RenderOptions, record: &Record) -> Vec<Variant<'a>> {
+    let themes = if options.themes.is_empty() {
+        vec![None]
+    } else {
+        options.themes.iter().map(Some).collect()
+    };
+    let record_locales = options
+        .locale_column
+        .as_ref()
+        .and_then(|column| record.get(column))
+        .map(|value| split_list(value))
+        .filter(|locales| !locales.is_empty());
+    let locales = match record_locales {
+        Some(locales) => locales.into_iter().map(Some).collect(),
+        None if options.locales.is_empty() => vec![None],
+        None => options.locales.iter().cloned().map(Some).collect(),
+    };
+
+    themes
+        .iter()
+        .flat_map(|theme| {
+            locales.iter().map(|locale| Variant {
+                theme: *theme,
+                locale: locale.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Select the exporter of a record.
+///
+/// A non-empty value in the exporter column takes precedence over the default
+/// exporter.
+#[cfg(feature = "native")]
+fn record_exporter(options: &RenderOptions, record: &Record) -> Result<Option<Exporter>, Report> {
+    let name = options
+        .exporter_column
+        .as_ref()
+        .and_then(|column| record.get(column))
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty());
+    let exporter = match name {
+        Some(name) => Some(Exporter::from_str(name, true).map_err(|_| {
+            let names = Exporter::value_variants()
+                .iter()
+                .filter_map(|exporter| exporter.to_possible_value())
+                .map(|value| value.get_name().to_owned())
+                .collect::<Vec<String>>();
+            eyre!(
+                "Unknown exporter {:?} (expected {})",
+                name,
+                names.join(", ")
+            )
+        })?),
+        None => options.exporter,
+    };
+    if let (Some(exporter), Some(area)) = (exporter, options.export_area) {
+        if !exporter.supports(area) {
+            return Err(eyre!("{:?} cannot export the {:?} area", exporter, area));
+        }
+    }
+
+    Ok(exporter)
+}
+
+/// Split a comma-separated list, dropping the empty items.
+#[cfg(feature = "native")]
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Number of lines shown around the line of a template error.
+const SNIPPET_CONTEXT: usize = 2;
+
+/// Convert a template error into a report showing the lines of the template
+/// around the error, so that the broken markup can be found quickly.
+fn template_error(source: &str) -> impl Fn(minijinja::Error) -> Report + '_ {
+    move |e| match e.line() {
+        Some(line) => eyre!("{}\n{}", e, snippet(source, line)),
+        None => Report::new(e),
+    }
+}
+
+/// Extract the lines of a template around a line, starting at 1, which is
+/// marked with `>`.
+fn snippet(source: &str, line: usize) -> String {
+    let first = line.saturating_sub(SNIPPET_CONTEXT).max(1);
+    let width = (line + SNIPPET_CONTEXT).to_string().len();
+    source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| (index + 1, text))
+        .skip(first - 1)
+        .take(line + SNIPPET_CONTEXT + 1 - first)
+        .map(|(number, text)| {
+            let marker = if number == line { '>' } else { ' ' };
+            format!("{} {:>width$} | {}", marker, number, text, width = width)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Build the template context of a record.
+///
+/// The record values take precedence over the theme variables, and the locale
+/// of the variant takes precedence over the record values. The names of the
+/// columns are available in order as `_columns`.
+fn context(record: &Record, variant: &Variant) -> Context {
+    let mut context = Context::new();
+    if let Some(theme) = variant.theme {
+        context.extend(theme.variables.clone());
+        context.insert("_theme".to_owned(), Value::from(theme.name.clone()));
+    }
+    context.extend(
+        record
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::from(v.clone()))),
+    );
+    let columns = record.keys().cloned().map(Value::from).collect::<Vec<_>>();
+    context.insert("_columns".to_owned(), Value::from(columns));
+    if let Some(locale) = &variant.locale {
+        context.insert("locale".to_owned(), Value::from(locale.clone()));
+    }
+
+    context
+}
+
+/// Build the context of a job, transforming its record as the options ask.
+#[cfg(feature = "native")]
+fn job_context(job: &Job, options: &RenderOptions) -> Result<Context, Report> {
+    let mut context = context(job.record, &job.variant);
+    if let Some(mapper) = &options.record_mapper {
+        map_record(&mut context, job.record, mapper)?;
+    }
+    parse_json_columns(&mut context, job, &options.json_columns)?;
+    if options.nest_columns {
+        nest_columns(&mut context);
+    }
+
+    Ok(context)
+}
+
+/// Replace the columns of a record in its context with the entries of the map
+/// returned by the `mapper`.
+#[cfg(feature = "native")]
+fn map_record(context: &mut Context, record: &Record, mapper: &RecordMapper) -> Result<(), Report> {
+    let value = (mapper.0)(record.clone())?;
+    if value.kind() != minijinja::value::ValueKind::Map {
+        return Err(eyre!(
+            "The record mapper must return a map, not {:?}",
+            value.kind()
+        ));
+    }
+    for column in record.keys() {
+        context.remove(column);
+    }
+    let mut columns = Vec::new();
+    for key in value.try_iter()? {
+        context.insert(key.to_string(), value.get_item(&key)?);
+        columns.push(key);
+    }
+    context.insert("_columns".to_owned(), Value::from(columns));
+
+    Ok(())
+}
+
+/// Replace the JSON texts of the `columns` with the values they represent, so
+/// that the templates can iterate over the arrays and the objects.
+///
+/// The empty cells are left as is.
+#[cfg(feature = "native")]
+fn parse_json_columns(context: &mut Context, job: &Job, columns: &[String]) -> Result<(), Report> {
+    for column in columns {
+        let text = &job.record[column];
+        if text.trim().is_empty() {
+            continue;
+        }
+        let json: serde_json::Value = serde_json::from_str(text).map_err(|e| {
+            eyre!(
+                "Invalid JSON in the {} column of record {}: {}",
+                column,
+                job.number,
+                e
+            )
+        })?;
+        context.insert(column.clone(), Value::from_serializable(&json));
+    }
+
+    Ok(())
+}
+
+/// A level of the nested objects built from the dotted columns.
+#[cfg(feature = "native")]
+enum Node {
+    Value(Value),
+    Object(BTreeMap<String, Node>),
+}
+
+#[cfg(feature = "native")]
+impl From<Node> for Value {
+    fn from(node: Node) -> Self {
+        match node {
+            Node::Value(value) => value,
+            Node::Object(object) => Value::from(
+                object
+                    .into_iter()
+                    .map(|(key, node)| (key, Value::from(node)))
+                    .collect::<BTreeMap<_, _>>(),
+            ),
+        }
+    }
+}
+
+/// Insert a value at a path of keys, unless the path is already taken.
+#[cfg(feature = "native")]
+fn insert_node(object: &mut BTreeMap<String, Node>, keys: &[&str], value: &Value) {
+    match keys {
+        [] => (),
+        [key] => {
+            object
+                .entry((*key).to_owned())
+                .or_insert_with(|| Node::Value(value.clone()));
+        }
+        [key, rest @ ..] => {
+            let node = object
+                .entry((*key).to_owned())
+                .or_insert_with(|| Node::Object(BTreeMap::new()));
+            if let Node::Object(inner) = node {
+                insert_node(inner, rest, value);
+            }
+        }
+    }
+}
+
+/// Nest the values of the dotted columns into objects, so that the
+/// `scores.network` column is available as `{{ scores.network }}`.
+///
+/// The dotted columns are kept. A column named like an object, e.g. `scores`,
+/// takes precedence over it, and so does the first value of a path.
+#[cfg(feature = "native")]
+fn nest_columns(context: &mut Context) {
+    let mut root: BTreeMap<String, Node> = BTreeMap::new();
+    for (column, value) in context.iter() {
+        if let Some((first, _)) = column.split_once('.') {
+            if !context.contains_key(first) {
+                let keys: Vec<&str> = column.split('.').collect();
+                insert_node(&mut root, &keys, value);
+            }
+        }
+    }
+    context.extend(root.into_iter().map(|(key, node)| (key, node.into())));
+}
+
+/// Parse a record from a JSON object.
+///
+/// The values which are not strings are kept as JSON text.
+pub fn record_from_json(json: &str) -> Result<Record, Report> {
+    let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json)?;
+    Ok(object
+        .into_iter()
+        .map(|(k, v)| match v {
+            serde_json::Value::String(s) => (k, s),
+            serde_json::Value::Null => (k, String::new()),
+            v => (k, v.to_string()),
+        })
+        .collect())
+}
+
+/// Render the template using a record from the CSV file.
+///
+/// ```no_run
+/// # use color_eyre::{eyre::Report, Result};
+/// use svggloo::template::render_record;
+/// use std::collections::HashMap;
+///
+/// # fn main() -> Result<(), Report> {
+/// let template = "This is {{city}}.";
+/// let record = HashMap::from([("city", "Austin")]);
+/// let rendered = render_record(template, record)?;
+/// assert_eq!(rendered, String::from("This is Austin"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn render_record<S: Serialize>(template: &str, record: S) -> Result<String, Report> {
+    let name = "template";
+    let mut env = environment(&RenderOptions::default());
+    env.add_template(name, template)
+        .map_err(template_error(template))?;
+    let tmpl = env.get_template(name).unwrap();
+
+    // Render the template to file for this specific record.
+    tmpl.render(&record).map_err(template_error(template))
+}
+
+/// Render a template with a record, for an optional locale.
+///
+/// The context is built like in a batch rendering, but the post-rendering
+/// passes are not applied. This is meant for previews, e.g. in a browser.
+pub fn render_preview(
+    template: &str,
+    record: &Record,
+    locale: Option<String>,
+) -> Result<String, Report> {
+    let name = "template";
+    let mut env = environment(&RenderOptions::default());
+    env.add_template(name, template)
+        .map_err(template_error(template))?;
+    let tmpl = env.get_template(name).unwrap();
+    let context = context(
+        record,
+        &Variant {
+            theme: None,
+            locale,
+        },
+    );
+
+    tmpl.render(context).map_err(template_error(template))
+}
+
+/// Template read from the standard input, which can only be read once.
+#[cfg(feature = "native")]
+static STDIN_TEMPLATE: OnceLock<String> = OnceLock::new();
+
+/// Read a template file, or the standard input if the path is `-`.
+#[cfg(feature = "native")]
+pub fn read_template(svg_template: &Path) -> Result<String, Report> {
+    if svg_template != Path::new("-") {
+        return Ok(fs::read_to_string(svg_template)?);
+    }
+    if let Some(source) = STDIN_TEMPLATE.get() {
+        return Ok(source.clone());
+    }
+    let mut source = String::new();
+    io::stdin().read_to_string(&mut source)?;
+    Ok(STDIN_TEMPLATE.get_or_init(|| source).clone())
+}
+
+/// Render a template file using a record from the CSV file.
+#[cfg(feature = "native")]
+pub fn render_record_from_file<S: Serialize>(
+    svg_template: &Path,
+    record: S,
+) -> Result<String, Report> {
+    // Load the template.
+    let template = read_template(svg_template)?;
+
+    // Render it.
+    render_record(&template, record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "native")]
+    use crate::export::get_in_out_file;
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_get_in_out_file() {
+        let src = PathBuf::from("brochure.svg");
+        let (in_svg, out_pdf) = get_in_out_file(src);
         assert_eq!(in_svg, String::from("brochure.svg"));
         assert_eq!(out_pdf, String::from("brochure.pdf"));
     }
+
+    #[test]
+    fn test_render_record_escapes_values() {
+        let record = HashMap::from([("name", "Fish & <Chips>")]);
+        assert_eq!(
+            render_record("<text>{{ name }}</text>", &record).unwrap(),
+            "<text>Fish &amp; &lt;Chips&gt;</text>"
+        );
+        assert_eq!(
+            render_record("<text>{{ name|safe }}</text>", &record).unwrap(),
+            "<text>Fish & <Chips></text>"
+        );
+    }
+
+    #[test]
+    fn test_block_whitespace() {
+        let source = "<text>\n  {% if city %}\n  {{ city }}\n  {% endif %}\n</text>";
+        let record = Record::from([(String::from("city"), String::from("Austin"))]);
+        assert_eq!(
+            render_record(source, &record).unwrap(),
+            "<text>\n  Austin\n</text>"
+        );
+    }
+
+    #[test]
+    fn test_template_error() {
+        let source = "<svg>\n<g>\n<text>{{ city }</text>\n</g>\n</svg>\n<!-- end -->\n";
+        let error = render_record(source, Record::new()).unwrap_err();
+        let message = error.to_string();
+        assert!(message.ends_with(
+            "\n  1 | <svg>\n  2 | <g>\n> 3 | <text>{{ city }</text>\n  4 | </g>\n  5 | </svg>"
+        ));
+    }
+
+    #[test]
+    fn test_record_from_json() {
+        let record = record_from_json(r#"{"city": "Austin", "score": 4.5, "note": null}"#).unwrap();
+        assert_eq!(record["city"], "Austin");
+        assert_eq!(record["score"], "4.5");
+        assert_eq!(record["note"], "");
+        assert!(record_from_json("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_context_precedence() {
+        let theme = Theme {
+            name: String::from("dark"),
+            variables: BTreeMap::from([
+                (String::from("city"), Value::from("Nowhere")),
+                (String::from("background"), Value::from("#000")),
+            ]),
+        };
+        let record = Record::from([
+            (String::from("state"), String::from("TX")),
+            (String::from("city"), String::from("Austin")),
+        ]);
+        let variant = Variant {
+            theme: Some(&theme),
+            locale: Some(String::from("es")),
+        };
+        let context = context(&record, &variant);
+        assert_eq!(context["city"], Value::from("Austin"));
+        assert_eq!(context["background"], Value::from("#000"));
+        assert_eq!(context["_theme"], Value::from("dark"));
+        assert_eq!(context["locale"], Value::from("es"));
+        assert_eq!(context["_columns"], Value::from(vec!["state", "city"]));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_variants_with_locale_column() {
+        let options = RenderOptions {
+            locales: vec![String::from("en")],
+            locale_column: Some(String::from("languages")),
+            ..Default::default()
+        };
+        let record = Record::from([(String::from("languages"), String::from("es, fr"))]);
+        let locales = variants(&options, &record)
+            .into_iter()
+            .map(|v| v.locale.unwrap())
+            .collect::<Vec<String>>();
+        assert_eq!(locales, vec!["es", "fr"]);
+
+        let record = Record::from([(String::from("languages"), String::new())]);
+        assert_eq!(variants(&options, &record)[0].locale.as_deref(), Some("en"));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_accessibility() {
+        let options = RenderOptions {
+            title_column: Some(String::from("name")),
+            lang: Some(String::from("en")),
+            lang_column: Some(String::from("language")),
+            image_alts: vec![(String::from("logo"), String::from("logo_alt"))],
+            ..Default::default()
+        };
+        let record = Record::from([
+            (String::from("name"), String::from("Austin")),
+            (String::from("language"), String::new()),
+            (String::from("logo_alt"), String::from("City logo")),
+        ]);
+        let (metadata, alts) = accessibility(&context(&record, &Variant::default()), &options);
+        assert_eq!(metadata.title.as_deref(), Some("Austin"));
+        assert_eq!(metadata.lang.as_deref(), Some("en"));
+        assert_eq!(alts["logo"], "City logo");
+
+        let variant = Variant {
+            theme: None,
+            locale: Some(String::from("es")),
+        };
+        let (metadata, _) = accessibility(&context(&record, &variant), &options);
+        assert_eq!(metadata.lang.as_deref(), Some("es"));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_record_exporter() {
+        let options = RenderOptions {
+            exporter: Some(Exporter::CairoSVG),
+            exporter_column: Some(String::from("exporter")),
+            ..Default::default()
+        };
+        let record = Record::from([(String::from("exporter"), String::from("Inkscape"))]);
+        assert_eq!(
+            record_exporter(&options, &record).unwrap(),
+            Some(Exporter::Inkscape)
+        );
+        let record = Record::from([(String::from("exporter"), String::new())]);
+        assert_eq!(
+            record_exporter(&options, &record).unwrap(),
+            Some(Exporter::CairoSVG)
+        );
+        let record = Record::from([(String::from("exporter"), String::from("gimp"))]);
+        assert!(record_exporter(&options, &record).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_plan_names() {
+        let record = |city: &str, state: &str| {
+            Record::from([
+                (String::from("city"), String::from(city)),
+                (String::from("state"), String::from(state)),
+            ])
+        };
+        let records = [record("San Jose", "CA"), record("San Jose", "CA")];
+        let options = RenderOptions {
+            field_based_name: Some(vec![String::from("state"), String::from("city")]),
+            ..Default::default()
+        };
+        let jobs = plan(&records, &[0], &options).unwrap();
+        assert_eq!(jobs[0].file_name, "ca-san_jose.svg");
+        let error = plan(&records, &[0, 1], &options).err().unwrap();
+        assert!(error.to_string().starts_with("Records 1 and 2"));
+        let options = RenderOptions {
+            disambiguate_names: true,
+            ..options
+        };
+        let jobs = plan(&records, &[0, 1], &options).unwrap();
+        assert_eq!(jobs[1].file_name, "ca-san_jose-2.svg");
+        let records = [record("Straße", "DE"), record("Strasse", "DE")];
+        let jobs = plan(&records, &[0, 1], &options).unwrap();
+        assert_eq!(jobs[1].file_name, "de-strasse-2.svg");
+
+        for separator in ["", "/", "a:b"] {
+            let options = RenderOptions {
+                separator: Some(String::from(separator)),
+                ..Default::default()
+            };
+            assert!(plan(&records, &[0], &options).is_err());
+        }
+        let options = RenderOptions {
+            field_based_name: Some(vec![String::from("county")]),
+            ..Default::default()
+        };
+        assert!(plan(&records, &[0], &options).is_err());
+
+        let records = [record("Austin", "TX"), record(" ", "TX")];
+        let mut options = RenderOptions {
+            field_based_name: Some(vec![String::from("state"), String::from("city")]),
+            ..Default::default()
+        };
+        let error = plan(&records, &[0, 1], &options).err().unwrap();
+        assert_eq!(error.to_string(), "Record 2");
+        options.on_empty_name = OnEmptyName::Placeholder;
+        let jobs = plan(&records, &[0, 1], &options).unwrap();
+        assert_eq!(jobs[1].file_name, "tx-unnamed.svg");
+        options.on_empty_name = OnEmptyName::Index;
+        let jobs = plan(&records, &[0, 1], &options).unwrap();
+        assert_eq!(jobs[1].file_name, "tx-2.svg");
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_parse_json_columns() {
+        let record = Record::from([
+            (
+                String::from("projects"),
+                String::from(r#"["Loop", "Bridge"]"#),
+            ),
+            (String::from("awards"), String::new()),
+        ]);
+        let job = Job {
+            number: 3,
+            record: &record,
+            variant: Variant::default(),
+            file_name: String::new(),
+            exporter: None,
+        };
+        let columns = [String::from("projects"), String::from("awards")];
+        let mut context = context(&record, &job.variant);
+        parse_json_columns(&mut context, &job, &columns).unwrap();
+        assert_eq!(context["projects"].len(), Some(2));
+        assert_eq!(context["awards"].to_string(), "");
+
+        let record = Record::from([(String::from("projects"), String::from("[Loop"))]);
+        let job = Job {
+            record: &record,
+            ..job
+        };
+        let error = parse_json_columns(&mut context, &job, &columns[..1])
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .starts_with("Invalid JSON in the projects column of record 3"));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_nest_columns() {
+        let record = Record::from([
+            (String::from("city"), String::from("Austin")),
+            (String::from("scores.network"), String::from("3.2")),
+            (String::from("scores.safety.night"), String::from("2")),
+            (String::from("contact.email"), String::from("a@example.org")),
+            (String::from("contact"), String::from("Alex")),
+        ]);
+        let mut context = context(&record, &Variant::default());
+        nest_columns(&mut context);
+        let mut env = Environment::new();
+        env.add_template(
+            "t",
+            "{{ scores.network }} {{ scores.safety.night }} {{ contact }} \
+             {% for name in scores %}{{ name }},{% endfor %}",
+        )
+        .unwrap();
+        assert_eq!(
+            env.get_template("t").unwrap().render(&context).unwrap(),
+            "3.2 2 Alex network,safety,"
+        );
+        assert!(context.contains_key("scores.network"));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_template_source() {
+        let source = crate::source::MemorySource::new()
+            .add("virtual/t.svg", "<svg><text>{{ city }}</text></svg>");
+        let options = RenderOptions {
+            template_source: Some(Arc::new(source)),
+            ..Default::default()
+        };
+        let record = Record::from([(String::from("city"), String::from("Austin"))]);
+        let outputs = render_to_strings(Path::new("virtual/t.svg"), &[record], &options).unwrap();
+        assert_eq!(outputs[0].0, "austin.svg");
+        assert!(outputs[0].1.contains("<text>Austin</text>"));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_streamed_rendering() {
+        let source = crate::source::MemorySource::new().add(
+            "virtual/t.svg",
+            "<svg><text>{{ city }}</text>{{ missing.field }}</svg>",
+        );
+        let mut options = RenderOptions {
+            template_source: Some(Arc::new(source)),
+            ..Default::default()
+        };
+        assert!(streams(&options));
+        let austin = Record::from([(String::from("city"), String::from("Austin"))]);
+        let error = render_to_strings(Path::new("virtual/t.svg"), &[austin], &options)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("Record 1 (city=\"Austin\")"));
+
+        let source = crate::source::MemorySource::new()
+            .add("virtual/t.svg", "<svg><text>{{ city }}</text></svg>");
+        options.template_source = Some(Arc::new(source));
+        let provo = Record::from([(String::from("city"), String::from("Provo"))]);
+        let streamed = render_to_strings(
+            Path::new("virtual/t.svg"),
+            std::slice::from_ref(&provo),
+            &options,
+        )
+        .unwrap();
+        options.large_template = true;
+        assert!(!streams(&options));
+        let buffered = render_to_strings(Path::new("virtual/t.svg"), &[provo], &options).unwrap();
+        assert_eq!(streamed, buffered);
+
+        // The streamed files are validated before being written.
+        let source =
+            crate::source::MemorySource::new().add("virtual/t.svg", "<svg><text>{{ city }}</svg>");
+        let options = RenderOptions {
+            template_source: Some(Arc::new(source)),
+            ..Default::default()
+        };
+        let output_dir = std::env::temp_dir().join("svggloo-test-streamed-validation");
+        let _ = fs::remove_dir_all(&output_dir);
+        let austin = Record::from([(String::from("city"), String::from("Austin"))]);
+        let error = render_records(Path::new("virtual/t.svg"), &[austin], &output_dir, &options)
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("malformed SVG"));
+        assert!(fs::read_dir(&output_dir)
+            .unwrap()
+            .flatten()
+            .all(|entry| !entry.file_name().to_string_lossy().ends_with(".svg")));
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_render_jobs() {
+        let source = crate::source::MemorySource::new().add(
+            "virtual/t.svg",
+            "<svg><text>{{ 100 // id|int }}</text></svg>",
+        );
+        let mut options = RenderOptions {
+            template_source: Some(Arc::new(source)),
+            render_jobs: 4,
+            ..Default::default()
+        };
+        let records: Vec<Record> = (1..=20)
+            .map(|id| Record::from([(String::from("id"), id.to_string())]))
+            .collect();
+        let outputs = render_to_strings(Path::new("virtual/t.svg"), &records, &options).unwrap();
+        let names: Vec<&str> = outputs.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names[..3], ["1.svg", "2.svg", "3.svg"]);
+        assert_eq!(names.len(), 20);
+        assert!(outputs[3].1.contains("<text>25</text>"));
+
+        options.render_jobs = 3;
+        let mut records = records;
+        records[12].insert(String::from("id"), String::from("0"));
+        let error = render_to_strings(Path::new("virtual/t.svg"), &records, &options)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("Record 13 "));
+
+        options.max_outputs = Some(10);
+        let error = render_to_strings(Path::new("virtual/t.svg"), &records, &options)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("The run would produce 20 outputs"));
+
+        // A rejected run leaves the output directory as it was.
+        let output_dir = std::env::temp_dir().join("svggloo-test-max-outputs");
+        let _ = fs::remove_dir_all(&output_dir);
+        fs::create_dir_all(&output_dir).unwrap();
+        let partial = output_dir.join(".1.svggloo-tmp.svg");
+        let journal = output_dir.join(".svggloo-journal");
+        fs::write(&partial, "<svg>").unwrap();
+        fs::write(&journal, "key 1.svg\n").unwrap();
+        let records = &records[..12];
+        let error = render_records(Path::new("virtual/t.svg"), records, &output_dir, &options)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("The run would produce 12 outputs"));
+        assert_eq!(fs::read_to_string(&partial).unwrap(), "<svg>");
+        assert_eq!(fs::read_to_string(&journal).unwrap(), "key 1.svg\n");
+        assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 2);
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_empty_records() {
+        let template = Path::new("virtual/t.svg");
+        let output_dir = std::env::temp_dir().join("svggloo-test-empty-records");
+        let mut options = RenderOptions::default();
+        let error = render_records(template, &[], &output_dir, &options).unwrap_err();
+        assert!(error.to_string().contains("--allow-empty"));
+        options.allow_empty = true;
+        assert!(render_records(template, &[], &output_dir, &options).is_ok());
+        assert!(!output_dir.exists());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_pick() {
+        let source = crate::source::MemorySource::new().add(
+            "virtual/t.svg",
+            "<svg><text>{{ city }} {{ _next.city }}</text></svg>",
+        );
+        let options = RenderOptions {
+            template_source: Some(Arc::new(source)),
+            disambiguate_names: true,
+            expose_neighbors: true,
+            pick: Some(vec![1]),
+            ..Default::default()
+        };
+        let records: Vec<Record> = ["Austin", "Austin", "Provo"]
+            .iter()
+            .map(|city| Record::from([(String::from("city"), city.to_string())]))
+            .collect();
+        let output_dir = std::env::temp_dir().join("svggloo-test-pick");
+        let _ = fs::remove_dir_all(&output_dir);
+        let (summary, result) = render_records_with_summary(
+            Path::new("virtual/t.svg"),
+            &records,
+            &output_dir,
+            &options,
+        );
+        result.unwrap();
+        assert_eq!((summary.rendered, summary.skipped), (1, 2));
+        assert_eq!(
+            fs::read_to_string(output_dir.join("austin-2.svg")).unwrap(),
+            "<svg><text>Austin Provo</text></svg>"
+        );
+        assert!(!output_dir.join("austin.svg").exists());
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_job_key() {
+        let context = Context::from([(String::from("city"), Value::from("Austin"))]);
+        let mut options = RenderOptions::default();
+        let key = job_key("<svg/>", &context, None, &options).unwrap();
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, job_key("<svg/>", &context, None, &options).unwrap());
+        assert_ne!(
+            key,
+            job_key("<svg></svg>", &context, None, &options).unwrap()
+        );
+        options.strip_metadata = true;
+        assert_ne!(key, job_key("<svg/>", &context, None, &options).unwrap());
+    }
+
+    #[test]
+    fn test_configure_env() {
+        let mut options = RenderOptions::default();
+        options.configure_env(|env| {
+            env.add_filter("shout", |value: String| value.to_uppercase() + "!");
+            env.add_global("country", Value::from("US"));
+        });
+        let mut env = environment(&options);
+        env.add_template("t", "{{ city|shout }} {{ country }}")
+            .unwrap();
+        let record = Record::from([(String::from("city"), String::from("Austin"))]);
+        assert_eq!(
+            env.get_template("t").unwrap().render(&record).unwrap(),
+            "AUSTIN! US"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_map_record() {
+        let record = Record::from([
+            (String::from("city"), String::from("Austin")),
+            (String::from("length_km"), String::from("12.5")),
+        ]);
+        let mut context = context(&record, &Variant::default());
+        let mapper = RecordMapper::new(|record: Record| {
+            let miles = record["length_km"].parse::<f64>()? / 1.609;
+            let mapped = BTreeMap::from([
+                ("city", Value::from(record["city"].to_uppercase())),
+                ("length_mi", Value::from((miles * 10.0).round() / 10.0)),
+            ]);
+            Ok(Value::from_serializable(&mapped))
+        });
+        map_record(&mut context, &record, &mapper).unwrap();
+        assert_eq!(context["city"].to_string(), "AUSTIN");
+        assert_eq!(context["length_mi"].to_string(), "7.8");
+        assert!(!context.contains_key("length_km"));
+        assert_eq!(context["_columns"].len(), Some(2));
+
+        let mapper = RecordMapper::new(|_| Ok(Value::from(1)));
+        assert!(map_record(&mut context, &record, &mapper).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_add_related() {
+        let record = |city: &str, state: &str| {
+            Record::from([
+                (String::from("city"), String::from(city)),
+                (String::from("state"), String::from(state)),
+            ])
+        };
+        let records = [
+            record("Austin", "TX"),
+            record("Provo", "UT"),
+            record("Dallas", "TX"),
+        ];
+        let values: Vec<Value> = records.iter().map(Value::from_serializable).collect();
+        let options = RenderOptions {
+            expose_all: true,
+            siblings_by: Some(String::from("state")),
+            expose_neighbors: true,
+            ..Default::default()
+        };
+        let mut context = Context::new();
+        add_related(&mut context, &records, &values, 0, &options);
+        assert_eq!(context["_all"].len(), Some(3));
+        assert_eq!(context["_siblings"], Value::from(vec![values[2].clone()]));
+        assert!(context["_prev"].is_none());
+        assert_eq!(context["_next"], values[1]);
+        add_related(&mut context, &records, &values, 2, &options);
+        assert_eq!(context["_prev"], values[1]);
+        assert!(context["_next"].is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_normalize_name() {
+        // "são_paulo" with a combining tilde.
+        let decomposed = "sa\u{0303}o_paulo";
+        assert_eq!(normalize_name(decomposed, false), "s\u{e3}o_paulo");
+        assert_eq!(normalize_name(decomposed, true), "sao_paulo");
+        assert_eq!(normalize_name("zürich-北京", true), "zurich-bei_jing");
+        assert_eq!(normalize_name("½_mile", true), "1_2_mile");
+        assert_eq!(normalize_name("a∕b⁄c", true), "a_b_c");
+    }
 }
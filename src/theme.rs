@@ -0,0 +1,66 @@
+#[cfg(feature = "native")]
+use color_eyre::{
+    eyre::{eyre, Report},
+    Result,
+};
+use minijinja::Value;
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::{fs, path::Path};
+
+/// A theme variant.
+///
+/// A theme contributes its variables (colors, logo paths, etc.) to the context
+/// of every record. Each record is rendered once per theme.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Name of the theme, used as a suffix for the rendered files.
+    pub name: String,
+    /// Variables added to the context.
+    pub variables: BTreeMap<String, Value>,
+}
+
+#[cfg(feature = "native")]
+impl Theme {
+    /// Load a theme from a YAML file.
+    ///
+    /// The theme is named after the file stem, e.g. `themes/dark.yaml` becomes
+    /// the `dark` theme.
+    pub fn from_path(path: &Path) -> Result<Self, Report> {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| eyre!("Invalid theme file name {}", path.display()))?
+            .to_owned();
+        let source = fs::read_to_string(path)?;
+        let variables: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(&source)
+            .map_err(|e| eyre!("Invalid theme file {}: {}", path.display(), e))?;
+        let variables = variables
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::from_serializable(v)))
+            .collect();
+
+        Ok(Self { name, variables })
+    }
+}
+
+/// Load the themes matching the provided glob patterns.
+///
+/// The themes are returned in the order of the patterns, and alphabetically
+/// within a pattern.
+#[cfg(feature = "native")]
+pub fn load_themes(patterns: &[String]) -> Result<Vec<Theme>, Report> {
+    let mut themes = Vec::new();
+    for pattern in patterns {
+        let mut paths = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
+        if paths.is_empty() {
+            return Err(eyre!("No theme file matches {:?}", pattern));
+        }
+        paths.sort();
+        for path in paths {
+            themes.push(Theme::from_path(&path)?);
+        }
+    }
+
+    Ok(themes)
+}
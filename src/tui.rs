@@ -0,0 +1,285 @@
+use crate::template::{output_names, render_records, Record, RenderOptions};
+use color_eyre::{eyre::Report, Result};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::{io, path::Path};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+    Frame, Terminal,
+};
+
+/// Help line of the interface.
+const HELP: &str = "↑/↓ move · space select · a select all · enter render · q quit";
+
+/// Rendering status of a record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Status {
+    Pending,
+    Rendering,
+    Done,
+    Failed(String),
+}
+
+/// A record listed in the interface.
+#[derive(Debug, Clone)]
+struct Item {
+    /// Index of the record in the data.
+    index: usize,
+    /// Names of the output files of the record.
+    names: Vec<String>,
+    selected: bool,
+    status: Status,
+}
+
+/// State of the interface.
+struct App {
+    items: Vec<Item>,
+    state: TableState,
+    message: String,
+}
+
+impl App {
+    /// List the records with the names of their output files.
+    fn new(records: &[Record], options: &RenderOptions) -> Result<Self, Report> {
+        let mut items: Vec<Item> = (0..records.len())
+            .map(|index| Item {
+                index,
+                names: Vec::new(),
+                selected: false,
+                status: Status::Pending,
+            })
+            .collect();
+        for (index, name) in output_names(records, options)? {
+            items[index].names.push(name);
+        }
+        let mut state = TableState::default();
+        if !items.is_empty() {
+            state.select(Some(0));
+        }
+
+        Ok(Self {
+            items,
+            state,
+            message: String::from(HELP),
+        })
+    }
+
+    /// Move the cursor by `offset` rows, wrapping around.
+    fn step(&mut self, offset: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let len = self.items.len() as isize;
+        let current = self.state.selected().unwrap_or(0) as isize;
+        self.state
+            .select(Some((current + offset).rem_euclid(len) as usize));
+    }
+
+    /// Select or unselect the record under the cursor.
+    fn toggle(&mut self) {
+        if let Some(item) = self.state.selected().map(|i| &mut self.items[i]) {
+            item.selected = !item.selected;
+        }
+    }
+
+    /// Select all the records, or unselect them all when they all are.
+    fn toggle_all(&mut self) {
+        let selected = !self.items.iter().all(|item| item.selected);
+        for item in &mut self.items {
+            item.selected = selected;
+        }
+    }
+
+    /// Positions of the selected records in the list.
+    fn selection(&self) -> Vec<usize> {
+        (0..self.items.len())
+            .filter(|&i| self.items[i].selected)
+            .collect()
+    }
+}
+
+/// Draw the interface.
+fn draw<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(frame.size());
+
+    let rows = app.items.iter().map(|item| {
+        let (status, color) = match &item.status {
+            Status::Pending => (String::new(), Color::Reset),
+            Status::Rendering => (String::from("rendering…"), Color::Yellow),
+            Status::Done => (String::from("done"), Color::Green),
+            Status::Failed(e) => (format!("failed: {}", e), Color::Red),
+        };
+        Row::new(vec![
+            Cell::from(if item.selected { "[x]" } else { "[ ]" }),
+            Cell::from((item.index + 1).to_string()),
+            Cell::from(item.names.join(", ")),
+            Cell::from(status).style(Style::default().fg(color)),
+        ])
+    });
+    let widths = [
+        Constraint::Length(3),
+        Constraint::Length(6),
+        Constraint::Percentage(50),
+        Constraint::Percentage(50),
+    ];
+    let table = Table::new(rows)
+        .header(
+            Row::new(vec!["", "Record", "Output", "Status"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title("svggloo"))
+        .widths(&widths)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, chunks[0], &mut app.state);
+
+    let message =
+        Paragraph::new(app.message.as_str()).block(Block::default().borders(Borders::ALL));
+    frame.render_widget(message, chunks[1]);
+}
+
+/// Render the selected records in a single run, redrawing their status.
+///
+/// The run picks the selected records among all of them, so that their files
+/// are named as listed.
+fn render_selection<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    template: &Path,
+    records: &[Record],
+    output_dir: &Path,
+    options: &RenderOptions,
+) -> Result<(), Report> {
+    let selection = app.selection();
+    if selection.is_empty() {
+        app.message = String::from("No record selected");
+        return Ok(());
+    }
+    for &i in &selection {
+        app.items[i].status = Status::Rendering;
+    }
+    terminal.draw(|frame| draw(frame, app))?;
+    let options = RenderOptions {
+        pick: Some(selection.iter().map(|&i| app.items[i].index).collect()),
+        ..options.clone()
+    };
+    let result = render_records(template, records, output_dir, &options);
+    let status = match &result {
+        Ok(()) => Status::Done,
+        Err(e) => Status::Failed(e.to_string()),
+    };
+    for &i in &selection {
+        app.items[i].status = status.clone();
+    }
+    app.message = match result {
+        Ok(()) => format!("Rendered {} record(s) · {}", selection.len(), HELP),
+        Err(_) => format!("Cannot render {} record(s) · {}", selection.len(), HELP),
+    };
+
+    Ok(())
+}
+
+/// Handle the keys until the user quits.
+fn event_loop<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    template: &Path,
+    records: &[Record],
+    output_dir: &Path,
+    options: &RenderOptions,
+) -> Result<(), Report> {
+    loop {
+        terminal.draw(|frame| draw(frame, app))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => app.step(1),
+            KeyCode::Up | KeyCode::Char('k') => app.step(-1),
+            KeyCode::Char(' ') => app.toggle(),
+            KeyCode::Char('a') => app.toggle_all(),
+            KeyCode::Enter => {
+                render_selection(terminal, app, template, records, output_dir, options)?
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Review the records of a template in a terminal interface, and render the
+/// selected ones.
+///
+/// The records are listed along with the names of their output files, and
+/// their rendering status is updated live. The messages of the renderings are
+/// printed once the interface is closed.
+pub fn review(
+    template: &Path,
+    records: &[Record],
+    output_dir: &Path,
+    options: &RenderOptions,
+) -> Result<(), Report> {
+    let mut app = App::new(records, options)?;
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let (result, messages) = crate::capture(|| {
+        event_loop(
+            &mut terminal,
+            &mut app,
+            template,
+            records,
+            output_dir,
+            options,
+        )
+    });
+
+    // Restore the terminal whatever the outcome.
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    for message in messages {
+        eprintln!("{}", message);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app() {
+        let records: Vec<Record> = ["Austin", "Provo", "Orem"]
+            .iter()
+            .map(|city| Record::from([(String::from("city"), city.to_string())]))
+            .collect();
+        let mut app = App::new(&records, &RenderOptions::default()).unwrap();
+        assert_eq!(app.items[1].names, ["provo.svg"]);
+
+        app.step(-1);
+        app.toggle();
+        app.step(1);
+        app.step(1);
+        app.toggle();
+        assert_eq!(app.selection(), [1, 2]);
+        app.toggle_all();
+        assert_eq!(app.selection(), [0, 1, 2]);
+        app.toggle_all();
+        assert!(app.selection().is_empty());
+    }
+}
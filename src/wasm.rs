@@ -0,0 +1,17 @@
+//! Bindings of the templating core for the browser.
+//!
+//! Build them with `wasm-pack build --no-default-features --features wasm` to
+//! preview the rendered templates in a web page, without any server.
+use crate::template::{record_from_json, render_preview};
+use wasm_bindgen::prelude::*;
+
+/// Render a template with a record, given as a JSON object.
+///
+/// The optional `locale` is exposed to the template as `locale`. The
+/// `map_image` and `icon` functions are not available in the browser.
+#[wasm_bindgen]
+pub fn render(template: &str, record: &str, locale: Option<String>) -> Result<String, JsError> {
+    record_from_json(record)
+        .and_then(|record| render_preview(template, &record, locale))
+        .map_err(|e| JsError::new(&format!("{:#}", e)))
+}